@@ -0,0 +1,149 @@
+//! 示例程序共用的命令行参数类型和解析函数。
+//!
+//! 各示例的 `Cli` 只声明自己的参数，图形后端、适配器偏好、呈现模式、
+//! 窗口大小和日志的解析都放在这里，保证两个程序的行为一致。
+
+use std::io::Write;
+use std::path::Path;
+
+use clap::ValueEnum;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum PowerPreference {
+    Default,
+    Low,
+    High,
+}
+
+impl From<PowerPreference> for wgpu::PowerPreference {
+    fn from(value: PowerPreference) -> Self {
+        match value {
+            PowerPreference::Default => wgpu::PowerPreference::None,
+            PowerPreference::Low => wgpu::PowerPreference::LowPower,
+            PowerPreference::High => wgpu::PowerPreference::HighPerformance,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum PresentMode {
+    /// 垂直同步，所有平台都支持
+    Fifo,
+    /// 垂直同步，迟到的帧立即呈现
+    FifoRelaxed,
+    /// 不等待垂直同步，可能出现撕裂
+    Immediate,
+    /// 垂直同步但不阻塞，只呈现最新的帧
+    Mailbox,
+}
+
+impl From<PresentMode> for wgpu::PresentMode {
+    fn from(value: PresentMode) -> Self {
+        match value {
+            PresentMode::Fifo => wgpu::PresentMode::Fifo,
+            PresentMode::FifoRelaxed => wgpu::PresentMode::FifoRelaxed,
+            PresentMode::Immediate => wgpu::PresentMode::Immediate,
+            PresentMode::Mailbox => wgpu::PresentMode::Mailbox,
+        }
+    }
+}
+
+/// 按日志级别初始化日志，`log_file` 为 `-` 时输出到标准错误，否则追加写入该文件
+pub fn init_logger(log_level: log::LevelFilter, log_file: &Path) -> anyhow::Result<()> {
+    let target = if log_file.as_os_str() == "-" {
+        env_logger::Target::Stderr
+    } else {
+        // 以追加模式创建或打开日志文件
+        let file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(log_file)
+            .map_err(|err| anyhow::anyhow!("无法打开日志文件 {}: {err}", log_file.display()))?;
+        env_logger::Target::Pipe(Box::new(file))
+    };
+
+    env_logger::builder()
+        .format(|buf, record| {
+            writeln!(
+                buf,
+                "[{} {}] {}",
+                record.level(),
+                record.target(), // 这会显示模块路径（如 wgpu_core）
+                record.args()
+            )
+        })
+        .filter_level(log_level)
+        .filter_module("naga", log::LevelFilter::Error)
+        .parse_default_env()
+        .target(target)
+        .init();
+    Ok(())
+}
+
+/// 打印 `backends` 下 `enumerate_adapters` 找到的所有适配器
+pub fn list_adapters(backends: wgpu::Backends) {
+    let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor {
+        backends,
+        ..Default::default()
+    });
+    let adapters = instance.enumerate_adapters(backends);
+    println!("找到 {} 个图形适配器:", adapters.len());
+    for (index, adapter) in adapters.iter().enumerate() {
+        let info = adapter.get_info();
+        println!("{}. {}", index + 1, info.name);
+        println!("   后端: {:?}", info.backend);
+        println!("   设备类型: {:?}", info.device_type);
+        println!("   驱动: {} {}", info.driver, info.driver_info);
+    }
+}
+
+/// `--backend` 的解析器：逗号分隔的后端名称，或 `all`
+pub fn parse_backends(value: &str) -> Result<wgpu::Backends, String> {
+    if value.trim().eq_ignore_ascii_case("all") {
+        return Ok(wgpu::Backends::all());
+    }
+    value
+        .split(',')
+        .try_fold(wgpu::Backends::empty(), |backends, name| {
+            let backend = wgpu::Backends::from_comma_list(name);
+            if backend.is_empty() {
+                Err(format!("未知的图形后端 \"{}\"", name.trim()))
+            } else {
+                Ok(backends | backend)
+            }
+        })
+}
+
+/// `--size` 的解析器：`宽x高`，宽高都需大于 0
+pub fn parse_size(value: &str) -> Result<(u32, u32), String> {
+    let error = || format!("窗口大小应为 宽x高，例如 1280x720，而不是 \"{value}\"");
+    let (width, height) = value.split_once(['x', 'X']).ok_or_else(error)?;
+    match (width.trim().parse(), height.trim().parse()) {
+        (Ok(width), Ok(height)) if width > 0 && height > 0 => Ok((width, height)),
+        _ => Err(error()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backends_are_combined() {
+        assert_eq!(parse_backends("all"), Ok(wgpu::Backends::all()));
+        assert_eq!(
+            parse_backends("vulkan, gl"),
+            Ok(wgpu::Backends::VULKAN | wgpu::Backends::GL)
+        );
+        assert!(parse_backends("vulkan,glide").is_err());
+    }
+
+    #[test]
+    fn sizes_need_two_positive_numbers() {
+        assert_eq!(parse_size("1280x720"), Ok((1280, 720)));
+        assert_eq!(parse_size("800 X 600"), Ok((800, 600)));
+        for value in ["1280", "0x720", "-1x720", "axb"] {
+            assert!(parse_size(value).is_err(), "{value}");
+        }
+    }
+}
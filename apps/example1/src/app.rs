@@ -1,47 +1,129 @@
-use crate::wgpu_ctx::WgpuCtx;
+use crate::camera_controller::CameraMode;
+use crate::cli::Cli;
+use crate::frame_timer::FrameTimer;
+use crate::scene_file::SceneFile;
+use crate::wgpu_ctx::{ContextOptions, WgpuCtx};
 
 use std::sync::Arc;
 use winit::application::ApplicationHandler;
-use winit::event::{ElementState, WindowEvent};
+use winit::dpi::PhysicalSize;
+use winit::event::{DeviceEvent, DeviceId, WindowEvent};
 use winit::event_loop::ActiveEventLoop;
 use winit::keyboard::{KeyCode, PhysicalKey};
-use winit::window::{Window, WindowId};
+use winit::window::{CursorGrabMode, Fullscreen, Window, WindowAttributes, WindowId};
 
-#[derive(Default)]
 pub struct App<'window> {
     window: Option<Arc<Window>>,
     wgpu_ctx: Option<WgpuCtx<'window>>,
+    /// 命令行指定的标题、大小和全屏，在 `resumed` 中创建窗口时使用
+    window_attributes: WindowAttributes,
+    context_options: ContextOptions,
+    /// 开发模式：修改 shader.wgsl 后无需重启即可生效
+    shader_hot_reload: bool,
+    /// 命令行指定的 MSAA 采样数，`None` 时使用场景文件中的设置
+    sample_count: Option<u32>,
+    /// 已校验的场景描述，创建窗口后据此构建场景
+    scene: SceneFile,
+    frame_timer: FrameTimer,
+    /// 创建渲染上下文失败时的错误，事件循环退出后由 `main` 输出
+    error: Option<anyhow::Error>,
 }
 
 impl<'window> App<'window> {
+    /// 指定了 `--fixed-timestep` 时场景模拟按固定步长运行
+    pub fn new(cli: &Cli, scene: SceneFile) -> Self {
+        let mut window_attributes = Window::default_attributes().with_title(&cli.title);
+        if let Some((width, height)) = cli.size {
+            window_attributes = window_attributes.with_inner_size(PhysicalSize::new(width, height));
+        }
+        if cli.fullscreen {
+            window_attributes =
+                window_attributes.with_fullscreen(Some(Fullscreen::Borderless(None)));
+        }
+        Self {
+            window: None,
+            wgpu_ctx: None,
+            window_attributes,
+            context_options: cli.context_options(),
+            shader_hot_reload: cli.hot_reload,
+            sample_count: cli.msaa,
+            scene,
+            frame_timer: FrameTimer::new(cli.fixed_timestep),
+            error: None,
+        }
+    }
+
+    /// 取出导致事件循环提前退出的错误
+    pub fn take_error(&mut self) -> Option<anyhow::Error> {
+        self.error.take()
+    }
+
     /// 请求重绘    
     fn request_redraw(&self) {
         if let Some(window) = self.window.as_ref() {
             window.request_redraw();
         }
     }
+
+    /// 按当前相机模式锁定或释放光标
+    fn update_cursor_grab(&self) {
+        let (Some(window), Some(wgpu_ctx)) = (self.window.as_ref(), self.wgpu_ctx.as_ref()) else {
+            return;
+        };
+        let grab = wgpu_ctx.camera_controller.mode().grabs_cursor();
+        let result = if grab {
+            // 部分平台（如 Windows）不支持 Locked，退而求其次把光标限制在窗口内
+            window
+                .set_cursor_grab(CursorGrabMode::Locked)
+                .or_else(|_| window.set_cursor_grab(CursorGrabMode::Confined))
+        } else {
+            window.set_cursor_grab(CursorGrabMode::None)
+        };
+        if let Err(err) = result {
+            log::warn!("无法设置光标锁定: {err}");
+        }
+        window.set_cursor_visible(!grab);
+    }
 }
 
 impl<'window> ApplicationHandler for App<'window> {
     fn resumed(&mut self, event_loop: &winit::event_loop::ActiveEventLoop) {
         if self.window.is_none() {
-            let win_attr = Window::default_attributes().with_title("title");
-            let window = Arc::new(
-                event_loop
-                    .create_window(win_attr)
-                    .expect("create window err."),
-            );
+            let window = match event_loop.create_window(self.window_attributes.clone()) {
+                Ok(window) => Arc::new(window),
+                Err(err) => {
+                    self.error = Some(anyhow::Error::new(err).context("无法创建窗口"));
+                    event_loop.exit();
+                    return;
+                }
+            };
 
-            let wgpu_ctx = WgpuCtx::new(window.clone());
+            let mut wgpu_ctx = match WgpuCtx::new(window.clone(), &self.scene, self.context_options)
+            {
+                Ok(wgpu_ctx) => wgpu_ctx,
+                Err(err) => {
+                    self.error = Some(err);
+                    event_loop.exit();
+                    return;
+                }
+            };
+            if self.shader_hot_reload {
+                wgpu_ctx.enable_shader_hot_reload();
+            }
+            if let Some(sample_count) = self.sample_count {
+                wgpu_ctx.set_sample_count(sample_count);
+            }
             self.wgpu_ctx = Some(wgpu_ctx);
             self.window = Some(window);
+            // 不把创建窗口和加载资源的时间算进第一帧
+            self.frame_timer.tick();
         }
     }
 
     fn window_event(
         &mut self,
         event_loop: &ActiveEventLoop,
-        window_id: WindowId,
+        _window_id: WindowId,
         event: WindowEvent,
     ) {
         match event {
@@ -53,29 +135,71 @@ impl<'window> ApplicationHandler for App<'window> {
                 if let (Some(window), Some(wgpu_ctx)) =
                     (self.window.as_ref(), self.wgpu_ctx.as_mut())
                 {
+                    let was_minimized = wgpu_ctx.is_minimized();
                     wgpu_ctx.resize(new_size);
-                    window.request_redraw();
+                    // 最小化期间停止请求重绘，恢复后重新开始；最小化的时间不算进下一帧
+                    if !wgpu_ctx.is_minimized() {
+                        if was_minimized {
+                            self.frame_timer.tick();
+                        }
+                        window.request_redraw();
+                    }
                 }
             }
-            WindowEvent::KeyboardInput {
-                event,
-                is_synthetic,
-                ..
-            } => {
-                if event.physical_key == PhysicalKey::Code(KeyCode::Space) {
-                    if event.state.is_pressed() {
-                        println!("space entre");
-                    }
+            WindowEvent::KeyboardInput { event, .. } => {
+                if event.physical_key == PhysicalKey::Code(KeyCode::Space)
+                    && event.state.is_pressed()
+                {
+                    println!("space entre");
+                }
+                let Some(wgpu_ctx) = self.wgpu_ctx.as_mut() else {
+                    return;
+                };
+                let controller = &mut wgpu_ctx.camera_controller;
+                let mode = controller.mode();
+                // Esc 退出第一人称模式，把光标还给用户
+                if event.physical_key == PhysicalKey::Code(KeyCode::Escape)
+                    && event.state.is_pressed()
+                {
+                    controller.set_mode(CameraMode::Orbit);
+                } else if !controller.process_events(&event)
+                    && !wgpu_ctx.tone_mapping.process_events(&event)
+                {
+                    // 相机和色调映射都没用到的按键用来开关后处理效果
+                    wgpu_ctx.post_process.process_events(&event);
+                }
+                if wgpu_ctx.camera_controller.mode() != mode {
+                    self.update_cursor_grab();
+                }
+            }
+            WindowEvent::MouseInput { state, button, .. } => {
+                if let Some(wgpu_ctx) = self.wgpu_ctx.as_mut() {
+                    wgpu_ctx
+                        .camera_controller
+                        .process_mouse_button(button, state);
+                }
+            }
+            WindowEvent::MouseWheel { delta, .. } => {
+                if let Some(wgpu_ctx) = self.wgpu_ctx.as_mut() {
+                    wgpu_ctx.camera_controller.process_scroll(&delta);
                 }
-                self.wgpu_ctx
-                    .as_mut()
-                    .unwrap()
-                    .camera_controller
-                    .process_events(&event);
             }
+            // 切回窗口时重新锁定光标
+            WindowEvent::Focused(true) => self.update_cursor_grab(),
             WindowEvent::RedrawRequested => {
+                if self
+                    .wgpu_ctx
+                    .as_ref()
+                    .is_some_and(|wgpu_ctx| wgpu_ctx.is_minimized())
+                {
+                    return;
+                }
+                let dt = self.frame_timer.tick();
                 if let Some(wgpu_ctx) = self.wgpu_ctx.as_mut() {
-                    wgpu_ctx.update();
+                    for step in self.frame_timer.simulation_steps(dt) {
+                        wgpu_ctx.simulate(step);
+                    }
+                    wgpu_ctx.update(dt);
                     wgpu_ctx.draw();
                 }
                 self.request_redraw();
@@ -84,4 +208,20 @@ impl<'window> ApplicationHandler for App<'window> {
             _ => (),
         }
     }
+
+    // 鼠标视角使用原始的设备位移，光标被锁定在窗口边缘时也能继续转动
+    fn device_event(
+        &mut self,
+        _event_loop: &ActiveEventLoop,
+        _device_id: DeviceId,
+        event: DeviceEvent,
+    ) {
+        if let (DeviceEvent::MouseMotion { delta }, Some(wgpu_ctx)) =
+            (event, self.wgpu_ctx.as_mut())
+        {
+            wgpu_ctx
+                .camera_controller
+                .process_mouse_motion(delta.0, delta.1);
+        }
+    }
 }
@@ -0,0 +1,290 @@
+use wgpu::util::DeviceExt;
+
+use crate::mipmap;
+use crate::postprocess::{self, PostEffect, Stage};
+
+/// 布局与 bloom.wgsl 中的 `BloomUniform` 一致
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct BloomUniform {
+    threshold: f32,
+    knee: f32,
+    intensity: f32,
+    _padding: f32,
+}
+
+/// 泛光，在 HDR 阶段运行。
+///
+/// 先在半分辨率纹理的第 0 级提取亮部，逐级降采样到 `MAX_LEVELS` 级，
+/// 再自下而上升采样、以加法混合叠加到上一级，最后把第 0 级按强度加回原图。
+pub struct Bloom {
+    sampler: wgpu::Sampler,
+    buffer: wgpu::Buffer,
+    source_layout: wgpu::BindGroupLayout,
+    bloom_layout: wgpu::BindGroupLayout,
+    prefilter_pipeline: wgpu::RenderPipeline,
+    downsample_pipeline: wgpu::RenderPipeline,
+    upsample_pipeline: wgpu::RenderPipeline,
+    composite_pipeline: wgpu::RenderPipeline,
+    /// 泛光纹理各级 mipmap 的视图
+    levels: Vec<wgpu::TextureView>,
+    /// 以对应级别为输入的绑定组，供降采样和升采样使用
+    level_bind_groups: Vec<wgpu::BindGroup>,
+    /// 合成通道读取第 0 级
+    bloom_bind_group: wgpu::BindGroup,
+}
+
+impl Bloom {
+    const MAX_LEVELS: u32 = 6;
+    /// 亮度超过 1 的部分才产生泛光，即色调映射前的过曝区域
+    const THRESHOLD: f32 = 1.0;
+    const KNEE: f32 = 0.5;
+    const INTENSITY: f32 = 0.3;
+
+    pub fn new(device: &wgpu::Device, width: u32, height: u32) -> Self {
+        let sampler = postprocess::linear_sampler(device);
+        let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Bloom Buffer"),
+            contents: bytemuck::cast_slice(&[BloomUniform {
+                threshold: Self::THRESHOLD,
+                knee: Self::KNEE,
+                intensity: Self::INTENSITY,
+                _padding: 0.0,
+            }]),
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+
+        let source_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("bloom_source_layout"),
+            entries: &[
+                postprocess::texture_entry(0),
+                postprocess::sampler_entry(1),
+                postprocess::uniform_entry(2),
+            ],
+        });
+        let bloom_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("bloom_texture_layout"),
+            entries: &[postprocess::texture_entry(0)],
+        });
+
+        let shader = postprocess::shader_module(device, "bloom_shader", include_str!("bloom.wgsl"));
+        let format = postprocess::PostProcess::HDR_FORMAT;
+        let pipeline = |label, entry_point, layouts: &[&wgpu::BindGroupLayout], blend| {
+            postprocess::fullscreen_pipeline(
+                device,
+                label,
+                &shader,
+                entry_point,
+                layouts,
+                format,
+                blend,
+            )
+        };
+        let additive = wgpu::BlendComponent {
+            src_factor: wgpu::BlendFactor::One,
+            dst_factor: wgpu::BlendFactor::One,
+            operation: wgpu::BlendOperation::Add,
+        };
+        let prefilter_pipeline =
+            pipeline("bloom_prefilter", "fs_prefilter", &[&source_layout], None);
+        let downsample_pipeline =
+            pipeline("bloom_downsample", "fs_downsample", &[&source_layout], None);
+        let upsample_pipeline = pipeline(
+            "bloom_upsample",
+            "fs_upsample",
+            &[&source_layout],
+            Some(wgpu::BlendState {
+                color: additive,
+                alpha: additive,
+            }),
+        );
+        let composite_pipeline = pipeline(
+            "bloom_composite",
+            "fs_composite",
+            &[&source_layout, &bloom_layout],
+            None,
+        );
+
+        let (levels, level_bind_groups, bloom_bind_group) = Self::create_levels(
+            device,
+            width,
+            height,
+            &source_layout,
+            &bloom_layout,
+            &sampler,
+            &buffer,
+        );
+
+        Self {
+            sampler,
+            buffer,
+            source_layout,
+            bloom_layout,
+            prefilter_pipeline,
+            downsample_pipeline,
+            upsample_pipeline,
+            composite_pipeline,
+            levels,
+            level_bind_groups,
+            bloom_bind_group,
+        }
+    }
+
+    fn create_levels(
+        device: &wgpu::Device,
+        width: u32,
+        height: u32,
+        source_layout: &wgpu::BindGroupLayout,
+        bloom_layout: &wgpu::BindGroupLayout,
+        sampler: &wgpu::Sampler,
+        buffer: &wgpu::Buffer,
+    ) -> (
+        Vec<wgpu::TextureView>,
+        Vec<wgpu::BindGroup>,
+        wgpu::BindGroup,
+    ) {
+        let (width, height) = ((width / 2).max(1), (height / 2).max(1));
+        let level_count = mipmap::mip_level_count(width, height).min(Self::MAX_LEVELS);
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("bloom_texture"),
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: level_count,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: postprocess::PostProcess::HDR_FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+
+        let levels = (0..level_count)
+            .map(|level| {
+                texture.create_view(&wgpu::TextureViewDescriptor {
+                    label: Some("bloom_level"),
+                    base_mip_level: level,
+                    mip_level_count: Some(1),
+                    ..Default::default()
+                })
+            })
+            .collect::<Vec<_>>();
+        let level_bind_groups = levels
+            .iter()
+            .map(|view| Self::source_bind_group(device, source_layout, view, sampler, buffer))
+            .collect();
+        let bloom_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("bloom_texture_bind_group"),
+            layout: bloom_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(&levels[0]),
+            }],
+        });
+        (levels, level_bind_groups, bloom_bind_group)
+    }
+
+    fn source_bind_group(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        view: &wgpu::TextureView,
+        sampler: &wgpu::Sampler,
+        buffer: &wgpu::Buffer,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("bloom_source_bind_group"),
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: buffer.as_entire_binding(),
+                },
+            ],
+        })
+    }
+}
+
+impl PostEffect for Bloom {
+    fn name(&self) -> &'static str {
+        "bloom"
+    }
+
+    fn stage(&self) -> Stage {
+        Stage::Hdr
+    }
+
+    fn resize(&mut self, device: &wgpu::Device, width: u32, height: u32) {
+        (self.levels, self.level_bind_groups, self.bloom_bind_group) = Self::create_levels(
+            device,
+            width,
+            height,
+            &self.source_layout,
+            &self.bloom_layout,
+            &self.sampler,
+            &self.buffer,
+        );
+    }
+
+    fn render(
+        &self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        input: &wgpu::TextureView,
+        output: &wgpu::TextureView,
+    ) {
+        let clear = wgpu::LoadOp::Clear(wgpu::Color::BLACK);
+        let input_bind_group = Self::source_bind_group(
+            device,
+            &self.source_layout,
+            input,
+            &self.sampler,
+            &self.buffer,
+        );
+
+        postprocess::fullscreen_pass(
+            encoder,
+            "bloom_prefilter",
+            &self.levels[0],
+            clear,
+            &self.prefilter_pipeline,
+            &[&input_bind_group],
+        );
+        for level in 1..self.levels.len() {
+            postprocess::fullscreen_pass(
+                encoder,
+                "bloom_downsample",
+                &self.levels[level],
+                clear,
+                &self.downsample_pipeline,
+                &[&self.level_bind_groups[level - 1]],
+            );
+        }
+        for level in (1..self.levels.len()).rev() {
+            postprocess::fullscreen_pass(
+                encoder,
+                "bloom_upsample",
+                &self.levels[level - 1],
+                wgpu::LoadOp::Load,
+                &self.upsample_pipeline,
+                &[&self.level_bind_groups[level]],
+            );
+        }
+        postprocess::fullscreen_pass(
+            encoder,
+            "bloom_composite",
+            output,
+            clear,
+            &self.composite_pipeline,
+            &[&input_bind_group, &self.bloom_bind_group],
+        );
+    }
+}
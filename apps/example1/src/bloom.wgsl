@@ -0,0 +1,84 @@
+// 泛光：提取亮部后逐级降采样，再逐级升采样叠加回来，最后与原图相加
+// 降采样和升采样的滤波核参考 Call of Duty: Advanced Warfare 的做法；顶点着色器见 fullscreen.wgsl
+
+// 与 bloom.rs 中的 `BloomUniform` 一致
+struct BloomUniform {
+    // 亮度超过阈值的部分才会产生泛光
+    threshold: f32,
+    // 阈值附近的柔和过渡宽度
+    knee: f32,
+    intensity: f32,
+    _padding: f32,
+}
+
+@group(0) @binding(0)
+var source_texture: texture_2d<f32>;
+@group(0) @binding(1)
+var source_sampler: sampler;
+@group(0) @binding(2)
+var<uniform> bloom: BloomUniform;
+// 只在合成通道中使用
+@group(1) @binding(0)
+var bloom_texture: texture_2d<f32>;
+
+fn sample_source(uv: vec2f) -> vec3f {
+    return textureSample(source_texture, source_sampler, uv).rgb;
+}
+
+// 13 次采样的降采样，相当于多个 2x2 盒式滤波的加权平均，能抑制闪烁
+fn downsample(uv: vec2f) -> vec3f {
+    let t = 1.0 / vec2f(textureDimensions(source_texture));
+    let a = sample_source(uv + t * vec2f(-2.0, -2.0));
+    let b = sample_source(uv + t * vec2f(0.0, -2.0));
+    let c = sample_source(uv + t * vec2f(2.0, -2.0));
+    let d = sample_source(uv + t * vec2f(-2.0, 0.0));
+    let e = sample_source(uv);
+    let f = sample_source(uv + t * vec2f(2.0, 0.0));
+    let g = sample_source(uv + t * vec2f(-2.0, 2.0));
+    let h = sample_source(uv + t * vec2f(0.0, 2.0));
+    let i = sample_source(uv + t * vec2f(2.0, 2.0));
+    let j = sample_source(uv + t * vec2f(-1.0, -1.0));
+    let k = sample_source(uv + t * vec2f(1.0, -1.0));
+    let l = sample_source(uv + t * vec2f(-1.0, 1.0));
+    let m = sample_source(uv + t * vec2f(1.0, 1.0));
+    return e * 0.125 + (a + c + g + i) * 0.03125 + (b + d + f + h) * 0.0625
+        + (j + k + l + m) * 0.125;
+}
+
+// 带二次曲线过渡的亮度阈值
+fn threshold(color: vec3f) -> vec3f {
+    let brightness = max(color.r, max(color.g, color.b));
+    var soft = clamp(brightness - bloom.threshold + bloom.knee, 0.0, 2.0 * bloom.knee);
+    soft = soft * soft / (4.0 * bloom.knee + 1e-4);
+    let contribution = max(soft, brightness - bloom.threshold) / max(brightness, 1e-4);
+    return color * contribution;
+}
+
+@fragment
+fn fs_prefilter(in: FullscreenOutput) -> @location(0) vec4f {
+    return vec4f(threshold(downsample(in.uv)), 1.0);
+}
+
+@fragment
+fn fs_downsample(in: FullscreenOutput) -> @location(0) vec4f {
+    return vec4f(downsample(in.uv), 1.0);
+}
+
+// 3x3 帐篷滤波升采样，结果以加法混合叠加到上一级
+@fragment
+fn fs_upsample(in: FullscreenOutput) -> @location(0) vec4f {
+    let t = 1.0 / vec2f(textureDimensions(source_texture));
+    var color = sample_source(in.uv) * 4.0;
+    color += (sample_source(in.uv + vec2f(-t.x, 0.0)) + sample_source(in.uv + vec2f(t.x, 0.0))
+        + sample_source(in.uv + vec2f(0.0, -t.y)) + sample_source(in.uv + vec2f(0.0, t.y))) * 2.0;
+    color += sample_source(in.uv - t) + sample_source(in.uv + t)
+        + sample_source(in.uv + vec2f(-t.x, t.y)) + sample_source(in.uv + vec2f(t.x, -t.y));
+    return vec4f(color / 16.0, 1.0);
+}
+
+@fragment
+fn fs_composite(in: FullscreenOutput) -> @location(0) vec4f {
+    let scene = textureSample(source_texture, source_sampler, in.uv);
+    let glow = textureSample(bloom_texture, source_sampler, in.uv).rgb;
+    return vec4f(scene.rgb + glow * bloom.intensity, scene.a);
+}
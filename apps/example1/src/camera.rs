@@ -1,3 +1,5 @@
+use crate::frustum::Frustum;
+
 pub struct Camera {
     pub eye: glam::Vec3,
     pub target: glam::Vec3,
@@ -9,10 +11,20 @@ pub struct Camera {
 }
 
 impl Camera {
+    pub fn build_view_matrix(&self) -> glam::Mat4 {
+        glam::Mat4::look_at_rh(self.eye, self.target, self.up)
+    }
+
+    pub fn build_projection_matrix(&self) -> glam::Mat4 {
+        glam::Mat4::perspective_rh(self.fovy.to_radians(), self.aspect, self.znear, self.zfar)
+    }
+
     pub fn build_view_projection_matrix(&self) -> glam::Mat4 {
-        let view = glam::Mat4::look_at_rh(self.eye, self.target, self.up);
-        let proj =
-            glam::Mat4::perspective_rh(self.fovy.to_radians(), self.aspect, self.znear, self.zfar);
-        return proj * view;
+        self.build_projection_matrix() * self.build_view_matrix()
+    }
+
+    /// 视锥平面，用于剔除画面外的网格和实例
+    pub fn frustum(&self) -> Frustum {
+        Frustum::from_view_projection(self.build_view_projection_matrix())
     }
 }
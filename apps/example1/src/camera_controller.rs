@@ -1,41 +1,106 @@
+use std::time::Duration;
+
 use winit::{
-    event::{ElementState, KeyEvent},
+    dpi::PhysicalPosition,
+    event::{ElementState, KeyEvent, MouseButton, MouseScrollDelta},
     keyboard::{KeyCode, PhysicalKey},
 };
 
 use crate::camera::Camera;
 
+/// 俯仰角限制在 ±90° 以内，避免视线与 `up` 平行时 `look_at` 退化
+const SAFE_FRAC_PI_2: f32 = std::f32::consts::FRAC_PI_2 - 0.0001;
+/// 环绕模式下相机与目标的最近距离
+const MIN_ORBIT_DISTANCE: f32 = 1.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CameraMode {
+    /// 第一人称飞行：鼠标移动控制朝向，WASD 平移，Space/Shift 升降
+    Fps,
+    /// 环绕 `target` 旋转：按住左键拖动旋转，滚轮缩放，WASD 同样可以旋转和缩放
+    Orbit,
+}
+
+impl CameraMode {
+    /// 第一人称模式需要锁定并隐藏光标
+    pub fn grabs_cursor(self) -> bool {
+        self == CameraMode::Fps
+    }
+}
+
 pub struct CameraController {
+    /// 移动速度（单位/秒）
     pub speed: f32,
+    /// 鼠标每移动一个像素转过的弧度
+    pub sensitivity: f32,
+    mode: CameraMode,
     pub is_up_pressed: bool,
     pub is_down_pressed: bool,
     pub is_forward_pressed: bool,
     pub is_backward_pressed: bool,
     pub is_left_pressed: bool,
     pub is_right_pressed: bool,
+    is_dragging: bool,
+    // 两次 update_camera 之间累积的鼠标输入
+    rotate_horizontal: f32,
+    rotate_vertical: f32,
+    scroll: f32,
 }
 
 impl CameraController {
     pub fn new(speed: f32) -> Self {
         Self {
             speed,
+            sensitivity: 0.004,
+            mode: CameraMode::Orbit,
             is_up_pressed: false,
             is_down_pressed: false,
             is_forward_pressed: false,
             is_backward_pressed: false,
             is_left_pressed: false,
             is_right_pressed: false,
+            is_dragging: false,
+            rotate_horizontal: 0.0,
+            rotate_vertical: 0.0,
+            scroll: 0.0,
         }
     }
+
+    pub fn mode(&self) -> CameraMode {
+        self.mode
+    }
+
+    /// 切换相机模式，同时清掉上一模式残留的鼠标输入
+    pub fn set_mode(&mut self, mode: CameraMode) {
+        self.mode = mode;
+        self.is_dragging = false;
+        self.rotate_horizontal = 0.0;
+        self.rotate_vertical = 0.0;
+        self.scroll = 0.0;
+    }
+
     pub fn process_events(&mut self, event: &KeyEvent) -> bool {
         let is_pressed = event.state == ElementState::Pressed;
 
-        if event.physical_key == PhysicalKey::Code(KeyCode::Space) {
-            self.is_up_pressed = is_pressed;
-            return true;
-        }
-
         match event.physical_key {
+            // Tab 在两种模式之间切换
+            PhysicalKey::Code(KeyCode::Tab) => {
+                if is_pressed && !event.repeat {
+                    self.set_mode(match self.mode {
+                        CameraMode::Fps => CameraMode::Orbit,
+                        CameraMode::Orbit => CameraMode::Fps,
+                    });
+                }
+                true
+            }
+            PhysicalKey::Code(KeyCode::Space) => {
+                self.is_up_pressed = is_pressed;
+                true
+            }
+            PhysicalKey::Code(KeyCode::ShiftLeft) => {
+                self.is_down_pressed = is_pressed;
+                true
+            }
             PhysicalKey::Code(KeyCode::KeyW) | PhysicalKey::Code(KeyCode::ArrowUp) => {
                 self.is_forward_pressed = is_pressed;
                 true
@@ -56,32 +121,178 @@ impl CameraController {
         }
     }
 
-    pub fn update_camera(&self, camera: &mut Camera) {
-        let forward = camera.target - camera.eye;
-        let forward_norm = forward.normalize();
-        let forward_mag = forward.length();
+    /// 处理 `DeviceEvent::MouseMotion` 的原始位移；环绕模式下只在拖动时生效
+    pub fn process_mouse_motion(&mut self, dx: f64, dy: f64) {
+        if self.mode == CameraMode::Fps || self.is_dragging {
+            self.rotate_horizontal += dx as f32;
+            self.rotate_vertical += dy as f32;
+        }
+    }
 
-        // 防止摄像机离场景中心太近时出现问题
-        if self.is_forward_pressed && forward_mag > self.speed {
-            camera.eye += forward_norm * self.speed;
+    pub fn process_mouse_button(&mut self, button: MouseButton, state: ElementState) -> bool {
+        if button != MouseButton::Left {
+            return false;
         }
-        if self.is_backward_pressed {
-            camera.eye -= forward_norm * self.speed;
+        self.is_dragging = self.mode == CameraMode::Orbit && state.is_pressed();
+        true
+    }
+
+    pub fn process_scroll(&mut self, delta: &MouseScrollDelta) {
+        self.scroll += match delta {
+            MouseScrollDelta::LineDelta(_, y) => *y,
+            // 触控板按像素滚动，大约 20 像素相当于一行
+            MouseScrollDelta::PixelDelta(PhysicalPosition { y, .. }) => *y as f32 / 20.0,
+        };
+    }
+
+    /// 按键移动按 `dt` 缩放；鼠标位移本身就是这段时间内的累积量，不再乘 `dt`
+    pub fn update_camera(&mut self, camera: &mut Camera, dt: Duration) {
+        let has_input = self.is_up_pressed
+            || self.is_down_pressed
+            || self.is_forward_pressed
+            || self.is_backward_pressed
+            || self.is_left_pressed
+            || self.is_right_pressed
+            || self.rotate_horizontal != 0.0
+            || self.rotate_vertical != 0.0
+            || self.scroll != 0.0;
+        // 没有输入时不做球坐标往返换算，避免误差逐帧累积
+        if has_input {
+            match self.mode {
+                CameraMode::Fps => self.update_fps(camera, dt.as_secs_f32()),
+                CameraMode::Orbit => self.update_orbit(camera, dt.as_secs_f32()),
+            }
         }
+        self.rotate_horizontal = 0.0;
+        self.rotate_vertical = 0.0;
+        self.scroll = 0.0;
+    }
+
+    fn update_fps(&self, camera: &mut Camera, dt: f32) {
+        let (yaw, pitch, distance) = yaw_pitch_distance(camera.target - camera.eye);
+        let yaw = yaw - self.rotate_horizontal * self.sensitivity;
+        let pitch = (pitch - self.rotate_vertical * self.sensitivity)
+            .clamp(-SAFE_FRAC_PI_2, SAFE_FRAC_PI_2);
+        let forward = direction(yaw, pitch);
+        let right = forward.cross(camera.up).normalize();
+
+        let axis = |positive: bool, negative: bool| positive as i32 as f32 - negative as i32 as f32;
+        let movement = forward * axis(self.is_forward_pressed, self.is_backward_pressed)
+            + right * axis(self.is_right_pressed, self.is_left_pressed)
+            + camera.up * axis(self.is_up_pressed, self.is_down_pressed);
+
+        camera.eye += movement.normalize_or_zero() * self.speed * dt;
+        // 保持视线长度不变，切回环绕模式时围绕视线前方的同一点旋转
+        camera.target = camera.eye + forward * distance;
+    }
 
-        let right = forward_norm.cross(camera.up);
+    fn update_orbit(&self, camera: &mut Camera, dt: f32) {
+        let (yaw, pitch, distance) = yaw_pitch_distance(camera.eye - camera.target);
+        // 键盘旋转按弧长换算，使 speed 的含义与第一人称模式大致一致
+        let key_angle = self.speed * dt / distance.max(MIN_ORBIT_DISTANCE);
+        let axis = |positive: bool, negative: bool| positive as i32 as f32 - negative as i32 as f32;
 
-        // 在按下前进或后退键时重做半径计算
-        let forward = camera.target - camera.eye;
-        let forward_mag = forward.length();
+        let yaw = yaw - self.rotate_horizontal * self.sensitivity
+            + axis(self.is_right_pressed, self.is_left_pressed) * key_angle;
+        let pitch = (pitch
+            + self.rotate_vertical * self.sensitivity
+            + axis(self.is_up_pressed, self.is_down_pressed) * key_angle)
+            .clamp(-SAFE_FRAC_PI_2, SAFE_FRAC_PI_2);
+        // 滚轮每一格缩放 10%
+        let distance = (distance * 0.9f32.powf(self.scroll)
+            - axis(self.is_forward_pressed, self.is_backward_pressed) * self.speed * dt)
+            .max(MIN_ORBIT_DISTANCE);
 
-        if self.is_right_pressed {
-            // 重新调整目标和眼睛之间的距离，以便其不发生变化。
-            // 因此，眼睛仍然位于目标和眼睛形成的圆圈上。
-            camera.eye = camera.target - (forward + right * self.speed).normalize() * forward_mag;
+        camera.eye = camera.target + direction(yaw, pitch) * distance;
+    }
+}
+
+/// 把向量分解为偏航角（绕 Y 轴，从 +Z 起算）、俯仰角和长度
+fn yaw_pitch_distance(v: glam::Vec3) -> (f32, f32, f32) {
+    let distance = v.length();
+    let yaw = v.x.atan2(v.z);
+    let pitch = (v.y / distance).clamp(-1.0, 1.0).asin();
+    (yaw, pitch, distance)
+}
+
+fn direction(yaw: f32, pitch: f32) -> glam::Vec3 {
+    let (sin_yaw, cos_yaw) = yaw.sin_cos();
+    let (sin_pitch, cos_pitch) = pitch.sin_cos();
+    glam::Vec3::new(cos_pitch * sin_yaw, sin_pitch, cos_pitch * cos_yaw)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn camera() -> Camera {
+        Camera {
+            eye: glam::Vec3::new(0.0, 0.0, 10.0),
+            target: glam::Vec3::ZERO,
+            up: glam::Vec3::Y,
+            aspect: 1.0,
+            fovy: 45.0,
+            znear: 0.1,
+            zfar: 100.0,
         }
-        if self.is_left_pressed {
-            camera.eye = camera.target - (forward - right * self.speed).normalize() * forward_mag;
+    }
+
+    #[test]
+    fn orbit_keeps_distance_and_zooms_with_scroll() {
+        let mut camera = camera();
+        let mut controller = CameraController::new(0.2);
+        controller.is_dragging = true;
+        controller.process_mouse_motion(200.0, 0.0);
+        controller.update_camera(&mut camera, Duration::from_secs(1));
+        assert!((camera.eye.length() - 10.0).abs() < 1e-4);
+        assert!(camera.eye.x.abs() > 1.0);
+
+        controller.process_scroll(&MouseScrollDelta::LineDelta(0.0, 1.0));
+        controller.update_camera(&mut camera, Duration::from_secs(1));
+        assert!((camera.eye.length() - 9.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn pitch_is_clamped() {
+        for mode in [CameraMode::Fps, CameraMode::Orbit] {
+            let mut camera = camera();
+            let mut controller = CameraController::new(0.2);
+            controller.set_mode(mode);
+            controller.is_dragging = true;
+            controller.process_mouse_motion(0.0, 1e6);
+            controller.update_camera(&mut camera, Duration::from_secs(1));
+
+            let (_, pitch, _) = yaw_pitch_distance(camera.target - camera.eye);
+            assert!(pitch.abs() <= SAFE_FRAC_PI_2 + 1e-4, "{mode:?}: {pitch}");
+            assert!(
+                camera.build_view_projection_matrix().is_finite(),
+                "{mode:?}"
+            );
         }
     }
+
+    #[test]
+    fn fps_moves_along_view_direction() {
+        let mut camera = camera();
+        // 速度按秒计算：2 单位/秒走 0.25 秒
+        let mut controller = CameraController::new(2.0);
+        controller.set_mode(CameraMode::Fps);
+        controller.is_forward_pressed = true;
+        controller.update_camera(&mut camera, Duration::from_millis(250));
+        assert!(camera.eye.abs_diff_eq(glam::Vec3::new(0.0, 0.0, 9.5), 1e-5));
+        assert!(
+            camera
+                .target
+                .abs_diff_eq(glam::Vec3::new(0.0, 0.0, -0.5), 1e-5)
+        );
+    }
+
+    #[test]
+    fn orbit_ignores_motion_without_drag() {
+        let mut camera = camera();
+        let mut controller = CameraController::new(0.2);
+        controller.process_mouse_motion(100.0, 100.0);
+        controller.update_camera(&mut camera, Duration::from_secs(1));
+        assert_eq!(camera.eye, glam::Vec3::new(0.0, 0.0, 10.0));
+    }
 }
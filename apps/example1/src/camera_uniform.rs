@@ -1,23 +1,27 @@
 use crate::camera::Camera;
 
 #[repr(C)]
-//// 此属性标注数据的内存布局兼容 C-ABI，令其可用于着色器
+// 此属性标注数据的内存布局兼容 C-ABI，令其可用于着色器
 // derive 属性自动导入的这些 trait，令其可被存入缓冲区
 #[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct CameraUniform {
     // glam 的数据类型不能直接用于 bytemuck
     // 需要先将 Matrix4 矩阵转为一个 4x4 的浮点数数组
     pub view_proj: [[f32; 4]; 4],
+    // 相机位置，用于计算高光；w 分量仅用于 16 字节对齐
+    pub view_position: [f32; 4],
 }
 
 impl CameraUniform {
     pub fn new() -> Self {
         Self {
             view_proj: glam::Mat4::IDENTITY.to_cols_array_2d(),
+            view_position: [0.0; 4],
         }
     }
 
     pub fn update_view_proj(&mut self, camera: &Camera) {
         self.view_proj = camera.build_view_projection_matrix().to_cols_array_2d();
+        self.view_position = camera.eye.extend(1.0).to_array();
     }
 }
@@ -0,0 +1,198 @@
+use std::path::PathBuf;
+use std::time::Duration;
+
+use app_cli::{parse_backends, parse_size};
+use clap::Parser;
+
+pub use app_cli::{PowerPreference, PresentMode, list_adapters};
+
+use crate::wgpu_ctx::ContextOptions;
+
+/// 实例化立方体场景：阴影、基于图像的光照、视锥剔除和后处理
+#[derive(Debug, Parser)]
+#[command(version)]
+pub struct Cli {
+    /// 图形后端，逗号分隔：vulkan、metal、dx12、gl 或 all
+    #[arg(long, value_name = "BACKENDS", default_value = "all", value_parser = parse_backends)]
+    pub backend: wgpu::Backends,
+
+    /// 适配器的功耗偏好
+    #[arg(long, value_enum, default_value_t = PowerPreference::Default)]
+    pub power: PowerPreference,
+
+    /// 交换链的呈现模式，适配器不支持时退回到 fifo
+    #[arg(long, value_enum, default_value_t = PresentMode::Fifo)]
+    pub present_mode: PresentMode,
+
+    /// 窗口大小，离屏渲染时为输出图像的大小，例如 1280x720
+    #[arg(long, value_name = "WxH", value_parser = parse_size)]
+    pub size: Option<(u32, u32)>,
+
+    /// 以无边框全屏方式打开窗口
+    #[arg(long)]
+    pub fullscreen: bool,
+
+    /// 窗口标题
+    #[arg(long, default_value = "wgpu_01")]
+    pub title: String,
+
+    /// 场景的 MSAA 采样数（1/2/4/8），适配器不支持时退回到更小的可用值；默认使用场景文件中的设置
+    #[arg(long, value_name = "SAMPLES", value_parser = parse_sample_count)]
+    pub msaa: Option<u32>,
+
+    /// RON 或 JSON 格式的场景描述，省略时使用内置的 assets/scene.ron
+    #[arg(long, value_name = "FILE")]
+    pub scene: Option<PathBuf>,
+
+    /// 替换场景中的模型，可重复：NAME=FILE 替换同名模型，只给 FILE 时替换第一个来自文件的模型
+    #[arg(long = "model", value_name = "[NAME=]FILE", value_parser = parse_model_override)]
+    pub models: Vec<ModelOverride>,
+
+    /// 不创建窗口，离屏渲染一帧并保存为 PNG
+    #[arg(long, value_name = "PNG", num_args = 0..=1, default_missing_value = "frame.png")]
+    pub headless: Option<PathBuf>,
+
+    /// 监视 shader.wgsl，保存后自动重建渲染管线
+    #[arg(long)]
+    pub hot_reload: bool,
+
+    /// 场景模拟按固定频率步进，与帧率无关
+    #[arg(long, value_name = "HZ", value_parser = parse_frequency)]
+    pub fixed_timestep: Option<Duration>,
+
+    /// 日志级别：off、error、warn、info、debug 或 trace；RUST_LOG 可进一步按模块调整
+    #[arg(long, value_name = "LEVEL", default_value = "debug")]
+    pub log_level: log::LevelFilter,
+
+    /// 追加写入的日志文件，`-` 表示输出到标准错误
+    #[arg(long, value_name = "FILE", default_value = "my_app.log")]
+    pub log_file: PathBuf,
+
+    /// 列出 `--backend` 下所有可用的图形适配器后退出
+    #[arg(long)]
+    pub list_adapters: bool,
+}
+
+/// `--model` 的值，见 `SceneFile::override_model`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ModelOverride {
+    pub name: Option<String>,
+    pub file: PathBuf,
+}
+
+impl Cli {
+    pub fn context_options(&self) -> ContextOptions {
+        ContextOptions {
+            backends: self.backend,
+            power_preference: self.power.into(),
+            present_mode: self.present_mode.into(),
+        }
+    }
+
+    /// 按 `--log-level` 和 `--log-file` 初始化日志
+    pub fn init_logger(&self) -> anyhow::Result<()> {
+        app_cli::init_logger(self.log_level, &self.log_file)
+    }
+}
+
+fn parse_model_override(value: &str) -> Result<ModelOverride, String> {
+    let (name, file) = match value.split_once('=') {
+        Some((name, file)) => (Some(name.trim().to_string()), file),
+        None => (None, value),
+    };
+    if name.as_deref() == Some("") || file.is_empty() {
+        return Err(format!("模型应为 名字=文件 或 文件，而不是 \"{value}\""));
+    }
+    Ok(ModelOverride {
+        name,
+        file: PathBuf::from(file),
+    })
+}
+
+fn parse_sample_count(value: &str) -> Result<u32, String> {
+    match value.parse() {
+        Ok(count @ (1 | 2 | 4 | 8)) => Ok(count),
+        _ => Err(format!("采样数应为 1、2、4 或 8，而不是 \"{value}\"")),
+    }
+}
+
+fn parse_frequency(value: &str) -> Result<Duration, String> {
+    match value.parse::<f64>() {
+        Ok(hz) if hz > 0.0 && hz.is_finite() => Ok(Duration::from_secs_f64(1.0 / hz)),
+        _ => Err(format!("频率应为正数，而不是 \"{value}\"")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::CommandFactory;
+
+    #[test]
+    fn cli_definition_is_valid() {
+        Cli::command().debug_assert();
+    }
+
+    #[test]
+    fn options_are_parsed() {
+        let cli = Cli::try_parse_from([
+            "wgpu_01",
+            "--backend",
+            "vulkan, gl",
+            "--power",
+            "high",
+            "--size",
+            "1280x720",
+            "--msaa",
+            "2",
+            "--headless",
+            "--log-level",
+            "warn",
+            "--model",
+            "box.glb",
+            "--model",
+            "ground=plane.obj",
+        ])
+        .unwrap();
+        assert_eq!(cli.backend, wgpu::Backends::VULKAN | wgpu::Backends::GL);
+        assert_eq!(cli.power, PowerPreference::High);
+        assert_eq!(cli.size, Some((1280, 720)));
+        assert_eq!(cli.msaa, Some(2));
+        assert_eq!(cli.headless, Some(PathBuf::from("frame.png")));
+        assert_eq!(cli.log_level, log::LevelFilter::Warn);
+        assert_eq!(
+            cli.models,
+            [
+                ModelOverride {
+                    name: None,
+                    file: PathBuf::from("box.glb"),
+                },
+                ModelOverride {
+                    name: Some("ground".to_string()),
+                    file: PathBuf::from("plane.obj"),
+                },
+            ]
+        );
+
+        let cli = Cli::try_parse_from(["wgpu_01"]).unwrap();
+        assert_eq!(cli.backend, wgpu::Backends::all());
+        assert_eq!(cli.headless, None);
+        assert_eq!(cli.log_file, PathBuf::from("my_app.log"));
+    }
+
+    #[test]
+    fn invalid_values_are_rejected() {
+        for args in [
+            ["--backend", "glide"],
+            ["--size", "1280"],
+            ["--size", "0x720"],
+            ["--msaa", "3"],
+            ["--fixed-timestep", "-60"],
+            ["--model", "=cube.obj"],
+            ["--model", "cube="],
+        ] {
+            let result = Cli::try_parse_from(std::iter::once("wgpu_01").chain(args));
+            assert!(result.is_err(), "{args:?}");
+        }
+    }
+}
@@ -0,0 +1,257 @@
+use anyhow::{Context, bail};
+use wgpu::util::DeviceExt;
+
+use crate::postprocess::{self, PostEffect, Stage};
+
+/// 从 `.cube` 文件读取的 3D 查找表，格点按 R 最快、B 最慢的顺序排列
+pub struct Lut {
+    pub size: u32,
+    pub data: Vec<[f32; 3]>,
+}
+
+impl Lut {
+    /// 解析 Adobe/Resolve 的 `.cube` 格式，只支持 [0, 1] 定义域的 3D LUT
+    pub fn parse_cube(text: &str) -> anyhow::Result<Self> {
+        let mut size = None;
+        let mut data = Vec::new();
+        for (line_number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let mut words = line.split_whitespace();
+            let keyword = words.next().unwrap_or_default();
+            match keyword {
+                "LUT_3D_SIZE" => {
+                    let value = words.next().context("LUT_3D_SIZE 缺少数值")?;
+                    size = Some(value.parse::<u32>().context("LUT_3D_SIZE 不是整数")?);
+                }
+                "LUT_1D_SIZE" => bail!("不支持 1D LUT"),
+                "DOMAIN_MIN" | "DOMAIN_MAX" => {
+                    let expected = if keyword == "DOMAIN_MIN" { 0.0 } else { 1.0 };
+                    if words.any(|word| word.parse::<f32>().ok() != Some(expected)) {
+                        bail!("第 {} 行: 只支持 [0, 1] 定义域", line_number + 1);
+                    }
+                }
+                // TITLE 等其余关键字不影响数据
+                _ if keyword.starts_with(|c: char| c.is_ascii_alphabetic()) => {}
+                _ => {
+                    let values = line
+                        .split_whitespace()
+                        .map(str::parse::<f32>)
+                        .collect::<Result<Vec<_>, _>>()
+                        .with_context(|| format!("第 {} 行: 无法解析颜色", line_number + 1))?;
+                    let [r, g, b] = values[..] else {
+                        bail!("第 {} 行: 每行应有 3 个分量", line_number + 1);
+                    };
+                    data.push([r, g, b]);
+                }
+            }
+        }
+
+        let size = size.context("缺少 LUT_3D_SIZE")?;
+        if size < 2 || data.len() != size.pow(3) as usize {
+            bail!("LUT 大小为 {size}，但有 {} 个格点", data.len());
+        }
+        Ok(Self { size, data })
+    }
+}
+
+/// 布局与 color_grading.wgsl 中的 `GradingUniform` 一致
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct GradingUniform {
+    strength: f32,
+    srgb_target: u32,
+    _padding: [u32; 2],
+}
+
+/// 基于 3D LUT 的调色，在 LDR 阶段运行
+pub struct ColorGrading {
+    sampler: wgpu::Sampler,
+    buffer: wgpu::Buffer,
+    lut_view: wgpu::TextureView,
+    bind_group_layout: wgpu::BindGroupLayout,
+    pipeline: wgpu::RenderPipeline,
+}
+
+impl ColorGrading {
+    const STRENGTH: f32 = 1.0;
+
+    /// `format` 为 LDR 阶段的纹理格式，即交换链格式
+    pub fn new(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        format: wgpu::TextureFormat,
+        lut: &Lut,
+    ) -> Self {
+        // LUT 中是显示编码值，8 位精度足够，三线性插值补足格点之间的值
+        let texels = lut
+            .data
+            .iter()
+            .flat_map(|rgb| {
+                let [r, g, b] = rgb.map(|value| (value.clamp(0.0, 1.0) * 255.0).round() as u8);
+                [r, g, b, 255]
+            })
+            .collect::<Vec<_>>();
+        let lut_texture = device.create_texture_with_data(
+            queue,
+            &wgpu::TextureDescriptor {
+                label: Some("color_grading_lut"),
+                size: wgpu::Extent3d {
+                    width: lut.size,
+                    height: lut.size,
+                    depth_or_array_layers: lut.size,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D3,
+                format: wgpu::TextureFormat::Rgba8Unorm,
+                usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+                view_formats: &[],
+            },
+            wgpu::util::TextureDataOrder::LayerMajor,
+            &texels,
+        );
+
+        let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Color Grading Buffer"),
+            contents: bytemuck::cast_slice(&[GradingUniform {
+                strength: Self::STRENGTH,
+                srgb_target: format.is_srgb() as u32,
+                _padding: [0; 2],
+            }]),
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("color_grading_bind_group_layout"),
+            entries: &[
+                postprocess::texture_entry(0),
+                postprocess::sampler_entry(1),
+                postprocess::uniform_entry(2),
+                wgpu::BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D3,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+            ],
+        });
+        let shader = postprocess::shader_module(
+            device,
+            "color_grading_shader",
+            include_str!("color_grading.wgsl"),
+        );
+        let pipeline = postprocess::fullscreen_pipeline(
+            device,
+            "color_grading_pipeline",
+            &shader,
+            "fs_main",
+            &[&bind_group_layout],
+            format,
+            None,
+        );
+
+        Self {
+            sampler: postprocess::linear_sampler(device),
+            buffer,
+            lut_view: lut_texture.create_view(&wgpu::TextureViewDescriptor::default()),
+            bind_group_layout,
+            pipeline,
+        }
+    }
+}
+
+impl PostEffect for ColorGrading {
+    fn name(&self) -> &'static str {
+        "color_grading"
+    }
+
+    fn stage(&self) -> Stage {
+        Stage::Ldr
+    }
+
+    fn render(
+        &self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        input: &wgpu::TextureView,
+        output: &wgpu::TextureView,
+    ) {
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("color_grading_bind_group"),
+            layout: &self.bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(input),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&self.sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: self.buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: wgpu::BindingResource::TextureView(&self.lut_view),
+                },
+            ],
+        });
+        postprocess::fullscreen_pass(
+            encoder,
+            "color_grading_pass",
+            output,
+            wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+            &self.pipeline,
+            &[&bind_group],
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_cube_with_red_varying_fastest() {
+        let text = "\
+# comment
+TITLE \"test\"
+LUT_3D_SIZE 2
+DOMAIN_MIN 0.0 0.0 0.0
+DOMAIN_MAX 1.0 1.0 1.0
+0 0 0
+1 0 0
+0 1 0
+1 1 0
+0 0 1
+1 0 1
+0 1 1
+1 1 1
+";
+        let lut = Lut::parse_cube(text).unwrap();
+        assert_eq!(lut.size, 2);
+        assert_eq!(lut.data[1], [1.0, 0.0, 0.0]);
+        assert_eq!(lut.data[4], [0.0, 0.0, 1.0]);
+    }
+
+    #[test]
+    fn rejects_incomplete_or_1d_luts() {
+        assert!(Lut::parse_cube("LUT_3D_SIZE 2\n0 0 0\n").is_err());
+        assert!(Lut::parse_cube("LUT_1D_SIZE 2\n0 0 0\n1 1 1\n").is_err());
+        assert!(Lut::parse_cube("LUT_3D_SIZE 2\nDOMAIN_MAX 2 2 2\n").is_err());
+    }
+
+    #[test]
+    fn bundled_lut_is_valid() {
+        let text = std::fs::read_to_string(crate::resources::asset_path("grading.cube")).unwrap();
+        assert_eq!(Lut::parse_cube(&text).unwrap().size, 16);
+    }
+}
@@ -0,0 +1,51 @@
+// 基于 3D LUT 的调色：用显示编码后的颜色查表；顶点着色器见 fullscreen.wgsl
+
+// 与 color_grading.rs 中的 `GradingUniform` 一致
+struct GradingUniform {
+    // 0 为原图，1 为完全使用 LUT 的结果
+    strength: f32,
+    // 输入输出纹理是 sRGB 格式时，采样得到的是线性值，需要先编码再查表
+    srgb_target: u32,
+    _padding: vec2u,
+}
+
+@group(0) @binding(0)
+var source_texture: texture_2d<f32>;
+@group(0) @binding(1)
+var source_sampler: sampler;
+@group(0) @binding(2)
+var<uniform> grading: GradingUniform;
+@group(0) @binding(3)
+var lut_texture: texture_3d<f32>;
+
+fn linear_to_srgb(color: vec3f) -> vec3f {
+    let low = color * 12.92;
+    let high = 1.055 * pow(color, vec3f(1.0 / 2.4)) - 0.055;
+    return select(high, low, color <= vec3f(0.0031308));
+}
+
+fn srgb_to_linear(color: vec3f) -> vec3f {
+    let low = color / 12.92;
+    let high = pow((color + 0.055) / 1.055, vec3f(2.4));
+    return select(high, low, color <= vec3f(0.04045));
+}
+
+@fragment
+fn fs_main(in: FullscreenOutput) -> @location(0) vec4f {
+    let color = textureSample(source_texture, source_sampler, in.uv);
+    var encoded = clamp(color.rgb, vec3f(0.0), vec3f(1.0));
+    if grading.srgb_target != 0u {
+        encoded = linear_to_srgb(encoded);
+    }
+
+    // 把 [0, 1] 映射到 LUT 首尾两个格点的中心，三线性插值得到格点之间的值
+    let size = f32(textureDimensions(lut_texture).x);
+    let coord = encoded * ((size - 1.0) / size) + 0.5 / size;
+    let graded = textureSample(lut_texture, source_sampler, coord).rgb;
+
+    var result = mix(encoded, graded, grading.strength);
+    if grading.srgb_target != 0u {
+        result = srgb_to_linear(result);
+    }
+    return vec4f(result, color.a);
+}
@@ -0,0 +1,361 @@
+use anyhow::{Context, bail};
+
+use crate::mipmap;
+
+const KTX2_MAGIC: [u8; 12] = [
+    0xAB, 0x4B, 0x54, 0x58, 0x20, 0x32, 0x30, 0xBB, 0x0D, 0x0A, 0x1A, 0x0A,
+];
+const DDS_MAGIC: [u8; 4] = *b"DDS ";
+
+/// 从 KTX2/DDS 容器中读出的二维纹理，各级 mipmap 按 GPU 上传的布局紧密排列
+pub struct CompressedImage {
+    pub format: wgpu::TextureFormat,
+    pub width: u32,
+    pub height: u32,
+    /// 第 0 级开始的各级 mipmap 数据
+    pub levels: Vec<Vec<u8>>,
+}
+
+/// 根据文件头判断是否为 KTX2 或 DDS 容器
+pub fn is_container(bytes: &[u8]) -> bool {
+    bytes.starts_with(&KTX2_MAGIC) || bytes.starts_with(&DDS_MAGIC)
+}
+
+pub fn parse(bytes: &[u8]) -> anyhow::Result<CompressedImage> {
+    if bytes.starts_with(&KTX2_MAGIC) {
+        parse_ktx2(bytes)
+    } else if bytes.starts_with(&DDS_MAGIC) {
+        parse_dds(bytes)
+    } else {
+        bail!("不是 KTX2 或 DDS 文件")
+    }
+}
+
+/// 设备是否启用了该格式所需的压缩纹理特性（BC、ETC2 或 ASTC）
+pub fn is_supported(device: &wgpu::Device, format: wgpu::TextureFormat) -> bool {
+    device.features().contains(format.required_features())
+}
+
+fn parse_ktx2(bytes: &[u8]) -> anyhow::Result<CompressedImage> {
+    let reader = ktx2::Reader::new(bytes).map_err(|err| anyhow::anyhow!("KTX2 解析失败: {err}"))?;
+    let header = reader.header();
+    if header.supercompression_scheme.is_some() {
+        bail!(
+            "暂不支持带超压缩（{:?}）的 KTX2",
+            header.supercompression_scheme
+        );
+    }
+    if header.pixel_depth > 1 || header.layer_count > 1 || header.face_count > 1 {
+        bail!("只支持二维 KTX2 纹理");
+    }
+    let vk_format = header
+        .format
+        .context("KTX2 格式未定义（Basis Universal 需要先转码）")?;
+    let format =
+        ktx2_format(vk_format).with_context(|| format!("不支持的 KTX2 格式 {vk_format:?}"))?;
+
+    let width = header.pixel_width;
+    let height = header.pixel_height.max(1);
+    let levels = reader
+        .levels()
+        .enumerate()
+        .map(|(level, data)| {
+            let expected = level_byte_len(format, width, height, level as u32);
+            data.data
+                .get(..expected)
+                .map(<[u8]>::to_vec)
+                .with_context(|| format!("KTX2 第 {level} 级 mipmap 数据不完整"))
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    Ok(CompressedImage {
+        format,
+        width,
+        height,
+        levels,
+    })
+}
+
+fn parse_dds(bytes: &[u8]) -> anyhow::Result<CompressedImage> {
+    let dds = ddsfile::Dds::read(bytes).map_err(|err| anyhow::anyhow!("DDS 解析失败: {err}"))?;
+    if dds.get_depth() > 1 || dds.get_num_array_layers() > 1 {
+        bail!("只支持二维 DDS 纹理");
+    }
+    let dxgi_format = dds.get_dxgi_format().context("不支持的 DDS 格式")?;
+    let format =
+        dds_format(dxgi_format).with_context(|| format!("不支持的 DDS 格式 {dxgi_format:?}"))?;
+
+    let width = dds.get_width();
+    let height = dds.get_height();
+    // DDS 中各级 mipmap 首尾相接地存放在同一块数据里
+    let mut data = dds
+        .get_data(0)
+        .map_err(|err| anyhow::anyhow!("DDS 数据读取失败: {err}"))?;
+    let mut levels = Vec::new();
+    for level in 0..dds.get_num_mipmap_levels().max(1) {
+        let len = level_byte_len(format, width, height, level);
+        if data.len() < len {
+            bail!("DDS 第 {level} 级 mipmap 数据不完整");
+        }
+        let (level_data, rest) = data.split_at(len);
+        levels.push(level_data.to_vec());
+        data = rest;
+    }
+
+    Ok(CompressedImage {
+        format,
+        width,
+        height,
+        levels,
+    })
+}
+
+fn ktx2_format(format: ktx2::Format) -> Option<wgpu::TextureFormat> {
+    use ktx2::Format as K;
+    use wgpu::TextureFormat as F;
+    const TABLE: &[(K, F)] = &[
+        (K::R8G8B8A8_UNORM, F::Rgba8Unorm),
+        (K::R8G8B8A8_SRGB, F::Rgba8UnormSrgb),
+        (K::BC1_RGB_UNORM_BLOCK, F::Bc1RgbaUnorm),
+        (K::BC1_RGB_SRGB_BLOCK, F::Bc1RgbaUnormSrgb),
+        (K::BC1_RGBA_UNORM_BLOCK, F::Bc1RgbaUnorm),
+        (K::BC1_RGBA_SRGB_BLOCK, F::Bc1RgbaUnormSrgb),
+        (K::BC2_UNORM_BLOCK, F::Bc2RgbaUnorm),
+        (K::BC2_SRGB_BLOCK, F::Bc2RgbaUnormSrgb),
+        (K::BC3_UNORM_BLOCK, F::Bc3RgbaUnorm),
+        (K::BC3_SRGB_BLOCK, F::Bc3RgbaUnormSrgb),
+        (K::BC4_UNORM_BLOCK, F::Bc4RUnorm),
+        (K::BC5_UNORM_BLOCK, F::Bc5RgUnorm),
+        (K::BC7_UNORM_BLOCK, F::Bc7RgbaUnorm),
+        (K::BC7_SRGB_BLOCK, F::Bc7RgbaUnormSrgb),
+        (K::ETC2_R8G8B8_UNORM_BLOCK, F::Etc2Rgb8Unorm),
+        (K::ETC2_R8G8B8_SRGB_BLOCK, F::Etc2Rgb8UnormSrgb),
+        (K::ETC2_R8G8B8A1_UNORM_BLOCK, F::Etc2Rgb8A1Unorm),
+        (K::ETC2_R8G8B8A1_SRGB_BLOCK, F::Etc2Rgb8A1UnormSrgb),
+        (K::ETC2_R8G8B8A8_UNORM_BLOCK, F::Etc2Rgba8Unorm),
+        (K::ETC2_R8G8B8A8_SRGB_BLOCK, F::Etc2Rgba8UnormSrgb),
+        (K::EAC_R11_UNORM_BLOCK, F::EacR11Unorm),
+        (K::EAC_R11G11_UNORM_BLOCK, F::EacRg11Unorm),
+    ];
+    if let Some((_, format)) = TABLE.iter().find(|(k, _)| *k == format) {
+        return Some(*format);
+    }
+
+    // ASTC 的 LDR 格式在 VkFormat 中按块尺寸依次排列，UNORM 与 SRGB 交替
+    use wgpu::AstcBlock as B;
+    const ASTC_BLOCKS: [B; 14] = [
+        B::B4x4,
+        B::B5x4,
+        B::B5x5,
+        B::B6x5,
+        B::B6x6,
+        B::B8x5,
+        B::B8x6,
+        B::B8x8,
+        B::B10x5,
+        B::B10x6,
+        B::B10x8,
+        B::B10x10,
+        B::B12x10,
+        B::B12x12,
+    ];
+    let offset = format
+        .value()
+        .checked_sub(K::ASTC_4x4_UNORM_BLOCK.value())?;
+    let block = *ASTC_BLOCKS.get(offset as usize / 2)?;
+    let channel = if offset % 2 == 0 {
+        wgpu::AstcChannel::Unorm
+    } else {
+        wgpu::AstcChannel::UnormSrgb
+    };
+    Some(F::Astc { block, channel })
+}
+
+fn dds_format(format: ddsfile::DxgiFormat) -> Option<wgpu::TextureFormat> {
+    use ddsfile::DxgiFormat as D;
+    use wgpu::TextureFormat as F;
+    Some(match format {
+        D::R8G8B8A8_UNorm => F::Rgba8Unorm,
+        D::R8G8B8A8_UNorm_sRGB => F::Rgba8UnormSrgb,
+        D::BC1_UNorm => F::Bc1RgbaUnorm,
+        D::BC1_UNorm_sRGB => F::Bc1RgbaUnormSrgb,
+        D::BC2_UNorm => F::Bc2RgbaUnorm,
+        D::BC2_UNorm_sRGB => F::Bc2RgbaUnormSrgb,
+        D::BC3_UNorm => F::Bc3RgbaUnorm,
+        D::BC3_UNorm_sRGB => F::Bc3RgbaUnormSrgb,
+        D::BC4_UNorm => F::Bc4RUnorm,
+        D::BC5_UNorm => F::Bc5RgUnorm,
+        D::BC7_UNorm => F::Bc7RgbaUnorm,
+        D::BC7_UNorm_sRGB => F::Bc7RgbaUnormSrgb,
+        _ => return None,
+    })
+}
+
+/// 第 `level` 级 mipmap 每行块的字节数和块的行数
+pub fn level_layout(
+    format: wgpu::TextureFormat,
+    width: u32,
+    height: u32,
+    level: u32,
+) -> (u32, u32) {
+    let (width, height) = mipmap::mip_size(width, height, level);
+    let (block_width, block_height) = format.block_dimensions();
+    let block_size = format
+        .block_copy_size(None)
+        .expect("颜色格式都有确定的块大小");
+    (
+        width.div_ceil(block_width) * block_size,
+        height.div_ceil(block_height),
+    )
+}
+
+fn level_byte_len(format: wgpu::TextureFormat, width: u32, height: u32, level: u32) -> usize {
+    let (bytes_per_row, rows) = level_layout(format, width, height, level);
+    (bytes_per_row * rows) as usize
+}
+
+impl CompressedImage {
+    /// 设备不支持压缩格式时在 CPU 上解码为 RGBA8，保留原有的 mipmap 和 sRGB 属性
+    pub fn transcode_rgba8(&self) -> anyhow::Result<CompressedImage> {
+        let format = if self.format.is_srgb() {
+            wgpu::TextureFormat::Rgba8UnormSrgb
+        } else {
+            wgpu::TextureFormat::Rgba8Unorm
+        };
+        let levels = self
+            .levels
+            .iter()
+            .enumerate()
+            .map(|(level, data)| {
+                let (width, height) = mipmap::mip_size(self.width, self.height, level as u32);
+                decode_rgba8(self.format, data, width, height)
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        Ok(CompressedImage {
+            format,
+            width: self.width,
+            height: self.height,
+            levels,
+        })
+    }
+}
+
+fn decode_rgba8(
+    format: wgpu::TextureFormat,
+    data: &[u8],
+    width: u32,
+    height: u32,
+) -> anyhow::Result<Vec<u8>> {
+    use texture2ddecoder as t2d;
+    use wgpu::TextureFormat as F;
+
+    if matches!(format, F::Rgba8Unorm | F::Rgba8UnormSrgb) {
+        return Ok(data.to_vec());
+    }
+
+    let (w, h) = (width as usize, height as usize);
+    let mut pixels = vec![0u32; w * h];
+    match format {
+        F::Bc1RgbaUnorm | F::Bc1RgbaUnormSrgb => t2d::decode_bc1a(data, w, h, &mut pixels),
+        F::Bc2RgbaUnorm | F::Bc2RgbaUnormSrgb => t2d::decode_bc2(data, w, h, &mut pixels),
+        F::Bc3RgbaUnorm | F::Bc3RgbaUnormSrgb => t2d::decode_bc3(data, w, h, &mut pixels),
+        F::Bc4RUnorm => t2d::decode_bc4(data, w, h, &mut pixels),
+        F::Bc5RgUnorm => t2d::decode_bc5(data, w, h, &mut pixels),
+        F::Bc7RgbaUnorm | F::Bc7RgbaUnormSrgb => t2d::decode_bc7(data, w, h, &mut pixels),
+        F::Etc2Rgb8Unorm | F::Etc2Rgb8UnormSrgb => t2d::decode_etc2_rgb(data, w, h, &mut pixels),
+        F::Etc2Rgb8A1Unorm | F::Etc2Rgb8A1UnormSrgb => {
+            t2d::decode_etc2_rgba1(data, w, h, &mut pixels)
+        }
+        F::Etc2Rgba8Unorm | F::Etc2Rgba8UnormSrgb => {
+            t2d::decode_etc2_rgba8(data, w, h, &mut pixels)
+        }
+        F::EacR11Unorm => t2d::decode_eacr(data, w, h, &mut pixels),
+        F::EacRg11Unorm => t2d::decode_eacrg(data, w, h, &mut pixels),
+        F::Astc { .. } => {
+            let (block_width, block_height) = format.block_dimensions();
+            t2d::decode_astc(
+                data,
+                w,
+                h,
+                block_width as usize,
+                block_height as usize,
+                &mut pixels,
+            )
+        }
+        _ => bail!("无法转码 {format:?}"),
+    }
+    .map_err(|err| anyhow::anyhow!("{format:?} 解码失败: {err}"))?;
+
+    // 解码结果按 BGRA 字节序打包在 u32 中
+    Ok(pixels
+        .iter()
+        .flat_map(|pixel| {
+            let [b, g, r, a] = pixel.to_le_bytes();
+            [r, g, b, a]
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn load(file_name: &str) -> CompressedImage {
+        let bytes = std::fs::read(crate::resources::asset_path(file_name)).unwrap();
+        assert!(is_container(&bytes));
+        parse(&bytes).unwrap()
+    }
+
+    #[test]
+    fn ktx2_and_dds_keep_prebuilt_mips() {
+        for file_name in ["checker-bc1.ktx2", "checker-bc1.dds"] {
+            let image = load(file_name);
+            assert_eq!(
+                image.format,
+                wgpu::TextureFormat::Bc1RgbaUnormSrgb,
+                "{file_name}"
+            );
+            assert_eq!((image.width, image.height), (8, 8));
+            // 8x8 有 4 个 BC1 块，之后每级都是一个块
+            let lens = image.levels.iter().map(Vec::len).collect::<Vec<_>>();
+            assert_eq!(lens, [32, 8, 8, 8], "{file_name}");
+        }
+    }
+
+    #[test]
+    fn transcodes_to_rgba8() {
+        let image = load("checker-bc1.ktx2").transcode_rgba8().unwrap();
+        assert_eq!(image.format, wgpu::TextureFormat::Rgba8UnormSrgb);
+        assert_eq!(image.levels[0].len(), 8 * 8 * 4);
+        assert_eq!(image.levels[3].len(), 4);
+
+        let pixel = |level: usize, x: usize, y: usize, width: usize| {
+            let i = (y * width + x) * 4;
+            image.levels[level][i..i + 4].to_vec()
+        };
+        assert_eq!(pixel(0, 0, 0, 8), [255, 0, 0, 255]);
+        assert_eq!(pixel(0, 4, 0, 8), [0, 0, 255, 255]);
+        assert_eq!(pixel(0, 7, 7, 8), [255, 0, 0, 255]);
+        assert_eq!(pixel(3, 0, 0, 1)[1], 0);
+    }
+
+    #[test]
+    fn astc_formats_follow_vk_order() {
+        assert_eq!(
+            ktx2_format(ktx2::Format::ASTC_6x6_SRGB_BLOCK),
+            Some(wgpu::TextureFormat::Astc {
+                block: wgpu::AstcBlock::B6x6,
+                channel: wgpu::AstcChannel::UnormSrgb,
+            })
+        );
+        assert_eq!(
+            ktx2_format(ktx2::Format::ASTC_12x12_UNORM_BLOCK),
+            Some(wgpu::TextureFormat::Astc {
+                block: wgpu::AstcBlock::B12x12,
+                channel: wgpu::AstcChannel::Unorm,
+            })
+        );
+        assert_eq!(ktx2_format(ktx2::Format::ASTC_4x4_SFLOAT_BLOCK), None);
+    }
+}
@@ -0,0 +1,396 @@
+use std::sync::mpsc;
+
+use wgpu::util::{DeviceExt, DrawIndexedIndirectArgs};
+
+use crate::frustum::{BoundingSphere, CullingStats, Frustum};
+use crate::instance::{InstanceManager, InstanceRaw};
+use crate::model::Model;
+
+/// 布局与 culling.wgsl 中的 `CullingUniform` 一致
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct CullingUniform {
+    planes: [[f32; 4]; 6],
+    bounds: [f32; 4],
+    instance_count: u32,
+    _padding: [u32; 3],
+}
+
+/// 在 GPU 上对实例做视锥剔除，结果通过间接绘制使用，绘制不需要等待 CPU 知道可见实例的数量。
+///
+/// 每帧先用计算着色器把可见实例紧密写入 `visible_buffer`，再把计数复制到模型每个网格的
+/// `DrawIndexedIndirectArgs::instance_count`。需要计算着色器和间接绘制，见 `is_supported`。
+///
+/// 可见实例数另外异步读回用于 `CullingStats`，因此统计会比画面晚一到几帧。
+pub struct GpuCulling {
+    pipeline: wgpu::ComputePipeline,
+    bind_group_layout: wgpu::BindGroupLayout,
+    bind_group: wgpu::BindGroup,
+    uniform_buffer: wgpu::Buffer,
+    uniform: CullingUniform,
+    /// 容量与实例缓冲区相同，实例缓冲区扩容时一起重建
+    visible_buffer: wgpu::Buffer,
+    capacity: usize,
+    count_buffer: wgpu::Buffer,
+    /// 模型每个网格一组 `DrawIndexedIndirectArgs`
+    indirect_buffer: wgpu::Buffer,
+    mesh_count: usize,
+    /// 读回可见实例数的暂存缓冲区，映射完成前不会再次写入
+    readback_buffer: wgpu::Buffer,
+    /// 本帧已把计数复制到 `readback_buffer`、提交后需要请求映射
+    readback_copied: bool,
+    /// 已请求映射、等待完成的读回
+    readback: Option<mpsc::Receiver<Result<(), wgpu::BufferAsyncError>>>,
+    /// 复制到 `readback_buffer` 时参与剔除的实例数
+    readback_instance_count: u32,
+    stats: CullingStats,
+}
+
+impl GpuCulling {
+    const WORKGROUP_SIZE: u32 = 64;
+
+    /// 适配器是否支持计算着色器和间接绘制；WebGL2 等不支持时应直接绘制全部实例
+    pub fn is_supported(adapter: &wgpu::Adapter) -> bool {
+        adapter.get_downlevel_capabilities().flags.contains(
+            wgpu::DownlevelFlags::COMPUTE_SHADERS | wgpu::DownlevelFlags::INDIRECT_EXECUTION,
+        )
+    }
+
+    /// 剔除 `instances` 中 `model` 的实例，包围球取自 `Model::bounding_sphere`
+    pub fn new(device: &wgpu::Device, model: &Model, instances: &InstanceManager) -> Self {
+        let BoundingSphere { center, radius } = model.bounding_sphere();
+        let uniform = CullingUniform {
+            planes: [[0.0; 4]; 6],
+            bounds: center.extend(radius).to_array(),
+            instance_count: 0,
+            _padding: [0; 3],
+        };
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Culling Uniform Buffer"),
+            contents: bytemuck::cast_slice(&[uniform]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let count_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Culling Count Buffer"),
+            size: size_of::<u32>() as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::STORAGE
+                | wgpu::BufferUsages::COPY_SRC
+                | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        // 实例数在每帧剔除后写入，其余参数固定
+        let args = model
+            .meshes
+            .iter()
+            .flat_map(|mesh| {
+                DrawIndexedIndirectArgs {
+                    index_count: mesh.index_count,
+                    instance_count: 0,
+                    first_index: 0,
+                    base_vertex: 0,
+                    first_instance: 0,
+                }
+                .as_bytes()
+                .to_vec()
+            })
+            .collect::<Vec<_>>();
+        let indirect_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Culling Indirect Buffer"),
+            contents: &args,
+            usage: wgpu::BufferUsages::INDIRECT | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let storage_entry = |binding, read_only| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage { read_only },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("culling_bind_group_layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                storage_entry(1, true),
+                storage_entry(2, false),
+                storage_entry(3, false),
+            ],
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("culling_pipeline_layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("culling_shader"),
+            source: wgpu::ShaderSource::Wgsl(std::borrow::Cow::Borrowed(include_str!(
+                "culling.wgsl"
+            ))),
+        });
+        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("culling_pipeline"),
+            layout: Some(&pipeline_layout),
+            module: &shader,
+            entry_point: Some("cs_main"),
+            compilation_options: Default::default(),
+            cache: None,
+        });
+
+        let capacity = instances.capacity();
+        let visible_buffer = Self::create_visible_buffer(device, capacity);
+        let bind_group = Self::create_bind_group(
+            device,
+            &bind_group_layout,
+            &uniform_buffer,
+            instances.buffer(),
+            &visible_buffer,
+            &count_buffer,
+        );
+
+        Self {
+            pipeline,
+            bind_group_layout,
+            bind_group,
+            uniform_buffer,
+            uniform,
+            visible_buffer,
+            capacity,
+            count_buffer,
+            indirect_buffer,
+            mesh_count: model.meshes.len(),
+            readback_buffer: device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("Culling Readback Buffer"),
+                size: size_of::<u32>() as wgpu::BufferAddress,
+                usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+                mapped_at_creation: false,
+            }),
+            readback_copied: false,
+            readback: None,
+            readback_instance_count: 0,
+            stats: CullingStats::default(),
+        }
+    }
+
+    fn create_visible_buffer(device: &wgpu::Device, capacity: usize) -> wgpu::Buffer {
+        device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Visible Instance Buffer"),
+            size: (capacity * size_of::<InstanceRaw>()) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::STORAGE,
+            mapped_at_creation: false,
+        })
+    }
+
+    fn create_bind_group(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        uniform_buffer: &wgpu::Buffer,
+        instance_buffer: &wgpu::Buffer,
+        visible_buffer: &wgpu::Buffer,
+        count_buffer: &wgpu::Buffer,
+    ) -> wgpu::BindGroup {
+        let buffers = [
+            uniform_buffer,
+            instance_buffer,
+            visible_buffer,
+            count_buffer,
+        ];
+        let entries = buffers
+            .iter()
+            .enumerate()
+            .map(|(binding, buffer)| wgpu::BindGroupEntry {
+                binding: binding as u32,
+                resource: buffer.as_entire_binding(),
+            })
+            .collect::<Vec<_>>();
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("culling_bind_group"),
+            layout,
+            entries: &entries,
+        })
+    }
+
+    /// 写入本帧的视锥和实例数；需在 `InstanceManager::update` 之后调用，
+    /// 实例缓冲区扩容后这里随之重建输出缓冲区和绑定组
+    pub fn update(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        frustum: &Frustum,
+        instances: &InstanceManager,
+    ) {
+        if instances.capacity() != self.capacity {
+            self.capacity = instances.capacity();
+            self.visible_buffer = Self::create_visible_buffer(device, self.capacity);
+            self.bind_group = Self::create_bind_group(
+                device,
+                &self.bind_group_layout,
+                &self.uniform_buffer,
+                instances.buffer(),
+                &self.visible_buffer,
+                &self.count_buffer,
+            );
+        }
+        self.uniform.planes = frustum.planes.map(|plane| plane.to_array());
+        self.uniform.instance_count = instances.range().end;
+        queue.write_buffer(
+            &self.uniform_buffer,
+            0,
+            bytemuck::cast_slice(&[self.uniform]),
+        );
+    }
+
+    /// 记录剔除计算，需在使用 `indirect_buffer` 的渲染通道之前；
+    /// 提交后调用 `request_readback` 读回本帧的可见实例数
+    pub fn cull(&mut self, encoder: &mut wgpu::CommandEncoder) {
+        encoder.clear_buffer(&self.count_buffer, 0, None);
+        {
+            let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("culling_pass"),
+                timestamp_writes: None,
+            });
+            pass.set_pipeline(&self.pipeline);
+            pass.set_bind_group(0, &self.bind_group, &[]);
+            pass.dispatch_workgroups(
+                self.uniform.instance_count.div_ceil(Self::WORKGROUP_SIZE),
+                1,
+                1,
+            );
+        }
+        // instance_count 是 DrawIndexedIndirectArgs 的第二个字段
+        let args_size = size_of::<DrawIndexedIndirectArgs>() as wgpu::BufferAddress;
+        for mesh in 0..self.mesh_count as wgpu::BufferAddress {
+            encoder.copy_buffer_to_buffer(
+                &self.count_buffer,
+                0,
+                &self.indirect_buffer,
+                mesh * args_size + size_of::<u32>() as wgpu::BufferAddress,
+                size_of::<u32>() as wgpu::BufferAddress,
+            );
+        }
+        // 上一次读回还没完成时跳过，暂存缓冲区映射期间不能写入
+        if self.readback.is_none() {
+            encoder.copy_buffer_to_buffer(
+                &self.count_buffer,
+                0,
+                &self.readback_buffer,
+                0,
+                size_of::<u32>() as wgpu::BufferAddress,
+            );
+            self.readback_copied = true;
+            self.readback_instance_count = self.uniform.instance_count;
+        }
+    }
+
+    /// 在包含 `cull` 的命令提交后请求映射暂存缓冲区，完成后由 `stats` 取用
+    pub fn request_readback(&mut self) {
+        if !std::mem::take(&mut self.readback_copied) {
+            return;
+        }
+        let (sender, receiver) = mpsc::channel();
+        self.readback_buffer
+            .slice(..)
+            .map_async(wgpu::MapMode::Read, move |result| {
+                let _ = sender.send(result);
+            });
+        self.readback = Some(receiver);
+    }
+
+    /// 最近一次读回的剔除统计，不会阻塞；映射在 `Queue::submit` 或 `Device::poll` 时完成
+    pub fn stats(&mut self) -> CullingStats {
+        if let Some(receiver) = &self.readback {
+            match receiver.try_recv() {
+                Ok(Ok(())) => {
+                    let visible: u32 = bytemuck::pod_read_unaligned(
+                        &self.readback_buffer.slice(..).get_mapped_range(),
+                    );
+                    self.readback_buffer.unmap();
+                    self.readback = None;
+                    let culled = self.readback_instance_count.saturating_sub(visible);
+                    let mesh_count = self.mesh_count as u32;
+                    // 整个模型共用一个包围球，网格要么全部绘制，要么全部剔除
+                    let (meshes_drawn, meshes_culled) = if visible > 0 {
+                        (mesh_count, 0)
+                    } else {
+                        (0, mesh_count)
+                    };
+                    self.stats = CullingStats {
+                        meshes_drawn,
+                        meshes_culled,
+                        instances_drawn: visible * mesh_count,
+                        instances_culled: culled * mesh_count,
+                    };
+                }
+                Ok(Err(err)) => {
+                    log::warn!("读回 GPU 剔除结果失败: {err}");
+                    self.readback = None;
+                }
+                Err(mpsc::TryRecvError::Empty) => {}
+                Err(mpsc::TryRecvError::Disconnected) => self.readback = None,
+            }
+        }
+        self.stats
+    }
+
+    /// 剔除后的实例，作为槽位 1 的顶点缓冲区
+    pub fn visible_buffer(&self) -> &wgpu::Buffer {
+        &self.visible_buffer
+    }
+
+    pub fn indirect_buffer(&self) -> &wgpu::Buffer {
+        &self.indirect_buffer
+    }
+
+    /// 读回最近一次剔除后的可见实例数，会阻塞直到 GPU 完成
+    #[cfg(test)]
+    pub fn read_visible_count(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+    ) -> anyhow::Result<u32> {
+        let staging_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("culling_count_staging_buffer"),
+            size: size_of::<u32>() as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+        let mut encoder =
+            device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        encoder.copy_buffer_to_buffer(&self.count_buffer, 0, &staging_buffer, 0, 4);
+        queue.submit(Some(encoder.finish()));
+
+        let buffer_slice = staging_buffer.slice(..);
+        let (sender, receiver) = std::sync::mpsc::channel();
+        buffer_slice.map_async(wgpu::MapMode::Read, move |result| {
+            let _ = sender.send(result);
+        });
+        device.poll(wgpu::PollType::wait_indefinitely())?;
+        receiver.recv()??;
+        let count = bytemuck::pod_read_unaligned(&buffer_slice.get_mapped_range());
+        Ok(count)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn culling_uniform_matches_wgsl() {
+        // 6 个平面、包围球和 16 字节对齐的实例数
+        assert_eq!(size_of::<CullingUniform>(), 128);
+        // culling.wgsl 中的 INSTANCE_FLOATS
+        assert_eq!(size_of::<InstanceRaw>(), 29 * size_of::<f32>());
+    }
+}
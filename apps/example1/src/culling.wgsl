@@ -0,0 +1,60 @@
+// 实例的视锥剔除：每个线程检查一个实例的包围球，可见的实例紧密写入输出缓冲区，
+// 计数随后复制到各网格的间接绘制参数中
+
+// 与 culling.rs 中的 `CullingUniform` 一致
+struct CullingUniform {
+    // 指向视锥内部的平面，xyz 为单位法线，w 为偏移
+    planes: array<vec4f, 6>,
+    // 模型空间的包围球，xyz 为球心，w 为半径
+    bounds: vec4f,
+    instance_count: u32,
+    _padding0: u32,
+    _padding1: u32,
+    _padding2: u32,
+}
+
+// `InstanceRaw` 的大小不是 16 字节的倍数，按 f32 数组读写；需与其字段保持一致
+const INSTANCE_FLOATS: u32 = 29u;
+
+@group(0) @binding(0)
+var<uniform> params: CullingUniform;
+@group(0) @binding(1)
+var<storage, read> instances: array<f32>;
+@group(0) @binding(2)
+var<storage, read_write> visible_instances: array<f32>;
+@group(0) @binding(3)
+var<storage, read_write> visible_count: atomic<u32>;
+
+fn model_column(base: u32, column: u32) -> vec4f {
+    let i = base + column * 4u;
+    return vec4f(instances[i], instances[i + 1u], instances[i + 2u], instances[i + 3u]);
+}
+
+@compute @workgroup_size(64)
+fn cs_main(@builtin(global_invocation_id) id: vec3u) {
+    if id.x >= params.instance_count {
+        return;
+    }
+    let base = id.x * INSTANCE_FLOATS;
+    let model = mat4x4f(
+        model_column(base, 0u),
+        model_column(base, 1u),
+        model_column(base, 2u),
+        model_column(base, 3u),
+    );
+    let center = (model * vec4f(params.bounds.xyz, 1.0)).xyz;
+    // 非等比缩放时按最大的轴缩放半径，结果偏保守
+    let scale = max(length(model[0].xyz), max(length(model[1].xyz), length(model[2].xyz)));
+    let radius = params.bounds.w * scale;
+    for (var i = 0; i < 6; i += 1) {
+        let plane = params.planes[i];
+        if dot(plane.xyz, center) + plane.w < -radius {
+            return;
+        }
+    }
+
+    let slot = atomicAdd(&visible_count, 1u) * INSTANCE_FLOATS;
+    for (var i = 0u; i < INSTANCE_FLOATS; i += 1u) {
+        visible_instances[slot + i] = instances[base + i];
+    }
+}
@@ -0,0 +1,414 @@
+use anyhow::{Context, bail};
+use wgpu::util::DeviceExt;
+
+use crate::mipmap;
+use crate::postprocess::{self, PostProcess};
+
+/// 布局与 environment.wgsl 中的 `FaceUniform` 一致
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct FaceUniform {
+    face: u32,
+    roughness: f32,
+    source_size: f32,
+    _padding: u32,
+}
+
+/// 由等距柱状投影的 HDR 环境图生成的天空盒和基于图像的光照（IBL）。
+///
+/// 创建时在 GPU 上用渲染通道逐面计算一次：先把环境图投影到立方体贴图并生成 mip 链，
+/// 再由它卷积出漫反射辐照度贴图和按粗糙度分级的预滤波镜面反射贴图，
+/// 另外计算与环境无关的 BRDF 查找表。
+pub struct Environment {
+    /// 天空盒采样的立方体贴图，带完整 mip 链
+    pub cube_view: wgpu::TextureView,
+    pub sampler: wgpu::Sampler,
+    pub bind_group_layout: wgpu::BindGroupLayout,
+    /// 辐照度、预滤波贴图和 BRDF 查找表，对应 shader.wgsl 中的第 3 组
+    pub bind_group: wgpu::BindGroup,
+}
+
+impl Environment {
+    const CUBE_SIZE: u32 = 256;
+    const IRRADIANCE_SIZE: u32 = 32;
+    const PREFILTER_SIZE: u32 = 128;
+    /// 预滤波贴图的级数，第 i 级对应粗糙度 i / (级数 - 1)
+    const PREFILTER_LEVELS: u32 = 5;
+    const BRDF_LUT_SIZE: u32 = 128;
+    const BRDF_LUT_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rg16Float;
+
+    /// 解码 `.hdr`、`.exr` 等 image 支持的格式，要求是宽高比 2:1 的等距柱状投影
+    pub fn decode_equirect(bytes: &[u8]) -> anyhow::Result<image::Rgba32FImage> {
+        let img = image::load_from_memory(bytes)
+            .context("无法解码环境贴图")?
+            .into_rgba32f();
+        if img.width() != img.height() * 2 {
+            bail!(
+                "环境贴图应为 2:1 的等距柱状投影，实际为 {}x{}",
+                img.width(),
+                img.height()
+            );
+        }
+        Ok(img)
+    }
+
+    pub fn new(device: &wgpu::Device, queue: &wgpu::Queue, equirect: &image::Rgba32FImage) -> Self {
+        let equirect_texture = device.create_texture_with_data(
+            queue,
+            &wgpu::TextureDescriptor {
+                label: Some("equirect_texture"),
+                size: wgpu::Extent3d {
+                    width: equirect.width(),
+                    height: equirect.height(),
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: wgpu::TextureFormat::Rgba32Float,
+                usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+                view_formats: &[],
+            },
+            wgpu::util::TextureDataOrder::LayerMajor,
+            bytemuck::cast_slice(equirect.as_raw()),
+        );
+        let equirect_view = equirect_texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+        let cube_levels = mipmap::mip_level_count(Self::CUBE_SIZE, Self::CUBE_SIZE);
+        let cube = Self::create_cube(device, "environment_cube", Self::CUBE_SIZE, cube_levels);
+        let irradiance = Self::create_cube(device, "irradiance_cube", Self::IRRADIANCE_SIZE, 1);
+        let prefiltered = Self::create_cube(
+            device,
+            "prefiltered_cube",
+            Self::PREFILTER_SIZE,
+            Self::PREFILTER_LEVELS,
+        );
+        let brdf_lut = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("brdf_lut"),
+            size: wgpu::Extent3d {
+                width: Self::BRDF_LUT_SIZE,
+                height: Self::BRDF_LUT_SIZE,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: Self::BRDF_LUT_FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("environment_sampler"),
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        let equirect_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("equirect_bind_group_layout"),
+            entries: &[
+                postprocess::uniform_entry(0),
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+            ],
+        });
+        let cube_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("environment_source_bind_group_layout"),
+            entries: &[
+                postprocess::uniform_entry(0),
+                Self::cube_entry(2),
+                postprocess::sampler_entry(3),
+            ],
+        });
+
+        let shader = postprocess::shader_module(
+            device,
+            "environment_shader",
+            include_str!("environment.wgsl"),
+        );
+        let pipeline = |label, entry_point, layouts: &[&wgpu::BindGroupLayout], format| {
+            postprocess::fullscreen_pipeline(
+                device,
+                label,
+                &shader,
+                entry_point,
+                layouts,
+                format,
+                None,
+            )
+        };
+        let cube_format = PostProcess::HDR_FORMAT;
+        let equirect_pipeline = pipeline(
+            "equirect_to_cube",
+            "fs_equirect_to_cube",
+            &[&equirect_layout],
+            cube_format,
+        );
+        let downsample_pipeline = pipeline(
+            "environment_downsample",
+            "fs_downsample",
+            &[&cube_layout],
+            cube_format,
+        );
+        let irradiance_pipeline = pipeline(
+            "environment_irradiance",
+            "fs_irradiance",
+            &[&cube_layout],
+            cube_format,
+        );
+        let prefilter_pipeline = pipeline(
+            "environment_prefilter",
+            "fs_prefilter",
+            &[&cube_layout],
+            cube_format,
+        );
+        let brdf_pipeline = pipeline("brdf_lut", "fs_brdf_lut", &[], Self::BRDF_LUT_FORMAT);
+
+        let uniform = |face, roughness, source_size| {
+            device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Environment Face Buffer"),
+                contents: bytemuck::cast_slice(&[FaceUniform {
+                    face,
+                    roughness,
+                    source_size,
+                    _padding: 0,
+                }]),
+                usage: wgpu::BufferUsages::UNIFORM,
+            })
+        };
+        let cube_bind_group = |buffer: &wgpu::Buffer, source: &wgpu::TextureView| {
+            device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("environment_source_bind_group"),
+                layout: &cube_layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: buffer.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 2,
+                        resource: wgpu::BindingResource::TextureView(source),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 3,
+                        resource: wgpu::BindingResource::Sampler(&sampler),
+                    },
+                ],
+            })
+        };
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("environment_encoder"),
+        });
+        let clear = wgpu::LoadOp::Clear(wgpu::Color::BLACK);
+        let source_size = Self::CUBE_SIZE as f32;
+        for face in 0..6 {
+            let buffer = uniform(face, 0.0, source_size);
+            let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("equirect_bind_group"),
+                layout: &equirect_layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: buffer.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::TextureView(&equirect_view),
+                    },
+                ],
+            });
+            postprocess::fullscreen_pass(
+                &mut encoder,
+                "equirect_to_cube",
+                &Self::face_view(&cube, face, 0),
+                clear,
+                &equirect_pipeline,
+                &[&bind_group],
+            );
+        }
+        // 渲染到第 level 级时只采样第 level - 1 级，两者是同一纹理的不同子资源
+        for level in 1..cube_levels {
+            let source = Self::cube_view(&cube, level - 1, 1);
+            for face in 0..6 {
+                let bind_group = cube_bind_group(&uniform(face, 0.0, source_size), &source);
+                postprocess::fullscreen_pass(
+                    &mut encoder,
+                    "environment_downsample",
+                    &Self::face_view(&cube, face, level),
+                    clear,
+                    &downsample_pipeline,
+                    &[&bind_group],
+                );
+            }
+        }
+
+        let cube_view = Self::cube_view(&cube, 0, cube_levels);
+        for face in 0..6 {
+            let bind_group = cube_bind_group(&uniform(face, 0.0, source_size), &cube_view);
+            postprocess::fullscreen_pass(
+                &mut encoder,
+                "environment_irradiance",
+                &Self::face_view(&irradiance, face, 0),
+                clear,
+                &irradiance_pipeline,
+                &[&bind_group],
+            );
+        }
+        for level in 0..Self::PREFILTER_LEVELS {
+            let roughness = level as f32 / (Self::PREFILTER_LEVELS - 1) as f32;
+            for face in 0..6 {
+                let bind_group =
+                    cube_bind_group(&uniform(face, roughness, source_size), &cube_view);
+                postprocess::fullscreen_pass(
+                    &mut encoder,
+                    "environment_prefilter",
+                    &Self::face_view(&prefiltered, face, level),
+                    clear,
+                    &prefilter_pipeline,
+                    &[&bind_group],
+                );
+            }
+        }
+        let brdf_lut_view = brdf_lut.create_view(&wgpu::TextureViewDescriptor::default());
+        postprocess::fullscreen_pass(
+            &mut encoder,
+            "brdf_lut",
+            &brdf_lut_view,
+            clear,
+            &brdf_pipeline,
+            &[],
+        );
+        queue.submit(Some(encoder.finish()));
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("environment_bind_group_layout"),
+            entries: &[
+                Self::cube_entry(0),
+                Self::cube_entry(1),
+                postprocess::texture_entry(2),
+                postprocess::sampler_entry(3),
+            ],
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("environment_bind_group"),
+            layout: &bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&Self::cube_view(
+                        &irradiance,
+                        0,
+                        1,
+                    )),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(&Self::cube_view(
+                        &prefiltered,
+                        0,
+                        Self::PREFILTER_LEVELS,
+                    )),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::TextureView(&brdf_lut_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: wgpu::BindingResource::Sampler(&sampler),
+                },
+            ],
+        });
+
+        Self {
+            cube_view,
+            sampler,
+            bind_group_layout,
+            bind_group,
+        }
+    }
+
+    fn create_cube(device: &wgpu::Device, label: &str, size: u32, levels: u32) -> wgpu::Texture {
+        device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(label),
+            size: wgpu::Extent3d {
+                width: size,
+                height: size,
+                depth_or_array_layers: 6,
+            },
+            mip_level_count: levels,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: PostProcess::HDR_FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        })
+    }
+
+    /// 立方体贴图某一面某一级的二维视图，作为渲染目标
+    fn face_view(texture: &wgpu::Texture, face: u32, level: u32) -> wgpu::TextureView {
+        texture.create_view(&wgpu::TextureViewDescriptor {
+            label: Some("environment_face"),
+            dimension: Some(wgpu::TextureViewDimension::D2),
+            base_mip_level: level,
+            mip_level_count: Some(1),
+            base_array_layer: face,
+            array_layer_count: Some(1),
+            ..Default::default()
+        })
+    }
+
+    fn cube_view(texture: &wgpu::Texture, base_level: u32, levels: u32) -> wgpu::TextureView {
+        texture.create_view(&wgpu::TextureViewDescriptor {
+            label: Some("environment_cube"),
+            dimension: Some(wgpu::TextureViewDimension::Cube),
+            base_mip_level: base_level,
+            mip_level_count: Some(levels),
+            ..Default::default()
+        })
+    }
+
+    pub fn cube_entry(binding: u32) -> wgpu::BindGroupLayoutEntry {
+        wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Texture {
+                sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                view_dimension: wgpu::TextureViewDimension::Cube,
+                multisampled: false,
+            },
+            count: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bundled_sky_is_equirectangular() {
+        let bytes = std::fs::read(crate::resources::asset_path("sky.hdr")).unwrap();
+        let sky = Environment::decode_equirect(&bytes).unwrap();
+        assert_eq!(sky.width(), 2 * sky.height());
+        // 太阳的亮度远超 1，解码后不能被截断
+        assert!(sky.pixels().any(|pixel| pixel[0] > 10.0));
+    }
+
+    #[test]
+    fn rejects_non_equirectangular_images() {
+        let bytes = std::fs::read(crate::resources::asset_path("example-img.png")).unwrap();
+        let err = Environment::decode_equirect(&bytes).unwrap_err();
+        assert!(err.to_string().contains("2:1"), "{err}");
+    }
+}
@@ -0,0 +1,179 @@
+// 由等距柱状投影的 HDR 图预计算天空盒立方体贴图和基于图像的光照（IBL）；
+// 顶点着色器见 fullscreen.wgsl，每次绘制写入立方体贴图某一面的某一级
+
+const PI: f32 = 3.14159265359;
+const IRRADIANCE_SAMPLES: u32 = 256u;
+const PREFILTER_SAMPLES: u32 = 128u;
+const BRDF_SAMPLES: u32 = 256u;
+
+// 与 environment.rs 中的 `FaceUniform` 一致
+struct FaceUniform {
+    // 立方体贴图的面，顺序为 +X -X +Y -Y +Z -Z
+    face: u32,
+    roughness: f32,
+    // 源立方体贴图第 0 级的边长，用于按采样密度选择 mip 级别
+    source_size: f32,
+    _padding: u32,
+}
+
+@group(0) @binding(0)
+var<uniform> params: FaceUniform;
+@group(0) @binding(1)
+var equirect_texture: texture_2d<f32>;
+@group(0) @binding(2)
+var environment_texture: texture_cube<f32>;
+@group(0) @binding(3)
+var environment_sampler: sampler;
+
+// 与 GPU 采样立方体贴图的约定一致，纹理坐标的 v 轴朝下
+fn cube_direction(face: u32, uv: vec2f) -> vec3f {
+    let c = uv * 2.0 - 1.0;
+    switch face {
+        case 0u: { return normalize(vec3f(1.0, -c.y, -c.x)); }
+        case 1u: { return normalize(vec3f(-1.0, -c.y, c.x)); }
+        case 2u: { return normalize(vec3f(c.x, 1.0, c.y)); }
+        case 3u: { return normalize(vec3f(c.x, -1.0, -c.y)); }
+        case 4u: { return normalize(vec3f(c.x, -c.y, 1.0)); }
+        default: { return normalize(vec3f(-c.x, -c.y, -1.0)); }
+    }
+}
+
+// 经度沿 u 从 -X 经 -Z、+X、+Z 回到 -X，纬度沿 v 从天顶到天底
+fn equirect_uv(dir: vec3f) -> vec2f {
+    return vec2f(atan2(dir.z, dir.x) / (2.0 * PI) + 0.5, acos(clamp(dir.y, -1.0, 1.0)) / PI);
+}
+
+// 32 位浮点纹理不一定可过滤，手动做双线性插值：水平方向环绕，竖直方向截断
+fn sample_equirect(uv: vec2f) -> vec3f {
+    let size = vec2i(textureDimensions(equirect_texture));
+    let coord = uv * vec2f(size) - 0.5;
+    let base = vec2i(floor(coord));
+    let t = fract(coord);
+    var texels: array<vec3f, 4>;
+    for (var i = 0; i < 4; i += 1) {
+        let x = ((base.x + (i & 1)) % size.x + size.x) % size.x;
+        let y = clamp(base.y + (i >> 1u), 0, size.y - 1);
+        texels[i] = textureLoad(equirect_texture, vec2i(x, y), 0).rgb;
+    }
+    return mix(mix(texels[0], texels[1], t.x), mix(texels[2], texels[3], t.x), t.y);
+}
+
+fn hammersley(i: u32, count: u32) -> vec2f {
+    return vec2f(f32(i) / f32(count), f32(reverseBits(i)) * 2.3283064365386963e-10);
+}
+
+// 把以 +Z 为法线的切线空间向量变换到以 `n` 为法线的世界空间
+fn tangent_to_world(v: vec3f, n: vec3f) -> vec3f {
+    let up = select(vec3f(1.0, 0.0, 0.0), vec3f(0.0, 0.0, 1.0), abs(n.z) < 0.999);
+    let tangent = normalize(cross(up, n));
+    let bitangent = cross(n, tangent);
+    return tangent * v.x + bitangent * v.y + n * v.z;
+}
+
+// 按 GGX 分布采样半程向量
+fn importance_sample_ggx(xi: vec2f, n: vec3f, roughness: f32) -> vec3f {
+    let a = roughness * roughness;
+    let phi = 2.0 * PI * xi.x;
+    let cos_theta = sqrt((1.0 - xi.y) / (1.0 + (a * a - 1.0) * xi.y));
+    let sin_theta = sqrt(1.0 - cos_theta * cos_theta);
+    return tangent_to_world(vec3f(cos(phi) * sin_theta, sin(phi) * sin_theta, cos_theta), n);
+}
+
+fn distribution_ggx(n_dot_h: f32, roughness: f32) -> f32 {
+    let a = roughness * roughness;
+    let a2 = a * a;
+    let denom = n_dot_h * n_dot_h * (a2 - 1.0) + 1.0;
+    return a2 / (PI * denom * denom);
+}
+
+// 重要性采样的采样数有限，按每个样本覆盖的立体角从更模糊的 mip 级别读取，避免亮点形成噪点
+fn source_lod(pdf: f32, sample_count: u32) -> f32 {
+    let texel_solid_angle = 4.0 * PI / (6.0 * params.source_size * params.source_size);
+    let sample_solid_angle = 1.0 / (f32(sample_count) * pdf + 1e-4);
+    return max(0.5 * log2(sample_solid_angle / texel_solid_angle) + 1.0, 0.0);
+}
+
+@fragment
+fn fs_equirect_to_cube(in: FullscreenOutput) -> @location(0) vec4f {
+    let dir = cube_direction(params.face, in.uv);
+    return vec4f(sample_equirect(equirect_uv(dir)), 1.0);
+}
+
+// 源视图只包含上一级，目标纹素中心的一次双线性采样正好平均 2x2 个源纹素
+@fragment
+fn fs_downsample(in: FullscreenOutput) -> @location(0) vec4f {
+    let dir = cube_direction(params.face, in.uv);
+    return vec4f(textureSampleLevel(environment_texture, environment_sampler, dir, 0.0).rgb, 1.0);
+}
+
+// 余弦加权采样半球，估计值 (1/N)ΣL 即辐照度除以 π，着色时直接乘以反照率
+@fragment
+fn fs_irradiance(in: FullscreenOutput) -> @location(0) vec4f {
+    let n = cube_direction(params.face, in.uv);
+    var sum = vec3f(0.0);
+    for (var i = 0u; i < IRRADIANCE_SAMPLES; i += 1u) {
+        let xi = hammersley(i, IRRADIANCE_SAMPLES);
+        let phi = 2.0 * PI * xi.x;
+        let cos_theta = sqrt(1.0 - xi.y);
+        let sin_theta = sqrt(xi.y);
+        let l = tangent_to_world(vec3f(cos(phi) * sin_theta, sin(phi) * sin_theta, cos_theta), n);
+        let lod = source_lod(cos_theta / PI, IRRADIANCE_SAMPLES);
+        sum += textureSampleLevel(environment_texture, environment_sampler, l, lod).rgb;
+    }
+    return vec4f(sum / f32(IRRADIANCE_SAMPLES), 1.0);
+}
+
+// split-sum 近似的第一项：假设视线方向与法线、反射方向相同，按 GGX 分布卷积环境光
+@fragment
+fn fs_prefilter(in: FullscreenOutput) -> @location(0) vec4f {
+    let n = cube_direction(params.face, in.uv);
+    if params.roughness <= 0.0 {
+        return vec4f(textureSampleLevel(environment_texture, environment_sampler, n, 0.0).rgb, 1.0);
+    }
+
+    var sum = vec3f(0.0);
+    var weight = 0.0;
+    for (var i = 0u; i < PREFILTER_SAMPLES; i += 1u) {
+        let h = importance_sample_ggx(hammersley(i, PREFILTER_SAMPLES), n, params.roughness);
+        let n_dot_h = max(dot(n, h), 0.0);
+        let l = 2.0 * n_dot_h * h - n;
+        let n_dot_l = dot(n, l);
+        if n_dot_l > 0.0 {
+            // v = n 时 pdf = D * (n·h) / (4 * v·h) 化简为 D / 4
+            let pdf = distribution_ggx(n_dot_h, params.roughness) / 4.0;
+            let lod = source_lod(pdf, PREFILTER_SAMPLES);
+            sum += textureSampleLevel(environment_texture, environment_sampler, l, lod).rgb * n_dot_l;
+            weight += n_dot_l;
+        }
+    }
+    return vec4f(sum / max(weight, 1e-4), 1.0);
+}
+
+// split-sum 近似的第二项：u 为 n·v，v 为粗糙度，输出菲涅耳项 F0 的缩放和偏移
+@fragment
+fn fs_brdf_lut(in: FullscreenOutput) -> @location(0) vec4f {
+    let n_dot_v = max(in.uv.x, 1e-4);
+    let roughness = in.uv.y;
+    let n = vec3f(0.0, 0.0, 1.0);
+    let v = vec3f(sqrt(1.0 - n_dot_v * n_dot_v), 0.0, n_dot_v);
+    // 与直接光照不同，IBL 的几何遮蔽项使用 k = r^2 / 2
+    let k = roughness * roughness / 2.0;
+
+    var scale = 0.0;
+    var bias = 0.0;
+    for (var i = 0u; i < BRDF_SAMPLES; i += 1u) {
+        let h = importance_sample_ggx(hammersley(i, BRDF_SAMPLES), n, roughness);
+        let v_dot_h = max(dot(v, h), 0.0);
+        let l = 2.0 * v_dot_h * h - v;
+        let n_dot_l = max(l.z, 0.0);
+        let n_dot_h = max(h.z, 0.0);
+        if n_dot_l > 0.0 {
+            let g = n_dot_v / (n_dot_v * (1.0 - k) + k) * n_dot_l / (n_dot_l * (1.0 - k) + k);
+            let g_vis = g * v_dot_h / (n_dot_h * n_dot_v + 1e-4);
+            let fc = pow(1.0 - v_dot_h, 5.0);
+            scale += (1.0 - fc) * g_vis;
+            bias += fc * g_vis;
+        }
+    }
+    return vec4f(scale, bias, 0.0, 1.0) / vec4f(f32(BRDF_SAMPLES), f32(BRDF_SAMPLES), 1.0, 1.0);
+}
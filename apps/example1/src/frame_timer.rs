@@ -0,0 +1,101 @@
+use std::time::{Duration, Instant};
+
+/// 单帧时间的上限：窗口被拖动或断点暂停后，不让一帧的更新跨度过大
+const MAX_FRAME_TIME: Duration = Duration::from_millis(250);
+
+/// 测量两帧之间的时间，并可选地把模拟切分为固定时长的步进。
+///
+/// 相机等交互逻辑直接使用可变的帧间隔；光源动画等模拟逻辑通过
+/// `simulation_steps` 运行，开启固定步长后结果不再依赖帧率。
+pub struct FrameTimer {
+    last_frame: Instant,
+    fixed_timestep: Option<Duration>,
+    accumulator: Duration,
+}
+
+impl FrameTimer {
+    pub fn new(fixed_timestep: Option<Duration>) -> Self {
+        Self {
+            last_frame: Instant::now(),
+            fixed_timestep: fixed_timestep.filter(|step| !step.is_zero()),
+            accumulator: Duration::ZERO,
+        }
+    }
+
+    /// 返回距上一次调用经过的时间
+    pub fn tick(&mut self) -> Duration {
+        let now = Instant::now();
+        let dt = now.duration_since(self.last_frame).min(MAX_FRAME_TIME);
+        self.last_frame = now;
+        dt
+    }
+
+    /// 本帧需要运行的模拟步进。
+    ///
+    /// 未开启固定步长时只有一步，时长为 `dt`；开启后把 `dt` 累积起来，
+    /// 按固定步长切分，不足一步的余量留到下一帧。
+    pub fn simulation_steps(&mut self, dt: Duration) -> SimulationSteps {
+        let Some(step) = self.fixed_timestep else {
+            return SimulationSteps {
+                step: dt,
+                remaining: 1,
+            };
+        };
+
+        self.accumulator = (self.accumulator + dt).min(MAX_FRAME_TIME);
+        let mut remaining = 0;
+        while self.accumulator >= step {
+            self.accumulator -= step;
+            remaining += 1;
+        }
+        SimulationSteps { step, remaining }
+    }
+}
+
+/// `FrameTimer::simulation_steps` 返回的迭代器，每一项是一步的时长
+pub struct SimulationSteps {
+    step: Duration,
+    remaining: u32,
+}
+
+impl Iterator for SimulationSteps {
+    type Item = Duration;
+
+    fn next(&mut self) -> Option<Duration> {
+        if self.remaining == 0 {
+            return None;
+        }
+        self.remaining -= 1;
+        Some(self.step)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    #[test]
+    fn variable_step_runs_once_per_frame() {
+        let mut timer = FrameTimer::new(None);
+        assert_eq!(timer.simulation_steps(ms(7)).collect::<Vec<_>>(), [ms(7)]);
+    }
+
+    #[test]
+    fn fixed_step_carries_remainder() {
+        let mut timer = FrameTimer::new(Some(ms(10)));
+        assert_eq!(timer.simulation_steps(ms(25)).count(), 2);
+        // 上一帧剩下的 5ms 加上本帧的 6ms 凑够一步
+        assert_eq!(timer.simulation_steps(ms(6)).collect::<Vec<_>>(), [ms(10)]);
+        assert_eq!(timer.simulation_steps(ms(3)).count(), 0);
+    }
+
+    #[test]
+    fn long_frames_are_clamped() {
+        let mut timer = FrameTimer::new(Some(ms(10)));
+        assert_eq!(timer.simulation_steps(Duration::from_secs(5)).count(), 25);
+    }
+}
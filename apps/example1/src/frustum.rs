@@ -0,0 +1,278 @@
+use core::ops::Range;
+
+use glam::{Mat4, Vec3, Vec4, Vec4Swizzles};
+
+/// 轴对齐包围盒
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Aabb {
+    pub min: Vec3,
+    pub max: Vec3,
+}
+
+impl Aabb {
+    /// 点集的包围盒；点集为空时退化为原点
+    pub fn from_points(points: impl Iterator<Item = Vec3>) -> Self {
+        let (min, max) = points.fold((Vec3::INFINITY, Vec3::NEG_INFINITY), |(min, max), p| {
+            (min.min(p), max.max(p))
+        });
+        if min.cmpgt(max).any() {
+            return Self {
+                min: Vec3::ZERO,
+                max: Vec3::ZERO,
+            };
+        }
+        Self { min, max }
+    }
+
+    pub fn center(&self) -> Vec3 {
+        (self.min + self.max) * 0.5
+    }
+
+    /// 变换后的包围盒仍与坐标轴对齐，包住旋转后的整个盒子
+    pub fn transformed(&self, matrix: &Mat4) -> Self {
+        let center = matrix.transform_point3(self.center());
+        let half_extent = glam::Mat3::from_mat4(*matrix).abs() * ((self.max - self.min) * 0.5);
+        Self {
+            min: center - half_extent,
+            max: center + half_extent,
+        }
+    }
+}
+
+/// 包围球，用于视锥剔除
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BoundingSphere {
+    pub center: Vec3,
+    pub radius: f32,
+}
+
+impl BoundingSphere {
+    /// 以点集包围盒的中心为球心、到最远点的距离为半径；点集为空时得到原点处半径为 0 的球
+    pub fn from_points(points: impl Iterator<Item = Vec3> + Clone) -> Self {
+        let center = Aabb::from_points(points.clone()).center();
+        let radius = points.map(|p| p.distance(center)).fold(0.0, f32::max);
+        Self { center, radius }
+    }
+
+    /// 非等比缩放时按最大的轴缩放半径，结果偏保守
+    pub fn transformed(&self, matrix: &Mat4) -> Self {
+        let scale = [matrix.x_axis, matrix.y_axis, matrix.z_axis]
+            .map(|axis| axis.xyz().length())
+            .into_iter()
+            .fold(0.0, f32::max);
+        Self {
+            center: matrix.transform_point3(self.center),
+            radius: self.radius * scale,
+        }
+    }
+
+    /// 同时包围两个球的最小球
+    pub fn union(self, other: Self) -> Self {
+        let distance = self.center.distance(other.center);
+        if distance + other.radius <= self.radius {
+            return self;
+        }
+        if distance + self.radius <= other.radius {
+            return other;
+        }
+        let radius = (distance + self.radius + other.radius) * 0.5;
+        let center =
+            self.center + (other.center - self.center) * ((radius - self.radius) / distance);
+        Self { center, radius }
+    }
+}
+
+/// 相机视锥的 6 个平面，顺序为左、右、下、上、近、远。
+///
+/// 每个平面的 xyz 为指向视锥内部的单位法线，w 为偏移，`dot(n, p) + w >= 0` 的点在平面内侧。
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Frustum {
+    pub planes: [Vec4; 6],
+}
+
+impl Frustum {
+    /// 从视图投影矩阵提取平面（Gribb-Hartmann 方法），裁剪空间深度范围为 wgpu 的 0..1
+    pub fn from_view_projection(view_proj: Mat4) -> Self {
+        let [x, y, z, w] = [0, 1, 2, 3].map(|i| view_proj.row(i));
+        let planes =
+            [w + x, w - x, w + y, w - y, z, w - z].map(|plane| plane / plane.xyz().length());
+        Self { planes }
+    }
+
+    /// 球与视锥相交或在视锥内时返回 `true`；靠近视锥角落的球可能被误判为可见，但不会被误剔除
+    pub fn intersects_sphere(&self, sphere: &BoundingSphere) -> bool {
+        self.planes
+            .iter()
+            .all(|plane| plane.xyz().dot(sphere.center) + plane.w >= -sphere.radius)
+    }
+
+    /// 对每个平面检查包围盒沿法线方向最靠内的顶点，与 `intersects_sphere` 一样偏保守
+    pub fn intersects_aabb(&self, aabb: &Aabb) -> bool {
+        self.planes.iter().all(|plane| {
+            let normal = plane.xyz();
+            let corner = Vec3::select(normal.cmpge(Vec3::ZERO), aabb.max, aabb.min);
+            normal.dot(corner) + plane.w >= 0.0
+        })
+    }
+
+    /// 模型空间的包围体经 `matrix` 变换后是否可见：先用包围球快速排除，再用更紧的包围盒检查
+    pub fn intersects_bounds(&self, matrix: &Mat4, sphere: &BoundingSphere, aabb: &Aabb) -> bool {
+        self.intersects_sphere(&sphere.transformed(matrix))
+            && self.intersects_aabb(&aabb.transformed(matrix))
+    }
+
+    /// 可见实例的下标，相邻的合并为一个区间，每个区间对应一次绘制调用
+    pub fn visible_ranges(
+        &self,
+        matrices: &[Mat4],
+        sphere: &BoundingSphere,
+        aabb: &Aabb,
+    ) -> Vec<Range<u32>> {
+        let mut ranges: Vec<Range<u32>> = Vec::new();
+        for (i, matrix) in matrices.iter().enumerate() {
+            if !self.intersects_bounds(matrix, sphere, aabb) {
+                continue;
+            }
+            let i = i as u32;
+            match ranges.last_mut() {
+                Some(range) if range.end == i => range.end += 1,
+                _ => ranges.push(i..i + 1),
+            }
+        }
+        ranges
+    }
+}
+
+/// 视锥剔除的统计，每帧重新计数；GPU 剔除的模型取 `GpuCulling::stats` 异步读回的结果。
+///
+/// 网格的每个实例分别计数，例如 3 个网格、10 个实例的模型共计 30 个网格实例；
+/// 所有实例都被剔除的网格不会产生绘制调用，计入 `meshes_culled`。
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct CullingStats {
+    pub meshes_drawn: u32,
+    pub meshes_culled: u32,
+    pub instances_drawn: u32,
+    pub instances_culled: u32,
+}
+
+impl std::ops::AddAssign for CullingStats {
+    fn add_assign(&mut self, other: Self) {
+        self.meshes_drawn += other.meshes_drawn;
+        self.meshes_culled += other.meshes_culled;
+        self.instances_drawn += other.instances_drawn;
+        self.instances_culled += other.instances_culled;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::camera::Camera;
+
+    fn camera() -> Camera {
+        Camera {
+            eye: Vec3::new(0.0, 0.0, 10.0),
+            target: Vec3::ZERO,
+            up: Vec3::Y,
+            aspect: 1.0,
+            fovy: 90.0,
+            znear: 0.1,
+            zfar: 100.0,
+        }
+    }
+
+    fn sphere(x: f32, y: f32, z: f32, radius: f32) -> BoundingSphere {
+        BoundingSphere {
+            center: Vec3::new(x, y, z),
+            radius,
+        }
+    }
+
+    #[test]
+    fn spheres_outside_each_plane_are_culled() {
+        let frustum = camera().frustum();
+        assert!(frustum.intersects_sphere(&sphere(0.0, 0.0, 0.0, 1.0)));
+        // 90° 视角下距离相机 10 处的视锥半宽为 10
+        assert!(!frustum.intersects_sphere(&sphere(-12.0, 0.0, 0.0, 1.0)));
+        assert!(!frustum.intersects_sphere(&sphere(12.0, 0.0, 0.0, 1.0)));
+        assert!(!frustum.intersects_sphere(&sphere(0.0, -12.0, 0.0, 1.0)));
+        assert!(!frustum.intersects_sphere(&sphere(0.0, 12.0, 0.0, 1.0)));
+        assert!(!frustum.intersects_sphere(&sphere(0.0, 0.0, 11.0, 0.5)));
+        assert!(!frustum.intersects_sphere(&sphere(0.0, 0.0, -95.0, 1.0)));
+        // 球心在视锥外，但球与平面相交
+        assert!(frustum.intersects_sphere(&sphere(-10.5, 0.0, 0.0, 1.0)));
+        assert!(frustum.intersects_sphere(&sphere(0.0, 0.0, -90.5, 1.0)));
+    }
+
+    #[test]
+    fn aabbs_are_tested_by_their_nearest_corner() {
+        let frustum = camera().frustum();
+        let aabb = |min: Vec3, max: Vec3| Aabb { min, max };
+        assert!(frustum.intersects_aabb(&aabb(Vec3::splat(-1.0), Vec3::splat(1.0))));
+        assert!(!frustum.intersects_aabb(&aabb(
+            Vec3::new(-14.0, -1.0, -1.0),
+            Vec3::new(-12.0, 1.0, 1.0)
+        )));
+        // 只有一角伸进视锥
+        assert!(frustum.intersects_aabb(&aabb(
+            Vec3::new(-14.0, -1.0, -1.0),
+            Vec3::new(-9.0, 1.0, 1.0)
+        )));
+
+        // 旋转 45° 后包围盒变大，仍包住原来的所有角点
+        let unit = aabb(Vec3::splat(-1.0), Vec3::splat(1.0));
+        let rotated = unit.transformed(&Mat4::from_rotation_y(std::f32::consts::FRAC_PI_4));
+        assert!((rotated.max.x - 2f32.sqrt()).abs() < 1e-5);
+        assert!((rotated.max.y - 1.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn visible_instances_merge_into_ranges() {
+        let frustum = camera().frustum();
+        let unit = Aabb {
+            min: Vec3::splat(-0.5),
+            max: Vec3::splat(0.5),
+        };
+        let sphere = BoundingSphere::from_points([unit.min, unit.max].into_iter());
+        let matrices = [0.0, 1.0, 50.0, 2.0, 3.0, 50.0, -50.0, 4.0]
+            .map(|x| Mat4::from_translation(Vec3::new(x, 0.0, 0.0)));
+        assert_eq!(
+            frustum.visible_ranges(&matrices, &sphere, &unit),
+            vec![0..2, 3..5, 7..8]
+        );
+        // 放大后原本在视锥外的实例也与视锥相交
+        let scaled = Mat4::from_scale_rotation_translation(
+            Vec3::new(90.0, 1.0, 1.0),
+            glam::Quat::IDENTITY,
+            Vec3::new(50.0, 0.0, 0.0),
+        );
+        assert!(frustum.intersects_bounds(&scaled, &sphere, &unit));
+    }
+
+    #[test]
+    fn bounding_spheres_contain_their_points() {
+        let points = [
+            Vec3::new(-1.0, 0.0, 0.0),
+            Vec3::new(3.0, 2.0, 0.0),
+            Vec3::new(1.0, -2.0, 1.0),
+        ];
+        let bounds = BoundingSphere::from_points(points.into_iter());
+        assert!(
+            points
+                .iter()
+                .all(|p| p.distance(bounds.center) <= bounds.radius + 1e-5)
+        );
+
+        let other = sphere(10.0, 0.0, 0.0, 2.0);
+        let union = bounds.union(other);
+        for s in [bounds, other] {
+            assert!(s.center.distance(union.center) + s.radius <= union.radius + 1e-5);
+        }
+        // 已被包含的球不改变结果
+        assert_eq!(bounds.union(sphere(1.0, 0.0, 0.5, 0.1)), bounds);
+        assert_eq!(
+            BoundingSphere::from_points(std::iter::empty()),
+            sphere(0.0, 0.0, 0.0, 0.0)
+        );
+    }
+}
@@ -0,0 +1,16 @@
+// 后处理共用的顶点着色器，由 postprocess.rs 拼接在各效果的片元着色器前面
+
+struct FullscreenOutput {
+    @builtin(position) pos: vec4f,
+    @location(0) uv: vec2f,
+}
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> FullscreenOutput {
+    // 三个顶点 (-1,-1) (3,-1) (-1,3) 覆盖整个视口
+    let uv = vec2f(f32((index << 1u) & 2u), f32(index & 2u));
+    var out: FullscreenOutput;
+    out.pos = vec4f(uv * vec2f(2.0, -2.0) + vec2f(-1.0, 1.0), 0.0, 1.0);
+    out.uv = uv;
+    return out;
+}
@@ -0,0 +1,75 @@
+use crate::postprocess::{self, PostEffect, Stage};
+
+/// 快速近似抗锯齿，在 LDR 阶段的最后运行，处理的是最终显示的颜色
+pub struct Fxaa {
+    sampler: wgpu::Sampler,
+    bind_group_layout: wgpu::BindGroupLayout,
+    pipeline: wgpu::RenderPipeline,
+}
+
+impl Fxaa {
+    /// `format` 为 LDR 阶段的纹理格式，即交换链格式
+    pub fn new(device: &wgpu::Device, format: wgpu::TextureFormat) -> Self {
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("fxaa_bind_group_layout"),
+            entries: &[postprocess::texture_entry(0), postprocess::sampler_entry(1)],
+        });
+        let shader = postprocess::shader_module(device, "fxaa_shader", include_str!("fxaa.wgsl"));
+        let pipeline = postprocess::fullscreen_pipeline(
+            device,
+            "fxaa_pipeline",
+            &shader,
+            "fs_main",
+            &[&bind_group_layout],
+            format,
+            None,
+        );
+
+        Self {
+            sampler: postprocess::linear_sampler(device),
+            bind_group_layout,
+            pipeline,
+        }
+    }
+}
+
+impl PostEffect for Fxaa {
+    fn name(&self) -> &'static str {
+        "fxaa"
+    }
+
+    fn stage(&self) -> Stage {
+        Stage::Ldr
+    }
+
+    fn render(
+        &self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        input: &wgpu::TextureView,
+        output: &wgpu::TextureView,
+    ) {
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("fxaa_bind_group"),
+            layout: &self.bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(input),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&self.sampler),
+                },
+            ],
+        });
+        postprocess::fullscreen_pass(
+            encoder,
+            "fxaa_pass",
+            output,
+            wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+            &self.pipeline,
+            &[&bind_group],
+        );
+    }
+}
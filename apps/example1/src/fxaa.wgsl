@@ -0,0 +1,51 @@
+// FXAA：沿亮度梯度的垂直方向做模糊，平滑几何边缘的锯齿
+// 基于 Timothy Lottes 的 FXAA 控制台版本；顶点着色器见 fullscreen.wgsl
+
+@group(0) @binding(0)
+var source_texture: texture_2d<f32>;
+@group(0) @binding(1)
+var source_sampler: sampler;
+
+const REDUCE_MIN: f32 = 1.0 / 128.0;
+const REDUCE_MUL: f32 = 1.0 / 8.0;
+// 搜索方向的最大跨度（像素）
+const SPAN_MAX: f32 = 8.0;
+
+fn sample_source(uv: vec2f) -> vec3f {
+    return textureSample(source_texture, source_sampler, uv).rgb;
+}
+
+// 在近似 gamma 空间计算亮度，使边缘检测与人眼感受一致
+fn luma(color: vec3f) -> f32 {
+    return sqrt(dot(color, vec3f(0.299, 0.587, 0.114)));
+}
+
+@fragment
+fn fs_main(in: FullscreenOutput) -> @location(0) vec4f {
+    let texel = 1.0 / vec2f(textureDimensions(source_texture));
+    let center = textureSample(source_texture, source_sampler, in.uv);
+    let luma_nw = luma(sample_source(in.uv + vec2f(-1.0, -1.0) * texel));
+    let luma_ne = luma(sample_source(in.uv + vec2f(1.0, -1.0) * texel));
+    let luma_sw = luma(sample_source(in.uv + vec2f(-1.0, 1.0) * texel));
+    let luma_se = luma(sample_source(in.uv + vec2f(1.0, 1.0) * texel));
+    let luma_m = luma(center.rgb);
+    let luma_min = min(luma_m, min(min(luma_nw, luma_ne), min(luma_sw, luma_se)));
+    let luma_max = max(luma_m, max(max(luma_nw, luma_ne), max(luma_sw, luma_se)));
+
+    var dir = vec2f(
+        -((luma_nw + luma_ne) - (luma_sw + luma_se)),
+        (luma_nw + luma_sw) - (luma_ne + luma_se),
+    );
+    let dir_reduce = max((luma_nw + luma_ne + luma_sw + luma_se) * (0.25 * REDUCE_MUL), REDUCE_MIN);
+    let rcp_dir_min = 1.0 / (min(abs(dir.x), abs(dir.y)) + dir_reduce);
+    dir = clamp(dir * rcp_dir_min, vec2f(-SPAN_MAX), vec2f(SPAN_MAX)) * texel;
+
+    let rgb_a = 0.5 * (sample_source(in.uv + dir * (1.0 / 3.0 - 0.5))
+        + sample_source(in.uv + dir * (2.0 / 3.0 - 0.5)));
+    let rgb_b = rgb_a * 0.5 + 0.25 * (sample_source(in.uv - dir * 0.5)
+        + sample_source(in.uv + dir * 0.5));
+    // 两端采样越出了局部亮度范围，说明跨过了另一条边缘，退回较短的模糊
+    let luma_b = luma(rgb_b);
+    let outside = luma_b < luma_min || luma_b > luma_max;
+    return vec4f(select(rgb_b, rgb_a, outside), center.a);
+}
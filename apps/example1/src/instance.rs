@@ -1,19 +1,46 @@
+use std::ops::Range;
+
 pub struct Instance {
     pub pos: glam::Vec3,
     pub rotation: glam::Quat,
+    /// 各轴的缩放，可以不等比，但不能为 0
+    pub scale: glam::Vec3,
+    /// 与基础色相乘的颜色
+    pub tint: glam::Vec4,
 }
 
 #[repr(C)]
 #[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct InstanceRaw {
     pub model: [[f32; 4]; 4],
+    pub tint: [f32; 4],
+    /// 模型矩阵左上 3x3 的逆转置，非等比缩放后法线仍与表面垂直
+    pub normal: [[f32; 3]; 3],
 }
 
 impl Instance {
+    /// 不缩放、不着色的实例
+    pub fn new(pos: glam::Vec3, rotation: glam::Quat) -> Self {
+        Self {
+            pos,
+            rotation,
+            scale: glam::Vec3::ONE,
+            tint: glam::Vec4::ONE,
+        }
+    }
+
+    pub fn model_matrix(&self) -> glam::Mat4 {
+        glam::Mat4::from_scale_rotation_translation(self.scale, self.rotation, self.pos)
+    }
+
     pub fn to_raw(&self) -> InstanceRaw {
         InstanceRaw {
-            model: (glam::Mat4::from_translation(self.pos) * glam::Mat4::from_quat(self.rotation))
-                .to_cols_array_2d(),
+            model: self.model_matrix().to_cols_array_2d(),
+            tint: self.tint.to_array(),
+            // (R * S) 的逆转置为 R * S⁻¹
+            normal: (glam::Mat3::from_quat(self.rotation)
+                * glam::Mat3::from_diagonal(self.scale.recip()))
+            .to_cols_array_2d(),
         }
     }
 }
@@ -45,7 +72,242 @@ impl InstanceRaw {
                     shader_location: 8,
                     format: wgpu::VertexFormat::Float32x4,
                 },
+                wgpu::VertexAttribute {
+                    offset: mem::size_of::<[f32; 16]>() as wgpu::BufferAddress,
+                    shader_location: 9,
+                    format: wgpu::VertexFormat::Float32x4,
+                },
+                wgpu::VertexAttribute {
+                    offset: mem::size_of::<[f32; 20]>() as wgpu::BufferAddress,
+                    shader_location: 10,
+                    format: wgpu::VertexFormat::Float32x3,
+                },
+                wgpu::VertexAttribute {
+                    offset: mem::size_of::<[f32; 23]>() as wgpu::BufferAddress,
+                    shader_location: 11,
+                    format: wgpu::VertexFormat::Float32x3,
+                },
+                wgpu::VertexAttribute {
+                    offset: mem::size_of::<[f32; 26]>() as wgpu::BufferAddress,
+                    shader_location: 12,
+                    format: wgpu::VertexFormat::Float32x3,
+                },
             ],
         }
     }
 }
+
+/// 需要重新上传的实例下标范围，多次修改合并为覆盖它们的最小区间
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct DirtyRange(Option<Range<usize>>);
+
+impl DirtyRange {
+    pub fn mark(&mut self, range: Range<usize>) {
+        if range.is_empty() {
+            return;
+        }
+        self.0 = Some(match self.0.take() {
+            Some(dirty) => dirty.start.min(range.start)..dirty.end.max(range.end),
+            None => range,
+        });
+    }
+
+    /// 取出脏区间并截断到 `len` 以内，之后变为干净
+    pub fn take(&mut self, len: usize) -> Option<Range<usize>> {
+        let range = self.0.take()?;
+        let range = range.start.min(len)..range.end.min(len);
+        (!range.is_empty()).then_some(range)
+    }
+}
+
+/// 实例句柄，由 `InstanceManager::add` 返回，删除其他实例后依然有效
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct InstanceId(usize);
+
+/// 管理可在运行时增删改的实例及其 GPU 实例缓冲区。
+///
+/// 实例在数组中紧密排列，删除时把最后一个实例移到空位，因此一次绘制调用即可画出全部实例。
+/// 修改只记录脏区间，调用 `update` 时才上传；容量不足时缓冲区按两倍增长并整体重新上传。
+pub struct InstanceManager {
+    label: &'static str,
+    instances: Vec<Instance>,
+    /// 与 `instances` 一一对应的句柄
+    ids: Vec<InstanceId>,
+    /// 句柄到 `instances` 下标的映射，已删除的为 `None`
+    slots: Vec<Option<usize>>,
+    buffer: wgpu::Buffer,
+    dirty: DirtyRange,
+}
+
+impl InstanceManager {
+    const MIN_CAPACITY: usize = 16;
+
+    pub fn new(device: &wgpu::Device, label: &'static str) -> Self {
+        Self {
+            label,
+            instances: Vec::new(),
+            ids: Vec::new(),
+            slots: Vec::new(),
+            buffer: Self::create_buffer(device, label, Self::MIN_CAPACITY),
+            dirty: DirtyRange::default(),
+        }
+    }
+
+    fn create_buffer(device: &wgpu::Device, label: &str, capacity: usize) -> wgpu::Buffer {
+        device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some(label),
+            size: (capacity * std::mem::size_of::<InstanceRaw>()) as wgpu::BufferAddress,
+            // GPU 剔除时作为计算着色器的输入，见 `GpuCulling`
+            usage: wgpu::BufferUsages::VERTEX
+                | wgpu::BufferUsages::STORAGE
+                | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        })
+    }
+
+    /// 缓冲区当前能容纳的实例数
+    pub fn capacity(&self) -> usize {
+        self.buffer.size() as usize / std::mem::size_of::<InstanceRaw>()
+    }
+
+    pub fn add(&mut self, instance: Instance) -> InstanceId {
+        let id = InstanceId(self.slots.len());
+        self.slots.push(Some(self.instances.len()));
+        self.ids.push(id);
+        self.instances.push(instance);
+        self.dirty
+            .mark(self.instances.len() - 1..self.instances.len());
+        id
+    }
+
+    pub fn remove(&mut self, id: InstanceId) -> Option<Instance> {
+        let index = self.slots.get_mut(id.0)?.take()?;
+        let instance = self.instances.swap_remove(index);
+        self.ids.swap_remove(index);
+        // 原来的最后一个实例移到了 index
+        if let Some(&moved) = self.ids.get(index) {
+            self.slots[moved.0] = Some(index);
+            self.dirty.mark(index..index + 1);
+        }
+        Some(instance)
+    }
+
+    #[allow(dead_code)]
+    pub fn get(&self, id: InstanceId) -> Option<&Instance> {
+        let index = (*self.slots.get(id.0)?)?;
+        self.instances.get(index)
+    }
+
+    /// 获取可修改的实例，调用即视为实例已改变
+    pub fn get_mut(&mut self, id: InstanceId) -> Option<&mut Instance> {
+        let index = (*self.slots.get(id.0)?)?;
+        self.dirty.mark(index..index + 1);
+        self.instances.get_mut(index)
+    }
+
+    /// 修改全部实例，整个数组都会重新上传
+    #[allow(dead_code)]
+    pub fn iter_mut(&mut self) -> std::slice::IterMut<'_, Instance> {
+        self.dirty.mark(0..self.instances.len());
+        self.instances.iter_mut()
+    }
+
+    /// 取走 `other` 的全部实例和句柄，缓冲区仍用自己的，下次 `update` 时整体重新上传。
+    ///
+    /// 重建设备时用来把实例搬到新设备上，旧的 `InstanceId` 依然有效
+    pub fn adopt(&mut self, other: &mut Self) {
+        self.instances = std::mem::take(&mut other.instances);
+        self.ids = std::mem::take(&mut other.ids);
+        self.slots = std::mem::take(&mut other.slots);
+        self.dirty.mark(0..self.instances.len());
+    }
+
+    /// 全部实例，顺序与实例缓冲区一致
+    pub fn as_slice(&self) -> &[Instance] {
+        &self.instances
+    }
+
+    /// 绘制调用使用的实例范围
+    pub fn range(&self) -> Range<u32> {
+        0..self.instances.len() as u32
+    }
+
+    pub fn buffer(&self) -> &wgpu::Buffer {
+        &self.buffer
+    }
+
+    /// 容量不足时重建缓冲区，再把脏区间写入 GPU
+    pub fn update(&mut self, device: &wgpu::Device, queue: &wgpu::Queue) {
+        if self.instances.len() > self.capacity() {
+            let capacity = self
+                .instances
+                .len()
+                .max(self.capacity() * 2)
+                .max(Self::MIN_CAPACITY);
+            self.buffer = Self::create_buffer(device, self.label, capacity);
+            self.dirty.mark(0..self.instances.len());
+        }
+        let Some(range) = self.dirty.take(self.instances.len()) else {
+            return;
+        };
+        let raw = self.instances[range.clone()]
+            .iter()
+            .map(Instance::to_raw)
+            .collect::<Vec<_>>();
+        queue.write_buffer(
+            &self.buffer,
+            (range.start * std::mem::size_of::<InstanceRaw>()) as wgpu::BufferAddress,
+            bytemuck::cast_slice(&raw),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dirty_range_merges_and_clamps() {
+        let mut dirty = DirtyRange::default();
+        assert_eq!(dirty.take(10), None);
+
+        dirty.mark(3..4);
+        dirty.mark(7..9);
+        dirty.mark(5..5);
+        assert_eq!(dirty.take(10), Some(3..9));
+        assert_eq!(dirty.take(10), None);
+
+        // 标记后实例又被删除，超出长度的部分不再上传
+        dirty.mark(6..9);
+        assert_eq!(dirty.take(4), None);
+        dirty.mark(2..9);
+        assert_eq!(dirty.take(4), Some(2..4));
+    }
+
+    #[test]
+    fn scale_and_tint_reach_raw_instance() {
+        let mut instance = Instance::new(glam::Vec3::new(1.0, 2.0, 3.0), glam::Quat::IDENTITY);
+        instance.scale = glam::Vec3::splat(2.0);
+        instance.tint = glam::Vec4::new(1.0, 0.5, 0.25, 1.0);
+        let raw = instance.to_raw();
+        assert_eq!(raw.model[0][0], 2.0);
+        assert_eq!(raw.model[3], [1.0, 2.0, 3.0, 1.0]);
+        assert_eq!(raw.tint, [1.0, 0.5, 0.25, 1.0]);
+    }
+
+    #[test]
+    fn normal_matrix_keeps_normals_perpendicular_under_non_uniform_scale() {
+        let mut instance = Instance::new(glam::Vec3::ZERO, glam::Quat::from_rotation_z(0.7));
+        instance.scale = glam::Vec3::new(3.0, 0.5, 1.0);
+        let raw = instance.to_raw();
+        let model = glam::Mat4::from_cols_array_2d(&raw.model);
+        let normal_matrix = glam::Mat3::from_cols_array_2d(&raw.normal);
+
+        // 45° 斜面：直接用模型矩阵变换法线会使其不再垂直于切线
+        let normal = glam::Vec3::new(1.0, 1.0, 0.0).normalize();
+        let tangent = glam::Vec3::new(1.0, -1.0, 0.0).normalize();
+        let world_tangent = model.transform_vector3(tangent);
+        assert!((normal_matrix * normal).dot(world_tangent).abs() < 1e-5);
+        assert!(model.transform_vector3(normal).dot(world_tangent).abs() > 0.1);
+    }
+}
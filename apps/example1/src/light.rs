@@ -0,0 +1,388 @@
+use crate::shadow::ShadowMap;
+use wgpu::util::DeviceExt;
+
+/// 着色器中光源数组的长度，需与 shader.wgsl 中的 `MAX_LIGHTS` 保持一致
+pub const MAX_LIGHTS: usize = 16;
+
+const LIGHT_KIND_POINT: u32 = 0;
+const LIGHT_KIND_DIRECTIONAL: u32 = 1;
+const LIGHT_KIND_SPOT: u32 = 2;
+/// `LightsUniform::shadow_light` 取此值表示没有投射阴影的光源
+const NO_SHADOW_LIGHT: u32 = u32::MAX;
+
+/// 场景中的一个光源
+#[derive(Debug, Clone, Copy)]
+pub enum Light {
+    /// 点光源，光照强度在 `range` 范围内平滑衰减到 0
+    Point {
+        position: glam::Vec3,
+        color: glam::Vec3,
+        intensity: f32,
+        range: f32,
+    },
+    /// 平行光，`direction` 为光线的传播方向
+    Directional {
+        direction: glam::Vec3,
+        color: glam::Vec3,
+        intensity: f32,
+    },
+    /// 聚光灯，`inner_angle`/`outer_angle` 为半角（弧度），两者之间平滑过渡
+    Spot {
+        position: glam::Vec3,
+        direction: glam::Vec3,
+        color: glam::Vec3,
+        intensity: f32,
+        range: f32,
+        inner_angle: f32,
+        outer_angle: f32,
+    },
+}
+
+impl Light {
+    /// 把局部空间的位置和方向变换到世界空间
+    pub fn transformed(self, matrix: &glam::Mat4) -> Light {
+        let direction = |d: glam::Vec3| matrix.transform_vector3(d).normalize_or(d);
+        match self {
+            Light::Point {
+                position,
+                color,
+                intensity,
+                range,
+            } => Light::Point {
+                position: matrix.transform_point3(position),
+                color,
+                intensity,
+                range,
+            },
+            Light::Directional {
+                direction: d,
+                color,
+                intensity,
+            } => Light::Directional {
+                direction: direction(d),
+                color,
+                intensity,
+            },
+            Light::Spot {
+                position,
+                direction: d,
+                color,
+                intensity,
+                range,
+                inner_angle,
+                outer_angle,
+            } => Light::Spot {
+                position: matrix.transform_point3(position),
+                direction: direction(d),
+                color,
+                intensity,
+                range,
+                inner_angle,
+                outer_angle,
+            },
+        }
+    }
+
+    pub fn to_raw(self) -> LightRaw {
+        match self {
+            Light::Point {
+                position,
+                color,
+                intensity,
+                range,
+            } => LightRaw {
+                position: position.to_array(),
+                kind: LIGHT_KIND_POINT,
+                direction: [0.0; 3],
+                range,
+                color: color.to_array(),
+                intensity,
+                ..Default::default()
+            },
+            Light::Directional {
+                direction,
+                color,
+                intensity,
+            } => LightRaw {
+                kind: LIGHT_KIND_DIRECTIONAL,
+                direction: direction.normalize_or_zero().to_array(),
+                color: color.to_array(),
+                intensity,
+                ..Default::default()
+            },
+            Light::Spot {
+                position,
+                direction,
+                color,
+                intensity,
+                range,
+                inner_angle,
+                outer_angle,
+            } => LightRaw {
+                position: position.to_array(),
+                kind: LIGHT_KIND_SPOT,
+                direction: direction.normalize_or_zero().to_array(),
+                range,
+                color: color.to_array(),
+                intensity,
+                inner_cos: inner_angle.cos(),
+                outer_cos: outer_angle.cos(),
+                _padding: [0.0; 2],
+            },
+        }
+    }
+}
+
+/// 单个光源在 GPU 上的布局，对应 shader.wgsl 中的 `Light`
+#[repr(C)]
+#[derive(Debug, Default, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct LightRaw {
+    pub position: [f32; 3],
+    pub kind: u32,
+    pub direction: [f32; 3],
+    pub range: f32,
+    pub color: [f32; 3],
+    pub intensity: f32,
+    pub inner_cos: f32,
+    pub outer_cos: f32,
+    _padding: [f32; 2],
+}
+
+/// 整个光源数组的 uniform 数据，对应 shader.wgsl 中的 `Lights`
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct LightsUniform {
+    pub ambient: [f32; 3],
+    pub count: u32,
+    pub shadow_view_proj: [[f32; 4]; 4],
+    /// 投射阴影的光源在 `lights` 中的下标
+    pub shadow_light: u32,
+    _padding: [u32; 3],
+    pub lights: [LightRaw; MAX_LIGHTS],
+}
+
+impl LightsUniform {
+    fn empty(ambient: glam::Vec3) -> Self {
+        Self {
+            ambient: ambient.to_array(),
+            count: 0,
+            shadow_view_proj: glam::Mat4::IDENTITY.to_cols_array_2d(),
+            shadow_light: NO_SHADOW_LIGHT,
+            _padding: [0; 3],
+            lights: [LightRaw::default(); MAX_LIGHTS],
+        }
+    }
+}
+
+/// 光源句柄，由 `LightManager::add` 返回
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct LightId(usize);
+
+/// 管理场景中的光源及其 GPU 缓冲区。
+///
+/// 修改光源只会标记为脏，调用 `update` 时才写入缓冲区，每帧最多上传一次。
+/// 第一个平行光会投射阴影，阴影贴图覆盖 `set_shadow_bounds` 指定的范围。
+pub struct LightManager {
+    lights: Vec<Option<Light>>,
+    ambient: glam::Vec3,
+    shadow_center: glam::Vec3,
+    shadow_radius: f32,
+    pub shadow_map: ShadowMap,
+    buffer: wgpu::Buffer,
+    pub bind_group_layout: wgpu::BindGroupLayout,
+    pub bind_group: wgpu::BindGroup,
+    dirty: bool,
+}
+
+impl LightManager {
+    pub fn new(device: &wgpu::Device, ambient: glam::Vec3) -> anyhow::Result<Self> {
+        let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Light Buffer"),
+            contents: bytemuck::cast_slice(&[LightsUniform::empty(ambient)]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("light_bind_group_layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Depth,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Comparison),
+                    count: None,
+                },
+            ],
+        });
+
+        let shadow_map = ShadowMap::new(device)?;
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("light_bind_group"),
+            layout: &bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(&shadow_map.texture.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::Sampler(&shadow_map.texture.sampler),
+                },
+            ],
+        });
+
+        Ok(Self {
+            lights: Vec::new(),
+            ambient,
+            shadow_center: glam::Vec3::ZERO,
+            shadow_radius: 20.0,
+            shadow_map,
+            buffer,
+            bind_group_layout,
+            bind_group,
+            dirty: false,
+        })
+    }
+
+    /// 添加光源，超过 `MAX_LIGHTS` 时返回 `None`
+    pub fn add(&mut self, light: Light) -> Option<LightId> {
+        let index = match self.lights.iter().position(Option::is_none) {
+            Some(index) => index,
+            None if self.lights.len() < MAX_LIGHTS => {
+                self.lights.push(None);
+                self.lights.len() - 1
+            }
+            None => return None,
+        };
+        self.lights[index] = Some(light);
+        self.dirty = true;
+        Some(LightId(index))
+    }
+
+    pub fn remove(&mut self, id: LightId) -> Option<Light> {
+        let light = self.lights.get_mut(id.0)?.take();
+        self.dirty |= light.is_some();
+        light
+    }
+
+    /// 获取可修改的光源，调用即视为光源已改变
+    pub fn get_mut(&mut self, id: LightId) -> Option<&mut Light> {
+        let light = self.lights.get_mut(id.0)?.as_mut();
+        self.dirty |= light.is_some();
+        light
+    }
+
+    /// 取走 `other` 的光源，并沿用它的环境光和阴影范围，GPU 资源仍用自己的，下次 `update` 时重新上传。
+    ///
+    /// 重建设备时用来把光源搬到新设备上，旧的 `LightId` 依然有效
+    pub fn adopt(&mut self, other: &mut Self) {
+        self.lights = std::mem::take(&mut other.lights);
+        self.ambient = other.ambient;
+        self.shadow_center = other.shadow_center;
+        self.shadow_radius = other.shadow_radius;
+        self.dirty = true;
+    }
+
+    /// 设置阴影贴图需要覆盖的场景包围球
+    pub fn set_shadow_bounds(&mut self, center: glam::Vec3, radius: f32) {
+        self.shadow_center = center;
+        self.shadow_radius = radius;
+        self.dirty = true;
+    }
+
+    /// 投射阴影的平行光的视图投影矩阵，没有平行光时返回 `None`
+    pub fn shadow_view_proj(&self) -> Option<glam::Mat4> {
+        self.lights.iter().flatten().find_map(|light| match *light {
+            Light::Directional { direction, .. } => Some(ShadowMap::light_view_proj(
+                direction,
+                self.shadow_center,
+                self.shadow_radius,
+            )),
+            _ => None,
+        })
+    }
+
+    pub fn to_uniform(&self) -> LightsUniform {
+        let mut uniform = LightsUniform::empty(self.ambient);
+        for light in self.lights.iter().flatten() {
+            if uniform.shadow_light == NO_SHADOW_LIGHT && matches!(light, Light::Directional { .. })
+            {
+                uniform.shadow_light = uniform.count;
+            }
+            uniform.lights[uniform.count as usize] = light.to_raw();
+            uniform.count += 1;
+        }
+        if let Some(shadow_view_proj) = self.shadow_view_proj() {
+            uniform.shadow_view_proj = shadow_view_proj.to_cols_array_2d();
+        }
+        uniform
+    }
+
+    /// 若光源有改动，把光源数组写入 GPU 缓冲区
+    pub fn update(&mut self, queue: &wgpu::Queue) {
+        if !self.dirty {
+            return;
+        }
+        let uniform = self.to_uniform();
+        queue.write_buffer(&self.buffer, 0, bytemuck::cast_slice(&[uniform]));
+        self.shadow_map.update(
+            queue,
+            glam::Mat4::from_cols_array_2d(&uniform.shadow_view_proj),
+        );
+        self.dirty = false;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn layout_matches_wgsl() {
+        // WGSL 中 Light 为 64 字节，Lights 头部（ambient、count、阴影矩阵和下标）为 96 字节
+        assert_eq!(size_of::<LightRaw>(), 64);
+        assert_eq!(size_of::<LightsUniform>(), 96 + 64 * MAX_LIGHTS);
+    }
+
+    #[test]
+    fn spot_angles_are_stored_as_cosines() {
+        let raw = Light::Spot {
+            position: glam::Vec3::ZERO,
+            direction: glam::Vec3::new(0.0, -2.0, 0.0),
+            color: glam::Vec3::ONE,
+            intensity: 1.0,
+            range: 10.0,
+            inner_angle: 0.0,
+            outer_angle: std::f32::consts::FRAC_PI_2,
+        }
+        .to_raw();
+        assert_eq!(raw.kind, LIGHT_KIND_SPOT);
+        assert_eq!(raw.direction, [0.0, -1.0, 0.0]);
+        assert!((raw.inner_cos - 1.0).abs() < 1e-6);
+        assert!(raw.outer_cos.abs() < 1e-6);
+    }
+}
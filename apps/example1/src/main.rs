@@ -1,49 +1,115 @@
 use crate::app::App;
-use std::fs::OpenOptions;
-use std::io::Write;
+use crate::cli::Cli;
+use crate::scene_file::SceneFile;
+use crate::wgpu_ctx::WgpuCtx;
+use anyhow::Context;
+use clap::Parser;
+use std::path::Path;
 use winit::error::EventLoopError;
 use winit::event_loop::{ControlFlow, EventLoop};
 
 mod app;
+mod bloom;
 mod camera;
 mod camera_controller;
 mod camera_uniform;
+mod cli;
+mod color_grading;
+mod compressed;
+mod culling;
+mod environment;
+mod frame_timer;
+mod frustum;
+mod fxaa;
+#[allow(dead_code)]
 mod img_utils;
 mod instance;
+mod light;
+mod mipmap;
 mod model;
+mod postprocess;
 mod resources;
+mod scene;
+mod scene_file;
+mod shader_reload;
+mod shadow;
+mod skybox;
 mod texture;
+mod tonemap;
+#[allow(dead_code)]
 mod vertex;
+mod vignette;
 mod wgpu_ctx;
 
 fn main() -> Result<(), EventLoopError> {
-    // 尝试创建或打开日志文件（以追加模式）
-    let log_file = OpenOptions::new()
-        .create(true)
-        .append(true)
-        .open("my_app.log")
-        .expect("无法打开日志文件");
+    let cli = Cli::parse();
+    if cli.list_adapters {
+        cli::list_adapters(cli.backend);
+        return Ok(());
+    }
+    if let Err(err) = cli.init_logger() {
+        eprintln!("{err}");
+        std::process::exit(1);
+    }
 
-    env_logger::builder()
-        .format(|buf, record| {
-            writeln!(
-                buf,
-                "[{} {}] {}",
-                record.level(),
-                record.target(), // 这会显示模块路径（如 wgpu_core）
-                record.args()
-            )
-        })
-        .filter_level(log::LevelFilter::Debug)
-        .filter_module("wgpu_core", log::LevelFilter::Debug)
-        .filter_module("wgpu_hal", log::LevelFilter::Debug)
-        .filter_module("naga", log::LevelFilter::Error)
-        .parse_default_env()
-        .target(env_logger::Target::Pipe(Box::new(log_file)))
-        .init();
+    let scene = match load_scene(&cli) {
+        Ok(scene) => scene,
+        Err(err) => {
+            log::error!("加载场景失败: {err:?}");
+            eprintln!("加载场景失败: {err:?}");
+            std::process::exit(1);
+        }
+    };
+    if let Some(output) = &cli.headless {
+        if let Err(err) = render_headless(output, &cli, &scene) {
+            log::error!("离屏渲染失败: {err:?}");
+            eprintln!("离屏渲染失败: {err:?}");
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
 
-    let event_loop = EventLoop::new().unwrap();
+    let event_loop = EventLoop::new()?;
     event_loop.set_control_flow(ControlFlow::Poll);
-    let mut app = App::default();
-    event_loop.run_app(&mut app)
+    let mut app = App::new(&cli, scene);
+    event_loop.run_app(&mut app)?;
+    if let Some(err) = app.take_error() {
+        log::error!("初始化渲染失败: {err:?}");
+        eprintln!("初始化渲染失败: {err:?}");
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+/// 加载 `--scene` 指定的场景（省略时为内置场景），再按 `--model` 替换其中的模型
+fn load_scene(cli: &Cli) -> anyhow::Result<SceneFile> {
+    let mut scene = match &cli.scene {
+        Some(path) => SceneFile::load(path)?,
+        None => SceneFile::builtin(),
+    };
+    if cli.models.is_empty() {
+        return Ok(scene);
+    }
+    for model in &cli.models {
+        // 模型路径按资源目录解析，命令行给出的相对路径先转为相对于当前目录的绝对路径
+        let file = std::path::absolute(&model.file)?;
+        let file = file
+            .to_str()
+            .with_context(|| format!("模型路径 {} 不是有效的 UTF-8", file.display()))?;
+        scene.override_model(model.name.as_deref(), file.to_string())?;
+    }
+    scene.validate().context("--model 替换后的场景无效")?;
+    Ok(scene)
+}
+
+fn render_headless(output: &Path, cli: &Cli, scene: &SceneFile) -> anyhow::Result<()> {
+    let (width, height) = cli.size.unwrap_or((800, 600));
+    let mut wgpu_ctx = WgpuCtx::new_headless(width, height, scene, cli.context_options())?;
+    if let Some(sample_count) = cli.msaa {
+        wgpu_ctx.set_sample_count(sample_count);
+    }
+    wgpu_ctx.simulate(WgpuCtx::HEADLESS_FRAME_TIME);
+    wgpu_ctx.update(WgpuCtx::HEADLESS_FRAME_TIME);
+    wgpu_ctx.draw();
+    wgpu_ctx.save_frame_png(output)
 }
@@ -0,0 +1,222 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+/// 完整 mip 链的层数：一直缩小到 1x1
+pub fn mip_level_count(width: u32, height: u32) -> u32 {
+    32 - width.max(height).max(1).leading_zeros()
+}
+
+/// 第 `level` 级 mipmap 的尺寸
+pub fn mip_size(width: u32, height: u32, level: u32) -> (u32, u32) {
+    ((width >> level).max(1), (height >> level).max(1))
+}
+
+/// 纹理格式不能作为渲染目标时使用的 CPU 实现，返回第 1 级开始的各级图像。
+///
+/// 直接对存储值做滤波，sRGB 纹理会比 GPU 版本略暗，只作为兜底。
+pub fn generate_cpu(img: &image::RgbaImage, level_count: u32) -> Vec<image::RgbaImage> {
+    let mut levels: Vec<image::RgbaImage> = Vec::new();
+    for level in 1..level_count {
+        let (width, height) = mip_size(img.width(), img.height(), level);
+        let previous = levels.last().unwrap_or(img);
+        levels.push(image::imageops::resize(
+            previous,
+            width,
+            height,
+            image::imageops::FilterType::Triangle,
+        ));
+    }
+    levels
+}
+
+/// GPU 上能否通过渲染通道为该格式生成 mipmap
+pub fn supports_gpu(device: &wgpu::Device, format: wgpu::TextureFormat) -> bool {
+    let features = format.guaranteed_format_features(device.features());
+    features
+        .allowed_usages
+        .contains(wgpu::TextureUsages::RENDER_ATTACHMENT)
+        && features
+            .flags
+            .contains(wgpu::TextureFormatFeatureFlags::FILTERABLE)
+}
+
+/// 在 GPU 上生成 mipmap，着色器和采样器只创建一次，渲染管线按纹理格式缓存。
+///
+/// 加载场景时创建一个，所有纹理共用。
+pub struct MipmapGenerator {
+    shader: wgpu::ShaderModule,
+    sampler: wgpu::Sampler,
+    pipelines: RefCell<HashMap<wgpu::TextureFormat, wgpu::RenderPipeline>>,
+}
+
+impl MipmapGenerator {
+    pub fn new(device: &wgpu::Device) -> Self {
+        let shader = device.create_shader_module(wgpu::include_wgsl!("mipmap.wgsl"));
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("mipmap_sampler"),
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+        Self {
+            shader,
+            sampler,
+            pipelines: RefCell::new(HashMap::new()),
+        }
+    }
+
+    fn pipeline(&self, device: &wgpu::Device, format: wgpu::TextureFormat) -> wgpu::RenderPipeline {
+        self.pipelines
+            .borrow_mut()
+            .entry(format)
+            .or_insert_with(|| {
+                device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                    label: Some("mipmap_pipeline"),
+                    layout: None,
+                    vertex: wgpu::VertexState {
+                        module: &self.shader,
+                        entry_point: Some("vs_main"),
+                        buffers: &[],
+                        compilation_options: Default::default(),
+                    },
+                    primitive: wgpu::PrimitiveState {
+                        topology: wgpu::PrimitiveTopology::TriangleList,
+                        ..Default::default()
+                    },
+                    fragment: Some(wgpu::FragmentState {
+                        module: &self.shader,
+                        entry_point: Some("fs_main"),
+                        compilation_options: Default::default(),
+                        targets: &[Some(format.into())],
+                    }),
+                    depth_stencil: None,
+                    multisample: wgpu::MultisampleState::default(),
+                    multiview: None,
+                    cache: None,
+                })
+            })
+            .clone()
+    }
+
+    /// 逐级把上一级渲染到下一级，纹理需带 `RENDER_ATTACHMENT | TEXTURE_BINDING` 用途，
+    /// 且第 0 级已经写入
+    pub fn generate(&self, device: &wgpu::Device, queue: &wgpu::Queue, texture: &wgpu::Texture) {
+        let pipeline = self.pipeline(device, texture.format());
+        let bind_group_layout = pipeline.get_bind_group_layout(0);
+
+        let views = (0..texture.mip_level_count())
+            .map(|level| {
+                texture.create_view(&wgpu::TextureViewDescriptor {
+                    label: Some("mipmap_level"),
+                    base_mip_level: level,
+                    mip_level_count: Some(1),
+                    ..Default::default()
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("mipmap_encoder"),
+        });
+        for pair in views.windows(2) {
+            let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: None,
+                layout: &bind_group_layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::TextureView(&pair[0]),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::Sampler(&self.sampler),
+                    },
+                ],
+            });
+
+            let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("mipmap_pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &pair[1],
+                    resolve_target: None,
+                    depth_slice: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+            pass.set_pipeline(&pipeline);
+            pass.set_bind_group(0, &bind_group, &[]);
+            pass.draw(0..3, 0..1);
+        }
+        queue.submit([encoder.finish()]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn full_chain_goes_down_to_one_pixel() {
+        assert_eq!(mip_level_count(1, 1), 1);
+        assert_eq!(mip_level_count(256, 256), 9);
+        assert_eq!(mip_level_count(300, 20), 9);
+        assert_eq!(mip_size(300, 20, 8), (1, 1));
+    }
+
+    #[test]
+    fn cpu_levels_average_the_previous_level() {
+        let img = image::RgbaImage::from_fn(4, 2, |x, _| {
+            if x % 2 == 0 {
+                image::Rgba([0, 0, 0, 255])
+            } else {
+                image::Rgba([200, 100, 50, 255])
+            }
+        });
+        let levels = generate_cpu(&img, mip_level_count(4, 2));
+        let sizes = levels.iter().map(|l| l.dimensions()).collect::<Vec<_>>();
+        assert_eq!(sizes, [(2, 1), (1, 1)]);
+
+        let [r, g, b, a] = levels[1].get_pixel(0, 0).0;
+        assert!(r.abs_diff(100) <= 2 && g.abs_diff(50) <= 2 && b.abs_diff(25) <= 2);
+        assert_eq!(a, 255);
+    }
+
+    #[test]
+    fn gpu_pipelines_are_cached_per_format() {
+        let (device, queue) =
+            golden::headless_device().expect("mipmap 测试需要一个图形适配器（软件适配器也可以）");
+        let generator = MipmapGenerator::new(&device);
+        let texture = |format| {
+            device.create_texture(&wgpu::TextureDescriptor {
+                label: None,
+                size: wgpu::Extent3d {
+                    width: 4,
+                    height: 4,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: mip_level_count(4, 4),
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format,
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                    | wgpu::TextureUsages::TEXTURE_BINDING,
+                view_formats: &[],
+            })
+        };
+
+        for format in [
+            wgpu::TextureFormat::Rgba8UnormSrgb,
+            wgpu::TextureFormat::Rgba8UnormSrgb,
+            wgpu::TextureFormat::Rgba8Unorm,
+        ] {
+            generator.generate(&device, &queue, &texture(format));
+        }
+        assert_eq!(generator.pipelines.borrow().len(), 2);
+    }
+}
@@ -0,0 +1,27 @@
+// mipmap 生成：用一个覆盖全屏的三角形，对上一级做双线性采样写入下一级
+
+struct VertexOutput {
+    @builtin(position) pos: vec4f,
+    @location(0) uv: vec2f,
+}
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    // 三个顶点 (-1,-1) (3,-1) (-1,3) 覆盖整个视口
+    let uv = vec2f(f32((index << 1u) & 2u), f32(index & 2u));
+    var out: VertexOutput;
+    out.pos = vec4f(uv * vec2f(2.0, -2.0) + vec2f(-1.0, 1.0), 0.0, 1.0);
+    out.uv = uv;
+    return out;
+}
+
+@group(0) @binding(0)
+var source_texture: texture_2d<f32>;
+@group(0) @binding(1)
+var source_sampler: sampler;
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4f {
+    // 目标像素中心正好落在上一级 2x2 像素的中间，双线性采样即为四者的平均
+    return textureSample(source_texture, source_sampler, in.uv);
+}
@@ -1,5 +1,8 @@
+use crate::frustum::{Aabb, BoundingSphere, CullingStats, Frustum};
+use crate::instance::Instance;
 use crate::texture;
 use core::ops::Range;
+use wgpu::util::DeviceExt;
 pub trait Vertex {
     fn desc<'a>() -> wgpu::VertexBufferLayout<'a>;
 }
@@ -10,6 +13,9 @@ pub struct ModelVertex {
     pub position: [f32; 3],
     pub tex_coords: [f32; 2],
     pub normal: [f32; 3],
+    // 切线空间基向量，加载模型时由位置和纹理坐标计算
+    pub tangent: [f32; 3],
+    pub bitangent: [f32; 3],
 }
 
 pub struct Model {
@@ -17,22 +23,256 @@ pub struct Model {
     pub materials: Vec<Material>,
 }
 
+impl Model {
+    /// 包围所有网格的球，模型空间
+    pub fn bounding_sphere(&self) -> BoundingSphere {
+        self.meshes
+            .iter()
+            .map(|mesh| mesh.bounds)
+            .reduce(BoundingSphere::union)
+            .unwrap_or(BoundingSphere {
+                center: glam::Vec3::ZERO,
+                radius: 0.0,
+            })
+    }
+}
+
+/// 金属度-粗糙度材质的标量参数，布局与 shader.wgsl 中的 `MaterialUniform` 一致。
+///
+/// 各参数与对应贴图相乘，贴图缺失时使用白色（法线贴图为平坦法线）的 1x1 纹理，
+/// 因此只设置标量也能得到正确结果。
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct MaterialUniform {
+    pub base_color: [f32; 4],
+    pub emissive: [f32; 3],
+    pub metallic: f32,
+    /// 环境光（IBL 与常量环境光）的颜色系数，对应 MTL 的 Ka
+    pub ambient: [f32; 3],
+    pub roughness: f32,
+    pub normal_scale: f32,
+    pub occlusion_strength: f32,
+    /// 非金属的镜面反射强度，F0 = 0.08 * specular，默认 0.5 即 4%；对应 MTL 的 Ks
+    pub specular: f32,
+    _padding: f32,
+}
+
+impl MaterialUniform {
+    pub fn new(base_color: [f32; 4], metallic: f32, roughness: f32) -> Self {
+        Self {
+            base_color,
+            emissive: [0.0; 3],
+            metallic,
+            ambient: [1.0; 3],
+            roughness,
+            normal_scale: 1.0,
+            occlusion_strength: 1.0,
+            specular: 0.5,
+            _padding: 0.0,
+        }
+    }
+}
+
+/// 材质用到的贴图，为 `None` 的贴图由 `Material::new` 补上 1x1 的默认纹理。
+///
+/// 金属度-粗糙度贴图沿用 glTF 约定：G 通道为粗糙度，B 通道为金属度；
+/// 环境光遮蔽取 R 通道。
+#[derive(Default)]
+pub struct MaterialTextures {
+    pub base_color: Option<texture::Texture>,
+    pub normal: Option<texture::Texture>,
+    pub metallic_roughness: Option<texture::Texture>,
+    pub occlusion: Option<texture::Texture>,
+    pub emissive: Option<texture::Texture>,
+}
+
+/// 贴图在材质中的用途，决定加载时使用的色彩空间
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextureSlot {
+    BaseColor,
+    Normal,
+    MetallicRoughness,
+    Occlusion,
+    Emissive,
+}
+
+impl TextureSlot {
+    /// 只有基础色和自发光是颜色，其余贴图存储的是向量或标量，必须按线性值采样
+    pub fn color_space(self) -> texture::ColorSpace {
+        match self {
+            TextureSlot::BaseColor | TextureSlot::Emissive => texture::ColorSpace::Srgb,
+            TextureSlot::Normal | TextureSlot::MetallicRoughness | TextureSlot::Occlusion => {
+                texture::ColorSpace::Linear
+            }
+        }
+    }
+
+    /// 该用途的默认纹理设置
+    pub fn texture_options(self) -> texture::TextureOptions {
+        texture::TextureOptions::default().color_space(self.color_space())
+    }
+}
+
+/// 贴图和参数缓冲区都由 `bind_group` 持有
 pub struct Material {
+    #[allow(dead_code)]
     pub name: String,
-    pub diffuse_texture: texture::Texture,
     pub bind_group: wgpu::BindGroup,
 }
 
+impl Material {
+    /// 材质贴图的数量，每张贴图占用纹理和采样器两个绑定
+    const TEXTURE_COUNT: u32 = 5;
+    const UNIFORM_BINDING: u32 = Self::TEXTURE_COUNT * 2;
+
+    pub fn new(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        name: &str,
+        textures: MaterialTextures,
+        uniform: MaterialUniform,
+        layout: &wgpu::BindGroupLayout,
+    ) -> anyhow::Result<Self> {
+        let fallback =
+            |texture: Option<texture::Texture>, rgba, label, slot: TextureSlot| match texture {
+                Some(texture) => Ok(texture),
+                None => {
+                    texture::Texture::from_color(device, queue, rgba, label, slot.color_space())
+                }
+            };
+        let base_color_texture = fallback(
+            textures.base_color,
+            [255; 4],
+            "white",
+            TextureSlot::BaseColor,
+        )?;
+        let normal_texture = fallback(
+            textures.normal,
+            [128, 128, 255, 255],
+            "flat_normal",
+            TextureSlot::Normal,
+        )?;
+        let metallic_roughness_texture = fallback(
+            textures.metallic_roughness,
+            [255; 4],
+            "white_linear",
+            TextureSlot::MetallicRoughness,
+        )?;
+        let occlusion_texture = fallback(
+            textures.occlusion,
+            [255; 4],
+            "white_linear",
+            TextureSlot::Occlusion,
+        )?;
+        let emissive_texture =
+            fallback(textures.emissive, [255; 4], "white", TextureSlot::Emissive)?;
+
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some(&format!("{name} Material Buffer")),
+            contents: bytemuck::cast_slice(&[uniform]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let textures = [
+            &base_color_texture,
+            &normal_texture,
+            &metallic_roughness_texture,
+            &occlusion_texture,
+            &emissive_texture,
+        ];
+        let mut entries = Vec::with_capacity(textures.len() * 2 + 1);
+        for (i, texture) in textures.into_iter().enumerate() {
+            entries.push(wgpu::BindGroupEntry {
+                binding: i as u32 * 2,
+                resource: wgpu::BindingResource::TextureView(&texture.view),
+            });
+            entries.push(wgpu::BindGroupEntry {
+                binding: i as u32 * 2 + 1,
+                resource: wgpu::BindingResource::Sampler(&texture.sampler),
+            });
+        }
+        entries.push(wgpu::BindGroupEntry {
+            binding: Self::UNIFORM_BINDING,
+            resource: uniform_buffer.as_entire_binding(),
+        });
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout,
+            entries: &entries,
+            label: Some(name),
+        });
+
+        Ok(Self {
+            name: name.to_string(),
+            bind_group,
+        })
+    }
+
+    /// 材质绑定组布局：基础色、法线、金属度-粗糙度、环境光遮蔽、自发光贴图及其采样器，
+    /// 最后是标量参数
+    pub fn bind_group_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+        let mut entries = Vec::new();
+        for i in 0..Self::TEXTURE_COUNT {
+            entries.push(wgpu::BindGroupLayoutEntry {
+                binding: i * 2,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                    multisampled: false,
+                },
+                count: None,
+            });
+            entries.push(wgpu::BindGroupLayoutEntry {
+                binding: i * 2 + 1,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                count: None,
+            });
+        }
+        entries.push(wgpu::BindGroupLayoutEntry {
+            binding: Self::UNIFORM_BINDING,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        });
+
+        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("material_bind_group_layout"),
+            entries: &entries,
+        })
+    }
+}
+
 pub struct Mesh {
+    #[allow(dead_code)]
     pub name: String,
     pub vertex_buffer: wgpu::Buffer,
     pub index_buffer: wgpu::Buffer,
     pub index_count: u32,
     pub material: usize,
+    /// 模型空间的包围球和包围盒，由顶点位置计算
+    pub bounds: BoundingSphere,
+    pub aabb: Aabb,
+}
+
+impl ModelVertex {
+    /// 一组顶点的包围球
+    pub fn bounding_sphere(vertices: &[ModelVertex]) -> BoundingSphere {
+        BoundingSphere::from_points(vertices.iter().map(|v| glam::Vec3::from(v.position)))
+    }
+
+    /// 一组顶点的包围盒
+    pub fn aabb(vertices: &[ModelVertex]) -> Aabb {
+        Aabb::from_points(vertices.iter().map(|v| glam::Vec3::from(v.position)))
+    }
 }
 impl Vertex for ModelVertex {
     fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
-        use std::mem;
         wgpu::VertexBufferLayout {
             array_stride: size_of::<ModelVertex>() as wgpu::BufferAddress,
             step_mode: wgpu::VertexStepMode::Vertex,
@@ -52,17 +292,29 @@ impl Vertex for ModelVertex {
                     shader_location: 2,
                     format: wgpu::VertexFormat::Float32x3,
                 },
+                wgpu::VertexAttribute {
+                    offset: size_of::<[f32; 8]>() as wgpu::BufferAddress,
+                    shader_location: 3,
+                    format: wgpu::VertexFormat::Float32x3,
+                },
+                wgpu::VertexAttribute {
+                    offset: size_of::<[f32; 11]>() as wgpu::BufferAddress,
+                    shader_location: 4,
+                    format: wgpu::VertexFormat::Float32x3,
+                },
             ],
         }
     }
 }
 
 pub trait DrawModel<'a> {
+    #[allow(dead_code)]
     fn draw_mesh(
         &mut self,
         mesh: &'a Mesh,
         material: &'a Material,
         camera_bind_group: &'a wgpu::BindGroup,
+        light_bind_group: &'a wgpu::BindGroup,
     );
     fn draw_mesh_instanced(
         &mut self,
@@ -70,13 +322,37 @@ pub trait DrawModel<'a> {
         material: &'a Material,
         instances: Range<u32>,
         camera_bind_group: &'a wgpu::BindGroup,
+        light_bind_group: &'a wgpu::BindGroup,
     );
 
+    /// 绘制模型的所有网格，每个网格绑定各自的 PBR 材质
+    #[allow(dead_code)]
     fn draw_model_instanced(
         &mut self,
         model: &'a Model,
         instances: Range<u32>,
         camera_bind_group: &'a wgpu::BindGroup,
+        light_bind_group: &'a wgpu::BindGroup,
+    );
+
+    /// 只绘制与视锥相交的网格实例，`instances` 的顺序需与槽位 1 的实例缓冲区一致
+    fn draw_model_culled(
+        &mut self,
+        model: &'a Model,
+        instances: &[Instance],
+        frustum: &Frustum,
+        stats: &mut CullingStats,
+        camera_bind_group: &'a wgpu::BindGroup,
+        light_bind_group: &'a wgpu::BindGroup,
+    );
+
+    /// 实例数由 GPU 决定，`indirect_buffer` 中按网格顺序每个网格一组 `DrawIndexedIndirectArgs`
+    fn draw_model_indirect(
+        &mut self,
+        model: &'a Model,
+        indirect_buffer: &'a wgpu::Buffer,
+        camera_bind_group: &'a wgpu::BindGroup,
+        light_bind_group: &'a wgpu::BindGroup,
     );
 }
 
@@ -89,8 +365,9 @@ where
         mesh: &'b Mesh,
         material: &'b Material,
         camera_bind_group: &'b wgpu::BindGroup,
+        light_bind_group: &'b wgpu::BindGroup,
     ) {
-        self.draw_mesh_instanced(mesh, material, 0..1, camera_bind_group);
+        self.draw_mesh_instanced(mesh, material, 0..1, camera_bind_group, light_bind_group);
     }
 
     fn draw_mesh_instanced(
@@ -99,11 +376,13 @@ where
         material: &'b Material,
         instances: Range<u32>,
         camera_bind_group: &'b wgpu::BindGroup,
+        light_bind_group: &'b wgpu::BindGroup,
     ) {
         self.set_vertex_buffer(0, mesh.vertex_buffer.slice(..));
         self.set_index_buffer(mesh.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
         self.set_bind_group(0, &material.bind_group, &[]);
         self.set_bind_group(1, camera_bind_group, &[]);
+        self.set_bind_group(2, light_bind_group, &[]);
         self.draw_indexed(0..mesh.index_count, 0, instances);
     }
 
@@ -112,10 +391,82 @@ where
         model: &'b Model,
         instances: Range<u32>,
         camera_bind_group: &'b wgpu::BindGroup,
+        light_bind_group: &'b wgpu::BindGroup,
+    ) {
+        for mesh in &model.meshes {
+            let material = &model.materials[mesh.material];
+            self.draw_mesh_instanced(
+                mesh,
+                material,
+                instances.clone(),
+                camera_bind_group,
+                light_bind_group,
+            );
+        }
+    }
+
+    fn draw_model_culled(
+        &mut self,
+        model: &'b Model,
+        instances: &[Instance],
+        frustum: &Frustum,
+        stats: &mut CullingStats,
+        camera_bind_group: &'b wgpu::BindGroup,
+        light_bind_group: &'b wgpu::BindGroup,
     ) {
+        let matrices = instances
+            .iter()
+            .map(Instance::model_matrix)
+            .collect::<Vec<_>>();
         for mesh in &model.meshes {
+            let ranges = frustum.visible_ranges(&matrices, &mesh.bounds, &mesh.aabb);
+            let drawn = ranges.iter().map(|range| range.len() as u32).sum::<u32>();
+            stats.instances_drawn += drawn;
+            stats.instances_culled += instances.len() as u32 - drawn;
+            if ranges.is_empty() {
+                stats.meshes_culled += 1;
+                continue;
+            }
+            stats.meshes_drawn += 1;
             let material = &model.materials[mesh.material];
-            self.draw_mesh_instanced(mesh, material, instances.clone(), camera_bind_group);
+            for range in ranges {
+                self.draw_mesh_instanced(
+                    mesh,
+                    material,
+                    range,
+                    camera_bind_group,
+                    light_bind_group,
+                );
+            }
         }
     }
+
+    fn draw_model_indirect(
+        &mut self,
+        model: &'b Model,
+        indirect_buffer: &'b wgpu::Buffer,
+        camera_bind_group: &'b wgpu::BindGroup,
+        light_bind_group: &'b wgpu::BindGroup,
+    ) {
+        let args_size = size_of::<wgpu::util::DrawIndexedIndirectArgs>() as wgpu::BufferAddress;
+        for (i, mesh) in model.meshes.iter().enumerate() {
+            self.set_vertex_buffer(0, mesh.vertex_buffer.slice(..));
+            self.set_index_buffer(mesh.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+            self.set_bind_group(0, &model.materials[mesh.material].bind_group, &[]);
+            self.set_bind_group(1, camera_bind_group, &[]);
+            self.set_bind_group(2, light_bind_group, &[]);
+            self.draw_indexed_indirect(indirect_buffer, i as wgpu::BufferAddress * args_size);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn material_uniform_matches_wgsl() {
+        // WGSL 中 MaterialUniform 按 vec4f 对齐，共 64 字节
+        assert_eq!(size_of::<MaterialUniform>(), 64);
+    }
 }
@@ -0,0 +1,344 @@
+use winit::event::{ElementState, KeyEvent};
+use winit::keyboard::{KeyCode, PhysicalKey};
+
+use crate::tonemap::ToneMapping;
+
+/// 效果运行在色调映射之前还是之后
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+    /// 输入输出都是 `PostProcess::HDR_FORMAT`，颜色可以超过 1
+    Hdr,
+    /// 输入输出都是交换链格式，颜色已映射到 [0, 1]
+    Ldr,
+}
+
+/// 后处理链中的一个节点：一个或多个全屏通道，从 `input` 读取、写入 `output`
+pub trait PostEffect {
+    fn name(&self) -> &'static str;
+
+    fn stage(&self) -> Stage;
+
+    /// 窗口大小改变时重建内部使用的纹理
+    fn resize(&mut self, _device: &wgpu::Device, _width: u32, _height: u32) {}
+
+    fn render(
+        &self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        input: &wgpu::TextureView,
+        output: &wgpu::TextureView,
+    );
+}
+
+struct Node {
+    effect: Box<dyn PostEffect>,
+    enabled: bool,
+}
+
+/// 场景通道之后的后处理链。
+///
+/// 场景渲染到 HDR 纹理后，依次运行已开启的 HDR 效果，再做色调映射，
+/// 最后运行 LDR 效果，最后一个通道直接写入交换链。每个阶段用两张纹理交替作为输入和输出。
+/// 数字键 `1`..`9` 按添加顺序开关各个效果。
+pub struct PostProcess {
+    nodes: Vec<Node>,
+    hdr_views: [wgpu::TextureView; 2],
+    ldr_views: [wgpu::TextureView; 2],
+    ldr_format: wgpu::TextureFormat,
+}
+
+impl PostProcess {
+    pub const HDR_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+
+    /// `ldr_format` 为交换链（或离屏纹理）的格式
+    pub fn new(
+        device: &wgpu::Device,
+        width: u32,
+        height: u32,
+        ldr_format: wgpu::TextureFormat,
+    ) -> Self {
+        Self {
+            nodes: Vec::new(),
+            hdr_views: Self::create_targets(device, width, height, Self::HDR_FORMAT, "hdr"),
+            ldr_views: Self::create_targets(device, width, height, ldr_format, "ldr"),
+            ldr_format,
+        }
+    }
+
+    fn create_targets(
+        device: &wgpu::Device,
+        width: u32,
+        height: u32,
+        format: wgpu::TextureFormat,
+        label: &str,
+    ) -> [wgpu::TextureView; 2] {
+        [0, 1].map(|index| {
+            device
+                .create_texture(&wgpu::TextureDescriptor {
+                    label: Some(&format!("post_process_{label}_{index}")),
+                    size: wgpu::Extent3d {
+                        width,
+                        height,
+                        depth_or_array_layers: 1,
+                    },
+                    mip_level_count: 1,
+                    sample_count: 1,
+                    dimension: wgpu::TextureDimension::D2,
+                    format,
+                    usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                        | wgpu::TextureUsages::TEXTURE_BINDING,
+                    view_formats: &[],
+                })
+                .create_view(&wgpu::TextureViewDescriptor::default())
+        })
+    }
+
+    /// 把效果追加到链尾；同一阶段内按添加顺序运行
+    pub fn push(&mut self, effect: impl PostEffect + 'static, enabled: bool) {
+        self.nodes.push(Node {
+            effect: Box::new(effect),
+            enabled,
+        });
+    }
+
+    /// 场景通道的颜色附件
+    pub fn scene_view(&self) -> &wgpu::TextureView {
+        &self.hdr_views[0]
+    }
+
+    pub fn resize(&mut self, device: &wgpu::Device, width: u32, height: u32) {
+        self.hdr_views = Self::create_targets(device, width, height, Self::HDR_FORMAT, "hdr");
+        self.ldr_views = Self::create_targets(device, width, height, self.ldr_format, "ldr");
+        for node in &mut self.nodes {
+            node.effect.resize(device, width, height);
+        }
+    }
+
+    /// 开关第 `index` 个效果，返回效果名和新状态
+    pub fn toggle(&mut self, index: usize) -> Option<(&'static str, bool)> {
+        let node = self.nodes.get_mut(index)?;
+        node.enabled = !node.enabled;
+        Some((node.effect.name(), node.enabled))
+    }
+
+    /// 各效果是否开启，按添加顺序
+    pub fn enabled(&self) -> Vec<bool> {
+        self.nodes.iter().map(|node| node.enabled).collect()
+    }
+
+    /// 按 `enabled` 的结果恢复各效果的开关，多出的项被忽略
+    pub fn set_enabled(&mut self, enabled: &[bool]) {
+        for (node, &enabled) in self.nodes.iter_mut().zip(enabled) {
+            node.enabled = enabled;
+        }
+    }
+
+    pub fn process_events(&mut self, event: &KeyEvent) -> bool {
+        if event.state != ElementState::Pressed || event.repeat {
+            return false;
+        }
+        const KEYS: [KeyCode; 9] = [
+            KeyCode::Digit1,
+            KeyCode::Digit2,
+            KeyCode::Digit3,
+            KeyCode::Digit4,
+            KeyCode::Digit5,
+            KeyCode::Digit6,
+            KeyCode::Digit7,
+            KeyCode::Digit8,
+            KeyCode::Digit9,
+        ];
+        let PhysicalKey::Code(code) = event.physical_key else {
+            return false;
+        };
+        let Some(index) = KEYS.iter().position(|key| *key == code) else {
+            return false;
+        };
+        if let Some((name, enabled)) = self.toggle(index) {
+            log::info!("后处理 {name}: {}", if enabled { "开启" } else { "关闭" });
+        }
+        true
+    }
+
+    /// 从 `scene_view` 开始运行整条链，结果写入 `output`
+    pub fn render(
+        &self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        tone_mapping: &ToneMapping,
+        output: &wgpu::TextureView,
+    ) {
+        let enabled = |stage| {
+            self.nodes
+                .iter()
+                .filter(move |node| node.enabled && node.effect.stage() == stage)
+                .map(|node| node.effect.as_ref())
+        };
+
+        let mut current = 0;
+        for effect in enabled(Stage::Hdr) {
+            effect.render(
+                device,
+                encoder,
+                &self.hdr_views[current],
+                &self.hdr_views[1 - current],
+            );
+            current = 1 - current;
+        }
+
+        let ldr_effects = enabled(Stage::Ldr).collect::<Vec<_>>();
+        let Some((last, rest)) = ldr_effects.split_last() else {
+            tone_mapping.render(device, encoder, &self.hdr_views[current], output);
+            return;
+        };
+        tone_mapping.render(
+            device,
+            encoder,
+            &self.hdr_views[current],
+            &self.ldr_views[0],
+        );
+        current = 0;
+        for effect in rest {
+            effect.render(
+                device,
+                encoder,
+                &self.ldr_views[current],
+                &self.ldr_views[1 - current],
+            );
+            current = 1 - current;
+        }
+        last.render(device, encoder, &self.ldr_views[current], output);
+    }
+}
+
+/// 拼接共用的全屏顶点着色器（fullscreen.wgsl）后创建着色器模块，
+/// 片元着色器中可直接使用 `FullscreenOutput`
+pub fn shader_module(device: &wgpu::Device, label: &str, fragment: &str) -> wgpu::ShaderModule {
+    let source = format!("{}\n{fragment}", include_str!("fullscreen.wgsl"));
+    device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some(label),
+        source: wgpu::ShaderSource::Wgsl(source.into()),
+    })
+}
+
+/// 不带深度的全屏三角形管线，`blend` 为 `None` 时直接覆盖
+pub fn fullscreen_pipeline(
+    device: &wgpu::Device,
+    label: &str,
+    shader: &wgpu::ShaderModule,
+    entry_point: &str,
+    bind_group_layouts: &[&wgpu::BindGroupLayout],
+    format: wgpu::TextureFormat,
+    blend: Option<wgpu::BlendState>,
+) -> wgpu::RenderPipeline {
+    let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: Some(label),
+        bind_group_layouts,
+        push_constant_ranges: &[],
+    });
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some(label),
+        layout: Some(&layout),
+        vertex: wgpu::VertexState {
+            module: shader,
+            entry_point: Some("vs_main"),
+            buffers: &[],
+            compilation_options: Default::default(),
+        },
+        primitive: wgpu::PrimitiveState {
+            topology: wgpu::PrimitiveTopology::TriangleList,
+            ..Default::default()
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: shader,
+            entry_point: Some(entry_point),
+            compilation_options: Default::default(),
+            targets: &[Some(wgpu::ColorTargetState {
+                format,
+                blend,
+                write_mask: wgpu::ColorWrites::ALL,
+            })],
+        }),
+        depth_stencil: None,
+        multisample: wgpu::MultisampleState::default(),
+        multiview: None,
+        cache: None,
+    })
+}
+
+/// 用全屏三角形绘制一次，`bind_groups` 依次绑定到第 0、1…组；`load` 为 `Load` 时与已有内容混合
+pub fn fullscreen_pass(
+    encoder: &mut wgpu::CommandEncoder,
+    label: &str,
+    output: &wgpu::TextureView,
+    load: wgpu::LoadOp<wgpu::Color>,
+    pipeline: &wgpu::RenderPipeline,
+    bind_groups: &[&wgpu::BindGroup],
+) {
+    let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+        label: Some(label),
+        color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+            view: output,
+            resolve_target: None,
+            depth_slice: None,
+            ops: wgpu::Operations {
+                load,
+                store: wgpu::StoreOp::Store,
+            },
+        })],
+        depth_stencil_attachment: None,
+        timestamp_writes: None,
+        occlusion_query_set: None,
+    });
+    pass.set_pipeline(pipeline);
+    for (index, bind_group) in bind_groups.iter().enumerate() {
+        pass.set_bind_group(index as u32, *bind_group, &[]);
+    }
+    pass.draw(0..3, 0..1);
+}
+
+/// 片元着色器中可过滤采样的二维纹理绑定
+pub fn texture_entry(binding: u32) -> wgpu::BindGroupLayoutEntry {
+    wgpu::BindGroupLayoutEntry {
+        binding,
+        visibility: wgpu::ShaderStages::FRAGMENT,
+        ty: wgpu::BindingType::Texture {
+            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+            view_dimension: wgpu::TextureViewDimension::D2,
+            multisampled: false,
+        },
+        count: None,
+    }
+}
+
+pub fn sampler_entry(binding: u32) -> wgpu::BindGroupLayoutEntry {
+    wgpu::BindGroupLayoutEntry {
+        binding,
+        visibility: wgpu::ShaderStages::FRAGMENT,
+        ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+        count: None,
+    }
+}
+
+pub fn uniform_entry(binding: u32) -> wgpu::BindGroupLayoutEntry {
+    wgpu::BindGroupLayoutEntry {
+        binding,
+        visibility: wgpu::ShaderStages::FRAGMENT,
+        ty: wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Uniform,
+            has_dynamic_offset: false,
+            min_binding_size: None,
+        },
+        count: None,
+    }
+}
+
+/// 后处理通道共用的双线性、边缘截断采样器
+pub fn linear_sampler(device: &wgpu::Device) -> wgpu::Sampler {
+    device.create_sampler(&wgpu::SamplerDescriptor {
+        label: Some("post_process_sampler"),
+        mag_filter: wgpu::FilterMode::Linear,
+        min_filter: wgpu::FilterMode::Linear,
+        ..Default::default()
+    })
+}
@@ -1,46 +1,80 @@
+use crate::mipmap::MipmapGenerator;
 use crate::model;
 use crate::texture;
-use anyhow::Ok;
+use anyhow::{Context, Ok};
 use std::io::{BufReader, Cursor};
 use wgpu::util::DeviceExt;
 use wgpu::{Device, Queue};
 
-pub async fn load_string(filename: &str) -> anyhow::Result<String> {
-    let path = std::path::Path::new(env!("OUT_DIR"))
+/// 资源文件在构建输出目录中的路径（build.rs 会把 assets 复制过去）
+pub fn asset_path(file_name: &str) -> std::path::PathBuf {
+    std::path::Path::new(env!("OUT_DIR"))
         .join("assets")
-        .join(filename);
+        .join(file_name)
+}
+
+pub async fn load_string(filename: &str) -> anyhow::Result<String> {
+    let path = asset_path(filename);
 
     let txt = std::fs::read_to_string(path)?;
     Ok(txt)
 }
 
 pub async fn load_binary(file_path: &str) -> anyhow::Result<Vec<u8>> {
-    let path = std::path::Path::new(env!("OUT_DIR"))
-        .join("assets")
-        .join(file_path);
+    let path = asset_path(file_path);
     let data = std::fs::read(path)?;
     Ok(data)
 }
 
+/// 按贴图在材质中的用途加载：法线（MTL 的 `map_Bump`）等数据贴图自动使用线性格式
 pub async fn load_texture(
     file_path: &str,
     device: &Device,
     queue: &Queue,
+    mipmaps: &MipmapGenerator,
+    slot: model::TextureSlot,
 ) -> anyhow::Result<texture::Texture> {
     let data = load_binary(file_path).await?;
-    texture::Texture::from_bytes(&device, &queue, &data, "")
+    texture::Texture::from_bytes(
+        device,
+        queue,
+        Some(mipmaps),
+        &data,
+        file_path,
+        &slot.texture_options(),
+    )
 }
 
+/// 按扩展名加载模型：`.gltf`/`.glb` 走 glTF 加载器，其余按 OBJ 处理
 pub async fn load_model(
     file_name: &str,
     device: &Device,
     queue: &Queue,
+    mipmaps: &MipmapGenerator,
+    layout: &wgpu::BindGroupLayout,
+) -> anyhow::Result<model::Model> {
+    let extension = std::path::Path::new(file_name)
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .map(str::to_ascii_lowercase);
+    match extension.as_deref() {
+        Some("gltf" | "glb") => load_gltf(file_name, device, queue, mipmaps, layout).await,
+        _ => load_obj(file_name, device, queue, mipmaps, layout).await,
+    }
+}
+
+async fn load_obj(
+    file_name: &str,
+    device: &Device,
+    queue: &Queue,
+    mipmaps: &MipmapGenerator,
     layout: &wgpu::BindGroupLayout,
 ) -> anyhow::Result<model::Model> {
     let obj_text = load_string(file_name).await?;
     let obj_cursor = Cursor::new(obj_text);
     let mut obj_reader = BufReader::new(obj_cursor);
 
+    #[allow(deprecated)]
     let (models, obj_materials) = tobj::load_obj_buf_async(
         &mut obj_reader,
         &tobj::LoadOptions {
@@ -60,48 +94,67 @@ pub async fn load_model(
     )
     .await?;
 
+    // 没有 mtllib 或材质库无法加载时，所有网格使用默认材质
+    let obj_materials = obj_materials.unwrap_or_else(|err| {
+        log::warn!("{file_name}: 无法加载材质库，使用默认材质: {err}");
+        Vec::new()
+    });
     let mut materials = Vec::new();
-    for mat in obj_materials? {
-        let diffuse_texture = load_texture(&mat.diffuse_texture.unwrap(), &device, &queue).await?;
-        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+    for mat in obj_materials {
+        let mut textures = model::MaterialTextures::default();
+        if let Some(diffuse_texture) = &mat.diffuse_texture {
+            textures.base_color = Some(
+                load_texture(
+                    diffuse_texture,
+                    device,
+                    queue,
+                    mipmaps,
+                    model::TextureSlot::BaseColor,
+                )
+                .await?,
+            );
+        }
+        // tobj 把 map_Bump/bump/norm 都解析为 normal_texture
+        if let Some(normal_texture) = &mat.normal_texture {
+            textures.normal = Some(
+                load_texture(
+                    normal_texture,
+                    device,
+                    queue,
+                    mipmaps,
+                    model::TextureSlot::Normal,
+                )
+                .await?,
+            );
+        }
+        materials.push(model::Material::new(
+            device,
+            queue,
+            &mat.name,
+            textures,
+            obj_material_uniform(&mat),
             layout,
-            entries: &[
-                wgpu::BindGroupEntry {
-                    binding: 0,
-                    resource: wgpu::BindingResource::TextureView(&diffuse_texture.view),
-                },
-                wgpu::BindGroupEntry {
-                    binding: 1,
-                    resource: wgpu::BindingResource::Sampler(&diffuse_texture.sampler),
-                },
-            ],
-            label: None,
-        });
-
-        materials.push(model::Material {
-            name: mat.name,
-            diffuse_texture,
-            bind_group,
-        })
+        )?);
     }
+    // 没有指定材质或材质编号无效的网格使用追加在末尾的默认材质
+    let default_material = materials.len();
+    let has_material = |m: &tobj::Model| m.mesh.material_id.is_some_and(|id| id < default_material);
+    if !models.iter().all(has_material) {
+        materials.push(model::Material::new(
+            device,
+            queue,
+            "default",
+            model::MaterialTextures::default(),
+            obj_material_uniform(&tobj::Material::default()),
+            layout,
+        )?);
+    }
+
     let meshes = models
-        .into_iter()
+        .iter()
         .map(|m| {
-            let vertices = (0..m.mesh.positions.len() / 3)
-                .map(|i| model::ModelVertex {
-                    position: [
-                        m.mesh.positions[i * 3],
-                        m.mesh.positions[i * 3 + 1],
-                        m.mesh.positions[i * 3 + 2],
-                    ],
-                    tex_coords: [m.mesh.texcoords[i * 2], m.mesh.texcoords[i * 2 + 1]],
-                    normal: [
-                        m.mesh.normals[i * 3],
-                        m.mesh.normals[i * 3 + 1],
-                        m.mesh.normals[i * 3 + 2],
-                    ],
-                })
-                .collect::<Vec<_>>();
+            let (vertices, indices) =
+                obj_mesh_vertices(&m.mesh, &format!("{file_name}: {}", m.name))?;
 
             let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
                 label: Some(&format!("{:?} Vertex Buffer", file_name)),
@@ -111,19 +164,784 @@ pub async fn load_model(
 
             let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
                 label: Some(&format!("{:?} Index Buffer", file_name)),
-                contents: bytemuck::cast_slice(&m.mesh.indices),
+                contents: bytemuck::cast_slice(&indices),
                 usage: wgpu::BufferUsages::INDEX,
             });
 
-            model::Mesh {
+            Ok(model::Mesh {
                 name: file_name.to_string(),
                 vertex_buffer,
                 index_buffer,
-                index_count: m.mesh.indices.len() as u32,
-                material: m.mesh.material_id.unwrap_or(0),
+                index_count: indices.len() as u32,
+                material: m
+                    .mesh
+                    .material_id
+                    .filter(|_| has_material(m))
+                    .unwrap_or(default_material),
+                bounds: model::ModelVertex::bounding_sphere(&vertices),
+                aabb: model::ModelVertex::aabb(&vertices),
+            })
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    Ok(model::Model { meshes, materials })
+}
+
+/// glTF 中一个三角形图元展开后的 CPU 端数据，节点层级的变换已烘焙进顶点
+struct GltfPrimitive {
+    name: String,
+    vertices: Vec<model::ModelVertex>,
+    indices: Vec<u32>,
+    material: Option<usize>,
+}
+
+/// 加载 `.gltf`（外部或 data URI 缓冲区）和 `.glb`（内嵌缓冲区和图像）文件
+async fn load_gltf(
+    file_name: &str,
+    device: &Device,
+    queue: &Queue,
+    mipmaps: &MipmapGenerator,
+    layout: &wgpu::BindGroupLayout,
+) -> anyhow::Result<model::Model> {
+    let data = load_binary(file_name).await?;
+    let gltf::Gltf { document, blob } = gltf::Gltf::from_slice(&data)?;
+    // 外部缓冲区和图像相对于 glTF 文件所在目录解析
+    let path = asset_path(file_name);
+    let base = path.parent();
+    let buffers = gltf::import_buffers(&document, base, blob)?;
+    let images = gltf::import_images(&document, base, &buffers)?;
+
+    let mut materials = Vec::new();
+    for material in document.materials() {
+        materials.push(gltf_material(
+            device, queue, mipmaps, &material, &images, layout,
+        )?);
+    }
+
+    // 没有指定材质的图元使用追加在末尾的默认材质
+    let default_material = materials.len();
+    let primitives = read_gltf_primitives(&document, &buffers, file_name)?;
+    if primitives.iter().any(|p| p.material.is_none()) {
+        // glTF 规范规定的默认材质：白色、完全金属、完全粗糙
+        materials.push(model::Material::new(
+            device,
+            queue,
+            "default",
+            model::MaterialTextures::default(),
+            model::MaterialUniform::new([1.0; 4], 1.0, 1.0),
+            layout,
+        )?);
+    }
+
+    let meshes = primitives
+        .into_iter()
+        .map(|primitive| {
+            let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some(&format!("{:?} Vertex Buffer", primitive.name)),
+                contents: bytemuck::cast_slice(&primitive.vertices),
+                usage: wgpu::BufferUsages::VERTEX,
+            });
+            let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some(&format!("{:?} Index Buffer", primitive.name)),
+                contents: bytemuck::cast_slice(&primitive.indices),
+                usage: wgpu::BufferUsages::INDEX,
+            });
+            model::Mesh {
+                name: primitive.name,
+                vertex_buffer,
+                index_buffer,
+                index_count: primitive.indices.len() as u32,
+                material: primitive.material.unwrap_or(default_material),
+                bounds: model::ModelVertex::bounding_sphere(&primitive.vertices),
+                aabb: model::ModelVertex::aabb(&primitive.vertices),
             }
         })
-        .collect::<Vec<_>>();
+        .collect();
 
     Ok(model::Model { meshes, materials })
 }
+
+/// 遍历默认场景的节点树，把每个三角形图元变换到模型空间
+fn read_gltf_primitives(
+    document: &gltf::Document,
+    buffers: &[gltf::buffer::Data],
+    file_name: &str,
+) -> anyhow::Result<Vec<GltfPrimitive>> {
+    let scene = document
+        .default_scene()
+        .or_else(|| document.scenes().next())
+        .with_context(|| format!("{file_name} 中没有场景"))?;
+
+    let mut primitives = Vec::new();
+    for node in scene.nodes() {
+        read_gltf_node(
+            &node,
+            glam::Mat4::IDENTITY,
+            buffers,
+            file_name,
+            &mut primitives,
+        )?;
+    }
+    Ok(primitives)
+}
+
+fn read_gltf_node(
+    node: &gltf::Node,
+    parent_transform: glam::Mat4,
+    buffers: &[gltf::buffer::Data],
+    file_name: &str,
+    primitives: &mut Vec<GltfPrimitive>,
+) -> anyhow::Result<()> {
+    let transform = parent_transform * glam::Mat4::from_cols_array_2d(&node.transform().matrix());
+
+    if let Some(mesh) = node.mesh() {
+        let name = node.name().or(mesh.name()).unwrap_or(file_name).to_string();
+        for primitive in mesh.primitives() {
+            if primitive.mode() != gltf::mesh::Mode::Triangles {
+                log::warn!("{name}: 跳过非三角形图元 {:?}", primitive.mode());
+                continue;
+            }
+            primitives.push(read_gltf_primitive(&primitive, transform, buffers, &name)?);
+        }
+    }
+
+    for child in node.children() {
+        read_gltf_node(&child, transform, buffers, file_name, primitives)?;
+    }
+    Ok(())
+}
+
+fn read_gltf_primitive(
+    primitive: &gltf::Primitive,
+    transform: glam::Mat4,
+    buffers: &[gltf::buffer::Data],
+    name: &str,
+) -> anyhow::Result<GltfPrimitive> {
+    let reader = primitive.reader(|buffer| Some(&buffers[buffer.index()]));
+
+    let positions = reader
+        .read_positions()
+        .with_context(|| format!("{name} 缺少 POSITION 属性"))?
+        .collect::<Vec<_>>();
+    // 规范要求缺少法线时使用平面法线，先填 0，变换后再按三角形计算
+    let normals = reader
+        .read_normals()
+        .map(|normals| normals.collect::<Vec<_>>());
+    let has_normals = normals.is_some();
+    let normals = normals.unwrap_or_else(|| vec![[0.0; 3]; positions.len()]);
+    let tex_coords = reader
+        .read_tex_coords(0)
+        .map(|tex_coords| tex_coords.into_f32().collect::<Vec<_>>())
+        .unwrap_or_else(|| vec![[0.0; 2]; positions.len()]);
+    let mut indices = reader
+        .read_indices()
+        .map(|indices| indices.into_u32().collect::<Vec<_>>())
+        .unwrap_or_else(|| (0..positions.len() as u32).collect());
+    if !indices.len().is_multiple_of(3) {
+        anyhow::bail!("{name}: 三角形图元的索引数 {} 不是 3 的倍数", indices.len());
+    }
+    if let Some(&index) = indices.iter().find(|&&i| i as usize >= positions.len()) {
+        anyhow::bail!("{name}: 索引 {index} 超出顶点数 {}", positions.len());
+    }
+
+    // 镜像变换会把逆时针的三角形变成顺时针，交换顶点顺序以免被背面剔除
+    if transform.determinant() < 0.0 {
+        for triangle in indices.chunks_exact_mut(3) {
+            triangle.swap(1, 2);
+        }
+    }
+
+    // 法线需要用逆转置矩阵变换，才能在非均匀缩放下保持与表面垂直
+    let normal_matrix = glam::Mat3::from_mat4(transform).inverse().transpose();
+    let mut vertices = positions
+        .iter()
+        .zip(&normals)
+        .zip(&tex_coords)
+        .map(|((&position, &normal), &tex_coords)| model::ModelVertex {
+            position: transform
+                .transform_point3(glam::Vec3::from(position))
+                .to_array(),
+            tex_coords,
+            normal: (normal_matrix * glam::Vec3::from(normal))
+                .normalize_or_zero()
+                .to_array(),
+            tangent: [0.0; 3],
+            bitangent: [0.0; 3],
+        })
+        .collect::<Vec<_>>();
+    if !has_normals {
+        (vertices, indices) = flat_normals(&vertices, &indices);
+    }
+    compute_tangents(&mut vertices, &indices);
+
+    Ok(GltfPrimitive {
+        name: name.to_string(),
+        vertices,
+        indices,
+        material: primitive.material().index(),
+    })
+}
+
+/// 把 tobj 网格转换为顶点和索引：缺少纹理坐标时取 (0, 0)，缺少法线时使用平面法线
+fn obj_mesh_vertices(
+    mesh: &tobj::Mesh,
+    name: &str,
+) -> anyhow::Result<(Vec<model::ModelVertex>, Vec<u32>)> {
+    let vertex_count = mesh.positions.len() / 3;
+    if !mesh.texcoords.is_empty() && mesh.texcoords.len() != vertex_count * 2 {
+        anyhow::bail!("{name}: 纹理坐标数与顶点数 {vertex_count} 不一致");
+    }
+    if !mesh.normals.is_empty() && mesh.normals.len() != vertex_count * 3 {
+        anyhow::bail!("{name}: 法线数与顶点数 {vertex_count} 不一致");
+    }
+    if !mesh.indices.len().is_multiple_of(3) {
+        anyhow::bail!("{name}: 索引数 {} 不是 3 的倍数", mesh.indices.len());
+    }
+    if let Some(&index) = mesh.indices.iter().find(|&&i| i as usize >= vertex_count) {
+        anyhow::bail!("{name}: 索引 {index} 超出顶点数 {vertex_count}");
+    }
+
+    let vertices = (0..vertex_count)
+        .map(|i| model::ModelVertex {
+            position: [
+                mesh.positions[i * 3],
+                mesh.positions[i * 3 + 1],
+                mesh.positions[i * 3 + 2],
+            ],
+            tex_coords: if mesh.texcoords.is_empty() {
+                [0.0; 2]
+            } else {
+                [mesh.texcoords[i * 2], mesh.texcoords[i * 2 + 1]]
+            },
+            normal: if mesh.normals.is_empty() {
+                [0.0; 3]
+            } else {
+                [
+                    mesh.normals[i * 3],
+                    mesh.normals[i * 3 + 1],
+                    mesh.normals[i * 3 + 2],
+                ]
+            },
+            // 下面根据三角形计算
+            tangent: [0.0; 3],
+            bitangent: [0.0; 3],
+        })
+        .collect::<Vec<_>>();
+    let (mut vertices, indices) = if mesh.normals.is_empty() {
+        flat_normals(&vertices, &mesh.indices)
+    } else {
+        (vertices, mesh.indices.clone())
+    };
+    compute_tangents(&mut vertices, &indices);
+    Ok((vertices, indices))
+}
+
+/// 展开索引让每个三角形拥有独立的顶点，法线取三角形所在平面的法向（逆时针为正面）。
+///
+/// 用于缺少法线的网格，`indices` 需已校验。
+fn flat_normals(
+    vertices: &[model::ModelVertex],
+    indices: &[u32],
+) -> (Vec<model::ModelVertex>, Vec<u32>) {
+    let mut flat = Vec::with_capacity(indices.len());
+    for triangle in indices.chunks_exact(3) {
+        let corners = [0, 1, 2].map(|k| vertices[triangle[k] as usize]);
+        let [p0, p1, p2] = corners.map(|v| glam::Vec3::from(v.position));
+        let normal = (p1 - p0).cross(p2 - p0).normalize_or_zero().to_array();
+        flat.extend(corners.map(|v| model::ModelVertex { normal, ..v }));
+    }
+    let indices = (0..flat.len() as u32).collect();
+    (flat, indices)
+}
+
+/// 把 MTL 材质的标量参数换算为金属度-粗糙度材质
+///
+/// MTL 没有金属度，按非金属处理；粗糙度由高光指数 Ns 近似换算，
+/// Ks 取平均值作为镜面反射强度，Ka 作为环境光系数。
+fn obj_material_uniform(mat: &tobj::Material) -> model::MaterialUniform {
+    let [r, g, b] = mat.diffuse.unwrap_or([1.0; 3]);
+    let mut uniform = model::MaterialUniform::new(
+        [r, g, b, mat.dissolve.unwrap_or(1.0)],
+        0.0,
+        shininess_to_roughness(mat.shininess.unwrap_or(32.0)),
+    );
+    if let Some(specular) = mat.specular {
+        uniform.specular = specular.iter().sum::<f32>() / 3.0;
+    }
+    if let Some(ambient) = mat.ambient {
+        uniform.ambient = ambient;
+    }
+    uniform
+}
+
+/// Blinn-Phong 高光指数到粗糙度的近似换算，是 `2 / roughness^4 - 2` 的反函数
+fn shininess_to_roughness(shininess: f32) -> f32 {
+    (2.0 / (shininess.max(0.0) + 2.0)).powf(0.25)
+}
+
+/// 把 glTF 的金属度-粗糙度材质转换为 `model::Material`
+fn gltf_material(
+    device: &Device,
+    queue: &Queue,
+    mipmaps: &MipmapGenerator,
+    material: &gltf::Material,
+    images: &[gltf::image::Data],
+    layout: &wgpu::BindGroupLayout,
+) -> anyhow::Result<model::Material> {
+    let name = material.name().unwrap_or("gltf_material");
+    let pbr = material.pbr_metallic_roughness();
+    let load = |texture: gltf::Texture, slot| -> anyhow::Result<texture::Texture> {
+        let img = gltf_image(&images[texture.source().index()])?;
+        let options = gltf_texture_options(&texture.sampler(), slot);
+        texture::Texture::from_image(device, queue, Some(mipmaps), &img, Some(name), &options)
+    };
+
+    use model::TextureSlot;
+    let textures = model::MaterialTextures {
+        base_color: pbr
+            .base_color_texture()
+            .map(|info| load(info.texture(), TextureSlot::BaseColor))
+            .transpose()?,
+        normal: material
+            .normal_texture()
+            .map(|info| load(info.texture(), TextureSlot::Normal))
+            .transpose()?,
+        metallic_roughness: pbr
+            .metallic_roughness_texture()
+            .map(|info| load(info.texture(), TextureSlot::MetallicRoughness))
+            .transpose()?,
+        occlusion: material
+            .occlusion_texture()
+            .map(|info| load(info.texture(), TextureSlot::Occlusion))
+            .transpose()?,
+        emissive: material
+            .emissive_texture()
+            .map(|info| load(info.texture(), TextureSlot::Emissive))
+            .transpose()?,
+    };
+
+    let mut uniform = model::MaterialUniform::new(
+        pbr.base_color_factor(),
+        pbr.metallic_factor(),
+        pbr.roughness_factor(),
+    );
+    uniform.emissive = material.emissive_factor();
+    if let Some(normal) = material.normal_texture() {
+        uniform.normal_scale = normal.scale();
+    }
+    if let Some(occlusion) = material.occlusion_texture() {
+        uniform.occlusion_strength = occlusion.strength();
+    }
+
+    model::Material::new(device, queue, name, textures, uniform, layout)
+}
+
+/// 把 glTF 采样器的寻址和过滤方式转换为 `TextureOptions`，未指定的过滤方式保持默认
+fn gltf_texture_options(
+    sampler: &gltf::texture::Sampler,
+    slot: model::TextureSlot,
+) -> texture::TextureOptions {
+    use gltf::texture::{MagFilter, MinFilter, WrappingMode};
+    let address_mode = |mode| match mode {
+        WrappingMode::ClampToEdge => wgpu::AddressMode::ClampToEdge,
+        WrappingMode::MirroredRepeat => wgpu::AddressMode::MirrorRepeat,
+        WrappingMode::Repeat => wgpu::AddressMode::Repeat,
+    };
+    let mut options = slot.texture_options().address_mode_uv(
+        address_mode(sampler.wrap_s()),
+        address_mode(sampler.wrap_t()),
+    );
+
+    if let Some(MagFilter::Nearest) = sampler.mag_filter() {
+        options = options.mag_filter(wgpu::FilterMode::Nearest);
+    }
+    let (min_filter, mipmap_filter) = match sampler.min_filter() {
+        None | Some(MinFilter::LinearMipmapLinear) => return options,
+        Some(MinFilter::Nearest) => (wgpu::FilterMode::Nearest, None),
+        Some(MinFilter::Linear) => (wgpu::FilterMode::Linear, None),
+        Some(MinFilter::NearestMipmapNearest) => {
+            (wgpu::FilterMode::Nearest, Some(wgpu::FilterMode::Nearest))
+        }
+        Some(MinFilter::LinearMipmapNearest) => {
+            (wgpu::FilterMode::Linear, Some(wgpu::FilterMode::Nearest))
+        }
+        Some(MinFilter::NearestMipmapLinear) => {
+            (wgpu::FilterMode::Nearest, Some(wgpu::FilterMode::Linear))
+        }
+    };
+    options = options.min_filter(min_filter);
+    match mipmap_filter {
+        Some(filter) => options.mipmap_filter(filter),
+        None => options.mipmaps(false),
+    }
+}
+
+/// 把 glTF 解码后的像素数据转换为 `image::DynamicImage`
+fn gltf_image(data: &gltf::image::Data) -> anyhow::Result<image::DynamicImage> {
+    use gltf::image::Format;
+    let (width, height, pixels) = (data.width, data.height, data.pixels.clone());
+    let img = match data.format {
+        Format::R8 => image::GrayImage::from_raw(width, height, pixels).map(Into::into),
+        Format::R8G8 => image::GrayAlphaImage::from_raw(width, height, pixels).map(Into::into),
+        Format::R8G8B8 => image::RgbImage::from_raw(width, height, pixels).map(Into::into),
+        Format::R8G8B8A8 => image::RgbaImage::from_raw(width, height, pixels).map(Into::into),
+        format => anyhow::bail!("不支持的 glTF 图像格式 {format:?}"),
+    };
+    img.context("glTF 图像数据大小与尺寸不符")
+}
+
+/// 根据三角形的位置和纹理坐标计算每个顶点的切线与副切线。
+///
+/// 共享顶点的切线取相邻三角形的平均值。
+fn compute_tangents(vertices: &mut [model::ModelVertex], indices: &[u32]) {
+    let mut triangles_included = vec![0u32; vertices.len()];
+
+    // 凑不成三角形的剩余索引忽略
+    for c in indices.chunks_exact(3) {
+        let v0 = vertices[c[0] as usize];
+        let v1 = vertices[c[1] as usize];
+        let v2 = vertices[c[2] as usize];
+
+        let pos0 = glam::Vec3::from(v0.position);
+        let pos1 = glam::Vec3::from(v1.position);
+        let pos2 = glam::Vec3::from(v2.position);
+
+        let uv0 = glam::Vec2::from(v0.tex_coords);
+        let uv1 = glam::Vec2::from(v1.tex_coords);
+        let uv2 = glam::Vec2::from(v2.tex_coords);
+
+        // 三角形的两条边及其对应的纹理坐标差
+        let delta_pos1 = pos1 - pos0;
+        let delta_pos2 = pos2 - pos0;
+        let delta_uv1 = uv1 - uv0;
+        let delta_uv2 = uv2 - uv0;
+
+        // 求解 delta_pos = delta_uv.x * T + delta_uv.y * B
+        let r = 1.0 / (delta_uv1.x * delta_uv2.y - delta_uv1.y * delta_uv2.x);
+        if !r.is_finite() {
+            continue;
+        }
+        let tangent = (delta_pos1 * delta_uv2.y - delta_pos2 * delta_uv1.y) * r;
+        // wgpu 的纹理坐标 v 轴朝下，副切线需要翻转
+        let bitangent = (delta_pos2 * delta_uv1.x - delta_pos1 * delta_uv2.x) * -r;
+
+        for &i in c {
+            let vertex = &mut vertices[i as usize];
+            vertex.tangent = (tangent + glam::Vec3::from(vertex.tangent)).into();
+            vertex.bitangent = (bitangent + glam::Vec3::from(vertex.bitangent)).into();
+            triangles_included[i as usize] += 1;
+        }
+    }
+
+    for (vertex, n) in vertices.iter_mut().zip(triangles_included) {
+        if n > 0 {
+            let denom = 1.0 / n as f32;
+            vertex.tangent = (glam::Vec3::from(vertex.tangent) * denom).into();
+            vertex.bitangent = (glam::Vec3::from(vertex.bitangent) * denom).into();
+        }
+    }
+}
+
+/// 创建位于 XZ 平面、边长为 `2 * half_size` 的纯色地面模型
+pub fn create_plane(
+    device: &Device,
+    queue: &Queue,
+    half_size: f32,
+    color: [u8; 4],
+    layout: &wgpu::BindGroupLayout,
+) -> anyhow::Result<model::Model> {
+    let corners = [
+        ([-half_size, 0.0, -half_size], [0.0, 0.0]),
+        ([-half_size, 0.0, half_size], [0.0, 1.0]),
+        ([half_size, 0.0, half_size], [1.0, 1.0]),
+        ([half_size, 0.0, -half_size], [1.0, 0.0]),
+    ];
+    let vertices = corners
+        .iter()
+        .map(|&(position, tex_coords)| model::ModelVertex {
+            position,
+            tex_coords,
+            normal: [0.0, 1.0, 0.0],
+            tangent: [1.0, 0.0, 0.0],
+            bitangent: [0.0, 0.0, -1.0],
+        })
+        .collect::<Vec<_>>();
+    let indices: [u32; 6] = [0, 1, 2, 0, 2, 3];
+
+    let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: Some("Plane Vertex Buffer"),
+        contents: bytemuck::cast_slice(&vertices),
+        usage: wgpu::BufferUsages::VERTEX,
+    });
+    let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: Some("Plane Index Buffer"),
+        contents: bytemuck::cast_slice(&indices),
+        usage: wgpu::BufferUsages::INDEX,
+    });
+
+    let textures = model::MaterialTextures {
+        base_color: Some(texture::Texture::from_color(
+            device,
+            queue,
+            color,
+            "plane_base_color",
+            texture::ColorSpace::Srgb,
+        )?),
+        ..Default::default()
+    };
+    let material = model::Material::new(
+        device,
+        queue,
+        "plane",
+        textures,
+        model::MaterialUniform::new([1.0; 4], 0.0, 0.9),
+        layout,
+    )?;
+
+    Ok(model::Model {
+        meshes: vec![model::Mesh {
+            name: "plane".to_string(),
+            vertex_buffer,
+            index_buffer,
+            index_count: indices.len() as u32,
+            material: 0,
+            bounds: model::ModelVertex::bounding_sphere(&vertices),
+            aabb: model::ModelVertex::aabb(&vertices),
+        }],
+        materials: vec![material],
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read_primitives(file_name: &str) -> Vec<GltfPrimitive> {
+        let path = asset_path(file_name);
+        let (document, buffers, _) = gltf::import(&path).unwrap();
+        read_gltf_primitives(&document, &buffers, file_name).unwrap()
+    }
+
+    fn bounds(vertices: &[model::ModelVertex]) -> (glam::Vec3, glam::Vec3) {
+        vertices.iter().fold(
+            (glam::Vec3::splat(f32::MAX), glam::Vec3::splat(f32::MIN)),
+            |(min, max), v| {
+                let p = glam::Vec3::from(v.position);
+                (min.min(p), max.max(p))
+            },
+        )
+    }
+
+    #[test]
+    fn gltf_node_transforms_are_baked() {
+        for file_name in ["box.gltf", "box.glb"] {
+            let primitives = read_primitives(file_name);
+            assert_eq!(primitives.len(), 1, "{file_name}");
+            let primitive = &primitives[0];
+            assert_eq!(primitive.name, "box");
+            assert_eq!(primitive.indices.len(), 36);
+            assert_eq!(primitive.material, Some(0));
+
+            // 子节点放大 2 倍，父节点沿 Y 平移 1
+            let (min, max) = bounds(&primitive.vertices);
+            assert!(
+                min.abs_diff_eq(glam::Vec3::new(-1.0, 0.0, -1.0), 1e-5),
+                "{min}"
+            );
+            assert!(
+                max.abs_diff_eq(glam::Vec3::new(1.0, 2.0, 1.0), 1e-5),
+                "{max}"
+            );
+        }
+    }
+
+    /// 每个三角形按顶点顺序求出的面法线都应与顶点法线同向，即从外侧看是逆时针
+    fn assert_front_faces_outward(primitive: &GltfPrimitive) {
+        for triangle in primitive.indices.chunks_exact(3) {
+            let [p0, p1, p2] = [0, 1, 2]
+                .map(|i| glam::Vec3::from(primitive.vertices[triangle[i] as usize].position));
+            let face_normal = (p1 - p0).cross(p2 - p0);
+            let normal = glam::Vec3::from(primitive.vertices[triangle[0] as usize].normal);
+            assert!(face_normal.dot(normal) > 0.0, "{triangle:?}");
+        }
+    }
+
+    #[test]
+    fn mirrored_gltf_nodes_keep_front_faces() {
+        let path = asset_path("box.gltf");
+        let json = std::fs::read_to_string(&path).unwrap();
+        assert_front_faces_outward(&read_primitives("box.gltf")[0]);
+
+        // 子节点沿 X 轴镜像
+        let mirrored = json.replacen(
+            "\"scale\": [\n        2.0,",
+            "\"scale\": [\n        -2.0,",
+            1,
+        );
+        assert_ne!(mirrored, json);
+        let gltf = gltf::Gltf::from_slice(mirrored.as_bytes()).unwrap();
+        let buffers = gltf::import_buffers(&gltf.document, path.parent(), None).unwrap();
+        let primitives = read_gltf_primitives(&gltf.document, &buffers, "box.gltf").unwrap();
+        assert_front_faces_outward(&primitives[0]);
+    }
+
+    #[test]
+    fn tangents_ignore_incomplete_triangles() {
+        let vertex = |position: [f32; 3], tex_coords: [f32; 2]| model::ModelVertex {
+            position,
+            tex_coords,
+            normal: [0.0, 0.0, 1.0],
+            tangent: [0.0; 3],
+            bitangent: [0.0; 3],
+        };
+        let mut vertices = [
+            vertex([0.0, 0.0, 0.0], [0.0, 1.0]),
+            vertex([1.0, 0.0, 0.0], [1.0, 1.0]),
+            vertex([0.0, 1.0, 0.0], [0.0, 0.0]),
+        ];
+        compute_tangents(&mut vertices, &[0, 1, 2, 0]);
+        for v in &vertices {
+            assert!(glam::Vec3::from(v.tangent).abs_diff_eq(glam::Vec3::X, 1e-5));
+            assert!(glam::Vec3::from(v.bitangent).abs_diff_eq(glam::Vec3::Y, 1e-5));
+        }
+    }
+
+    #[test]
+    fn obj_material_keeps_ambient_and_specular() {
+        let mtl = "newmtl test\nKa 0.2 0.3 0.4\nKd 0.8 0.8 0.8\nKs 0.3 0.6 0.9\nNs 32\nd 0.5\n";
+        let (materials, _) = tobj::load_mtl_buf(&mut BufReader::new(Cursor::new(mtl))).unwrap();
+        let uniform = obj_material_uniform(&materials[0]);
+
+        assert_eq!(uniform.base_color, [0.8, 0.8, 0.8, 0.5]);
+        assert_eq!(uniform.ambient, [0.2, 0.3, 0.4]);
+        assert!((uniform.specular - 0.6).abs() < 1e-6);
+        assert_eq!(uniform.metallic, 0.0);
+
+        // 缺省 Ka/Ks 时保持中性：白色环境光系数、4% 基础反射率
+        let (materials, _) =
+            tobj::load_mtl_buf(&mut BufReader::new(Cursor::new("newmtl plain\nKd 1 1 1\n")))
+                .unwrap();
+        let uniform = obj_material_uniform(&materials[0]);
+        assert_eq!(uniform.ambient, [1.0; 3]);
+        assert_eq!(uniform.specular, 0.5);
+    }
+
+    #[test]
+    fn bare_obj_gets_default_uvs_and_flat_normals() {
+        // 没有纹理坐标、法线和材质库的四边形，三角化后为两个三角形
+        let obj = "v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\nf 1 2 3 4\n";
+        let (models, materials) = tobj::load_obj_buf(
+            &mut BufReader::new(Cursor::new(obj)),
+            &tobj::LoadOptions {
+                triangulate: true,
+                single_index: true,
+                ..Default::default()
+            },
+            |_| Err(tobj::LoadError::OpenFileFailed),
+        )
+        .unwrap();
+        assert!(materials.unwrap().is_empty());
+        assert_eq!(models[0].mesh.material_id, None);
+
+        let (vertices, indices) = obj_mesh_vertices(&models[0].mesh, "quad").unwrap();
+        assert_eq!(indices, [0, 1, 2, 3, 4, 5]);
+        for vertex in &vertices {
+            assert_eq!(vertex.tex_coords, [0.0; 2]);
+            assert_eq!(vertex.normal, [0.0, 0.0, 1.0]);
+        }
+
+        let mut mesh = models[0].mesh.clone();
+        mesh.indices.push(9);
+        mesh.indices.extend([0, 1]);
+        assert!(obj_mesh_vertices(&mesh, "broken").is_err());
+    }
+
+    #[test]
+    fn bare_obj_model_loads() {
+        let (device, queue) =
+            golden::headless_device().expect("加载模型需要一个图形适配器（软件适配器也可以）");
+        let path = std::env::temp_dir().join("wgpu_01_bare_triangle.obj");
+        std::fs::write(
+            &path,
+            "mtllib missing.mtl\nv 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 3\n",
+        )
+        .unwrap();
+
+        let model = pollster::block_on(load_model(
+            path.to_str().unwrap(),
+            &device,
+            &queue,
+            &MipmapGenerator::new(&device),
+            &model::Material::bind_group_layout(&device),
+        ))
+        .unwrap();
+        assert_eq!(model.meshes.len(), 1);
+        assert_eq!(model.meshes[0].index_count, 3);
+        // 唯一的材质是补上的默认材质
+        assert_eq!(model.materials.len(), 1);
+        assert_eq!(model.meshes[0].material, 0);
+    }
+
+    /// 只有 POSITION 属性的单个三角形，作为两个节点引用：原样的和沿 X 轴镜像的
+    fn glb_without_normals() -> Vec<u8> {
+        let json = r#"{
+            "asset": {"version": "2.0"},
+            "scene": 0,
+            "scenes": [{"nodes": [0, 1]}],
+            "nodes": [{"mesh": 0}, {"mesh": 0, "scale": [-1, 1, 1]}],
+            "meshes": [{"primitives": [{"attributes": {"POSITION": 0}}]}],
+            "accessors": [{
+                "bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3",
+                "min": [0, 0, 0], "max": [1, 1, 0]
+            }],
+            "bufferViews": [{"buffer": 0, "byteLength": 36}],
+            "buffers": [{"byteLength": 36}]
+        }"#;
+        let mut json = json.as_bytes().to_vec();
+        json.resize(json.len().next_multiple_of(4), b' ');
+        let bin: Vec<u8> = [[0.0f32, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]]
+            .as_flattened()
+            .iter()
+            .flat_map(|v| v.to_le_bytes())
+            .collect();
+
+        let mut glb = Vec::new();
+        let length = 12 + 8 + json.len() + 8 + bin.len();
+        for word in [
+            0x4654_6c67,
+            2,
+            length as u32,
+            json.len() as u32,
+            0x4e4f_534a,
+        ] {
+            glb.extend(u32::to_le_bytes(word));
+        }
+        glb.extend(&json);
+        glb.extend(u32::to_le_bytes(bin.len() as u32));
+        glb.extend(u32::to_le_bytes(0x004e_4942));
+        glb.extend(&bin);
+        glb
+    }
+
+    #[test]
+    fn gltf_without_normals_gets_flat_normals() {
+        let (document, buffers, _) = gltf::import_slice(glb_without_normals()).unwrap();
+        let primitives = read_gltf_primitives(&document, &buffers, "triangle.glb").unwrap();
+        assert_eq!(primitives.len(), 2);
+        // 镜像后交换了绕序，正面和法线都仍朝 +Z
+        for primitive in &primitives {
+            assert_eq!(primitive.indices, [0, 1, 2]);
+            for vertex in &primitive.vertices {
+                assert_eq!(vertex.normal, [0.0, 0.0, 1.0]);
+            }
+        }
+    }
+
+    #[test]
+    fn gltf_embedded_image_is_decoded() {
+        let (_, _, images) = gltf::import(asset_path("box.glb")).unwrap();
+        let img = gltf_image(&images[0]).unwrap().to_rgba8();
+        assert_eq!(img.dimensions(), (2, 2));
+        assert_eq!(img.get_pixel(0, 0).0, [200, 60, 60, 255]);
+    }
+}
@@ -0,0 +1,394 @@
+use glam::{Mat4, Quat, Vec3};
+
+use crate::light::Light;
+
+/// 平移、旋转、缩放，按缩放、旋转、平移的顺序作用于顶点
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Transform {
+    pub translation: Vec3,
+    pub rotation: Quat,
+    pub scale: Vec3,
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+impl Transform {
+    pub const IDENTITY: Self = Self {
+        translation: Vec3::ZERO,
+        rotation: Quat::IDENTITY,
+        scale: Vec3::ONE,
+    };
+
+    pub fn from_translation_rotation(translation: Vec3, rotation: Quat) -> Self {
+        Self {
+            translation,
+            rotation,
+            ..Self::IDENTITY
+        }
+    }
+
+    /// 位于 `eye`、-Z 轴指向 `target` 的变换，与相机的约定一致
+    pub fn looking_at(eye: Vec3, target: Vec3, up: Vec3) -> Self {
+        let rotation = Quat::from_mat4(&Mat4::look_at_rh(eye, target, up).inverse());
+        Self::from_translation_rotation(eye, rotation)
+    }
+
+    pub fn matrix(&self) -> Mat4 {
+        Mat4::from_scale_rotation_translation(self.scale, self.rotation, self.translation)
+    }
+}
+
+/// 场景引用的模型，即在 `SceneFile::models` 中的下标
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ModelId(pub usize);
+
+/// 节点句柄，由 `SceneGraph::add` 返回；节点删除后句柄不会被复用
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct NodeId(usize);
+
+/// 节点挂载的对象，随节点的世界变换移动
+#[derive(Debug, Clone, Copy)]
+pub enum NodeContent {
+    /// 只用于组织层级，例如旋转的支点
+    Empty,
+    /// 模型的一个实例
+    Model(ModelId),
+    /// 光源的位置和方向位于节点的局部空间
+    Light(Light),
+    /// 相机位于节点原点，沿 -Z 方向观察
+    Camera,
+}
+
+pub struct Node {
+    pub name: String,
+    pub content: NodeContent,
+    local: Transform,
+    parent: Option<NodeId>,
+    children: Vec<NodeId>,
+    /// `update_world_transforms` 计算的世界矩阵
+    world: Mat4,
+    /// 局部变换或父节点改变后为 `true`
+    dirty: bool,
+}
+
+impl Node {
+    #[allow(dead_code)]
+    pub fn local(&self) -> &Transform {
+        &self.local
+    }
+
+    pub fn world(&self) -> Mat4 {
+        self.world
+    }
+
+    #[allow(dead_code)]
+    pub fn parent(&self) -> Option<NodeId> {
+        self.parent
+    }
+
+    #[allow(dead_code)]
+    pub fn children(&self) -> &[NodeId] {
+        &self.children
+    }
+}
+
+/// 节点的层级树，每个节点的世界矩阵为父节点的世界矩阵乘以自身的局部变换。
+///
+/// 修改局部变换或父节点只会标记节点为脏，`update_world_transforms` 时才重新计算
+/// 脏节点及其整棵子树的世界矩阵，并返回这些节点，渲染端据此同步实例、光源和相机。
+#[derive(Default)]
+pub struct SceneGraph {
+    nodes: Vec<Option<Node>>,
+    roots: Vec<NodeId>,
+    /// 删除后尚未由渲染端处理的节点，见 `take_removed`
+    removed: Vec<NodeId>,
+}
+
+impl SceneGraph {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 添加节点，`parent` 为 `None` 时作为根节点；父节点不存在时返回 `None`
+    pub fn add(
+        &mut self,
+        parent: Option<NodeId>,
+        name: impl Into<String>,
+        local: Transform,
+        content: NodeContent,
+    ) -> Option<NodeId> {
+        let id = NodeId(self.nodes.len());
+        match parent {
+            Some(parent) => self.node_mut(parent)?.children.push(id),
+            None => self.roots.push(id),
+        }
+        self.nodes.push(Some(Node {
+            name: name.into(),
+            content,
+            local,
+            parent,
+            children: Vec::new(),
+            world: Mat4::IDENTITY,
+            dirty: true,
+        }));
+        Some(id)
+    }
+
+    /// 删除节点及其所有子孙，返回被删除的节点
+    #[allow(dead_code)]
+    pub fn remove(&mut self, id: NodeId) -> Vec<NodeId> {
+        let Some(node) = self.get(id) else {
+            return Vec::new();
+        };
+        let parent = node.parent;
+        self.siblings_mut(parent).retain(|&child| child != id);
+
+        let mut removed = Vec::new();
+        let mut stack = vec![id];
+        while let Some(id) = stack.pop() {
+            if let Some(node) = self.nodes[id.0].take() {
+                stack.extend(node.children);
+                removed.push(id);
+            }
+        }
+        self.removed.extend_from_slice(&removed);
+        removed
+    }
+
+    /// 取出上次调用以来删除的节点，渲染端据此释放节点占用的实例和光源
+    pub fn take_removed(&mut self) -> Vec<NodeId> {
+        std::mem::take(&mut self.removed)
+    }
+
+    /// 把节点移到 `parent` 下，局部变换保持不变；会形成环或节点不存在时返回 `false`
+    #[allow(dead_code)]
+    pub fn set_parent(&mut self, id: NodeId, parent: Option<NodeId>) -> bool {
+        if self.get(id).is_none() {
+            return false;
+        }
+        if let Some(parent) = parent {
+            // 新父节点不能是自身或自身的子孙
+            let mut ancestor = Some(parent);
+            while let Some(node) = ancestor {
+                if node == id {
+                    return false;
+                }
+                match self.get(node) {
+                    Some(node) => ancestor = node.parent,
+                    None => return false,
+                }
+            }
+        }
+
+        let old_parent = self.nodes[id.0].as_ref().and_then(|node| node.parent);
+        self.siblings_mut(old_parent).retain(|&child| child != id);
+        self.siblings_mut(parent).push(id);
+        let node = self.node_mut(id).unwrap();
+        node.parent = parent;
+        node.dirty = true;
+        true
+    }
+
+    pub fn get(&self, id: NodeId) -> Option<&Node> {
+        self.nodes.get(id.0)?.as_ref()
+    }
+
+    fn node_mut(&mut self, id: NodeId) -> Option<&mut Node> {
+        self.nodes.get_mut(id.0)?.as_mut()
+    }
+
+    /// 父节点的子节点列表，`None` 表示根节点列表
+    fn siblings_mut(&mut self, parent: Option<NodeId>) -> &mut Vec<NodeId> {
+        match parent.and_then(|parent| self.nodes[parent.0].as_mut()) {
+            Some(parent) => &mut parent.children,
+            None => &mut self.roots,
+        }
+    }
+
+    /// 获取可修改的局部变换，调用即视为变换已改变
+    pub fn local_mut(&mut self, id: NodeId) -> Option<&mut Transform> {
+        let node = self.node_mut(id)?;
+        node.dirty = true;
+        Some(&mut node.local)
+    }
+
+    /// 按名称查找第一个匹配的节点
+    #[allow(dead_code)]
+    pub fn find(&self, name: &str) -> Option<NodeId> {
+        self.iter()
+            .find(|(_, node)| node.name == name)
+            .map(|(id, _)| id)
+    }
+
+    /// 所有节点，按添加顺序
+    pub fn iter(&self) -> impl Iterator<Item = (NodeId, &Node)> {
+        self.nodes
+            .iter()
+            .enumerate()
+            .filter_map(|(i, node)| Some((NodeId(i), node.as_ref()?)))
+    }
+
+    /// 把所有节点标记为脏，渲染端重建后据此重新同步整个场景
+    pub fn mark_all_dirty(&mut self) {
+        for node in self.nodes.iter_mut().flatten() {
+            node.dirty = true;
+        }
+    }
+
+    /// 重新计算脏节点及其子树的世界矩阵，按从父到子的顺序返回世界矩阵改变了的节点
+    pub fn update_world_transforms(&mut self) -> Vec<NodeId> {
+        let mut changed = Vec::new();
+        // (节点, 父节点的世界矩阵, 父节点是否改变)
+        let mut stack = self
+            .roots
+            .iter()
+            .rev()
+            .map(|&root| (root, Mat4::IDENTITY, false))
+            .collect::<Vec<_>>();
+        while let Some((id, parent_world, parent_changed)) = stack.pop() {
+            let Some(node) = self.nodes[id.0].as_mut() else {
+                continue;
+            };
+            let changed_here = node.dirty || parent_changed;
+            if changed_here {
+                node.world = parent_world * node.local.matrix();
+                node.dirty = false;
+                changed.push(id);
+            }
+            let world = node.world;
+            stack.extend(
+                node.children
+                    .iter()
+                    .rev()
+                    .map(|&child| (child, world, changed_here)),
+            );
+        }
+        changed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn translation(x: f32, y: f32, z: f32) -> Transform {
+        Transform {
+            translation: Vec3::new(x, y, z),
+            ..Transform::IDENTITY
+        }
+    }
+
+    fn position(scene: &SceneGraph, id: NodeId) -> Vec3 {
+        scene.get(id).unwrap().world().transform_point3(Vec3::ZERO)
+    }
+
+    #[test]
+    fn world_transforms_propagate_only_from_dirty_nodes() {
+        let mut scene = SceneGraph::new();
+        let root = scene
+            .add(None, "root", translation(1.0, 0.0, 0.0), NodeContent::Empty)
+            .unwrap();
+        let arm = scene
+            .add(
+                Some(root),
+                "arm",
+                translation(0.0, 2.0, 0.0),
+                NodeContent::Empty,
+            )
+            .unwrap();
+        let hand = scene
+            .add(
+                Some(arm),
+                "hand",
+                translation(0.0, 0.0, 3.0),
+                NodeContent::Empty,
+            )
+            .unwrap();
+        let other = scene
+            .add(None, "other", Transform::IDENTITY, NodeContent::Empty)
+            .unwrap();
+
+        assert_eq!(
+            scene.update_world_transforms(),
+            vec![root, arm, hand, other]
+        );
+        assert_eq!(position(&scene, hand), Vec3::new(1.0, 2.0, 3.0));
+        assert!(scene.update_world_transforms().is_empty());
+
+        // 绕 arm 的原点旋转 90°，hand 随之移动，兄弟树不受影响
+        scene.local_mut(arm).unwrap().rotation = Quat::from_rotation_y(std::f32::consts::FRAC_PI_2);
+        assert_eq!(scene.update_world_transforms(), vec![arm, hand]);
+        assert!(position(&scene, hand).distance(Vec3::new(4.0, 2.0, 0.0)) < 1e-5);
+        assert_eq!(scene.find("hand"), Some(hand));
+    }
+
+    #[test]
+    fn reparenting_rejects_cycles_and_removal_drops_subtree() {
+        let mut scene = SceneGraph::new();
+        let a = scene
+            .add(None, "a", translation(1.0, 0.0, 0.0), NodeContent::Empty)
+            .unwrap();
+        let b = scene
+            .add(Some(a), "b", translation(0.0, 1.0, 0.0), NodeContent::Empty)
+            .unwrap();
+        let c = scene
+            .add(None, "c", translation(0.0, 0.0, 5.0), NodeContent::Empty)
+            .unwrap();
+        scene.update_world_transforms();
+
+        assert!(!scene.set_parent(a, Some(b)));
+        assert!(!scene.set_parent(a, Some(a)));
+        assert!(scene.set_parent(b, Some(c)));
+        assert_eq!(scene.update_world_transforms(), vec![b]);
+        assert_eq!(position(&scene, b), Vec3::new(0.0, 1.0, 5.0));
+        assert_eq!(scene.get(c).unwrap().children(), &[b]);
+        assert!(scene.get(a).unwrap().children().is_empty());
+
+        assert_eq!(scene.remove(c), vec![c, b]);
+        assert_eq!(scene.take_removed(), vec![c, b]);
+        assert!(scene.take_removed().is_empty());
+        assert!(scene.get(b).is_none());
+        assert!(
+            scene
+                .add(Some(c), "d", Transform::IDENTITY, NodeContent::Empty)
+                .is_none()
+        );
+        assert_eq!(scene.iter().map(|(id, _)| id).collect::<Vec<_>>(), vec![a]);
+    }
+
+    #[test]
+    fn lights_follow_their_node() {
+        let matrix = Transform::from_translation_rotation(
+            Vec3::new(0.0, 5.0, 0.0),
+            Quat::from_rotation_x(-std::f32::consts::FRAC_PI_2),
+        )
+        .matrix();
+        let spot = Light::Spot {
+            position: Vec3::ZERO,
+            direction: Vec3::NEG_Z,
+            color: Vec3::ONE,
+            intensity: 1.0,
+            range: 10.0,
+            inner_angle: 0.1,
+            outer_angle: 0.2,
+        };
+        let Light::Spot {
+            position,
+            direction,
+            ..
+        } = spot.transformed(&matrix)
+        else {
+            unreachable!();
+        };
+        assert_eq!(position, Vec3::new(0.0, 5.0, 0.0));
+        assert!(direction.distance(Vec3::NEG_Y) < 1e-5);
+
+        let camera = Transform::looking_at(Vec3::new(0.0, 3.0, 4.0), Vec3::ZERO, Vec3::Y);
+        let forward = camera.matrix().transform_vector3(Vec3::NEG_Z);
+        assert!(forward.distance(Vec3::new(0.0, -0.6, -0.8)) < 1e-5);
+    }
+}
@@ -0,0 +1,775 @@
+use std::path::Path;
+
+use anyhow::{Context, anyhow, bail};
+use glam::{Quat, Vec3};
+use serde::Deserialize;
+
+use crate::camera::Camera;
+use crate::light::{Light, MAX_LIGHTS};
+use crate::resources;
+use crate::scene::{ModelId, NodeContent, NodeId, SceneGraph, Transform};
+use crate::tonemap::Tonemapper;
+
+/// 启动时加载的场景描述，RON 或 JSON 格式，按扩展名区分，见 `SceneFile::load`。
+///
+/// 角度一律以度为单位，向量和颜色写成数组（RON 中为元组）；模型、环境贴图等文件位于 assets 目录。
+/// 没有指定场景文件时使用内置的 assets/scene.ron。
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SceneFile {
+    /// 场景通道的清屏色（线性 RGB）
+    #[serde(default = "default_clear_color")]
+    pub clear_color: [f64; 3],
+    #[serde(default)]
+    pub render: RenderSettings,
+    pub camera: CameraDesc,
+    /// 节点按名字引用，`ModelId` 为在其中的下标
+    pub models: Vec<ModelDesc>,
+    #[serde(default)]
+    pub nodes: Vec<NodeDesc>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RenderSettings {
+    /// 场景通道的 MSAA 采样数，适配器不支持时退回到更小的可用值；命令行的 `--msaa` 优先
+    pub msaa: u32,
+    pub tonemapper: Tonemapper,
+    /// 曝光值（EV）
+    pub exposure: f32,
+    /// 等距柱状投影的 HDR 环境贴图
+    pub environment: String,
+    /// 阴影贴图需要覆盖的球形区域
+    pub shadow_center: [f32; 3],
+    pub shadow_radius: f32,
+}
+
+impl Default for RenderSettings {
+    fn default() -> Self {
+        Self {
+            msaa: 4,
+            tonemapper: Tonemapper::Aces,
+            exposure: 0.0,
+            environment: "sky.hdr".to_string(),
+            shadow_center: [0.0; 3],
+            shadow_radius: 20.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CameraDesc {
+    pub eye: [f32; 3],
+    pub target: [f32; 3],
+    #[serde(default = "default_up")]
+    pub up: [f32; 3],
+    /// 垂直视角（度）
+    #[serde(default = "default_fovy")]
+    pub fovy: f32,
+    #[serde(default = "default_znear")]
+    pub znear: f32,
+    #[serde(default = "default_zfar")]
+    pub zfar: f32,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ModelDesc {
+    pub name: String,
+    pub source: ModelSource,
+    /// 地面等只接收阴影的模型设为 `false`
+    #[serde(default = "default_true")]
+    pub casts_shadow: bool,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub enum ModelSource {
+    /// OBJ 或 glTF 文件，见 `resources::load_model`
+    File(String),
+    /// XZ 平面上法线朝上的正方形，见 `resources::create_plane`
+    Plane { half_size: f32, color: [u8; 4] },
+}
+
+/// 场景图中的一个节点，变换相对于父节点
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NodeDesc {
+    pub name: String,
+    pub translation: [f32; 3],
+    pub rotation: Option<AxisAngle>,
+    pub scale: [f32; 3],
+    pub content: ContentDesc,
+    /// 绕局部轴持续自转，`degrees` 为每秒转过的角度
+    pub spin: Option<AxisAngle>,
+    /// 在节点下按网格生成模型实例，排在 `children` 之前
+    pub grid: Option<GridDesc>,
+    pub children: Vec<NodeDesc>,
+}
+
+impl Default for NodeDesc {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            translation: [0.0; 3],
+            rotation: None,
+            scale: [1.0; 3],
+            content: ContentDesc::Empty,
+            spin: None,
+            grid: None,
+            children: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub enum ContentDesc {
+    #[default]
+    Empty,
+    /// `SceneFile::models` 中模型的名字
+    Model(String),
+    Light(LightDesc),
+}
+
+/// 与 `Light` 对应，位置和方向位于节点的局部空间
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub enum LightDesc {
+    Point {
+        #[serde(default)]
+        position: [f32; 3],
+        #[serde(default = "default_light_color")]
+        color: [f32; 3],
+        intensity: f32,
+        range: f32,
+    },
+    Directional {
+        direction: [f32; 3],
+        #[serde(default = "default_light_color")]
+        color: [f32; 3],
+        intensity: f32,
+    },
+    Spot {
+        #[serde(default)]
+        position: [f32; 3],
+        direction: [f32; 3],
+        #[serde(default = "default_light_color")]
+        color: [f32; 3],
+        intensity: f32,
+        range: f32,
+        /// 半角（度）
+        inner_angle: f32,
+        outer_angle: f32,
+    },
+}
+
+impl LightDesc {
+    pub fn to_light(self) -> Light {
+        match self {
+            LightDesc::Point {
+                position,
+                color,
+                intensity,
+                range,
+            } => Light::Point {
+                position: position.into(),
+                color: color.into(),
+                intensity,
+                range,
+            },
+            LightDesc::Directional {
+                direction,
+                color,
+                intensity,
+            } => Light::Directional {
+                direction: direction.into(),
+                color: color.into(),
+                intensity,
+            },
+            LightDesc::Spot {
+                position,
+                direction,
+                color,
+                intensity,
+                range,
+                inner_angle,
+                outer_angle,
+            } => Light::Spot {
+                position: position.into(),
+                direction: direction.into(),
+                color: color.into(),
+                intensity,
+                range,
+                inner_angle: inner_angle.to_radians(),
+                outer_angle: outer_angle.to_radians(),
+            },
+        }
+    }
+}
+
+/// 绕 `axis` 旋转 `degrees` 度，轴不必是单位向量
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AxisAngle {
+    pub axis: [f32; 3],
+    pub degrees: f32,
+}
+
+impl AxisAngle {
+    pub fn to_quat(self) -> Quat {
+        Quat::from_axis_angle(Vec3::from(self.axis).normalize(), self.degrees.to_radians())
+    }
+}
+
+/// XZ 平面上以节点原点为中心的实例网格
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct GridDesc {
+    pub model: String,
+    /// X 和 Z 方向的实例数
+    pub count: [u32; 2],
+    /// 相邻实例的间距
+    pub spacing: f32,
+    /// 实例绕“中心指向实例”的轴倾斜的角度，中心处的实例不倾斜
+    #[serde(default)]
+    pub tilt: f32,
+    /// 每个实例绕局部轴的自转，含义同 `NodeDesc::spin`
+    #[serde(default)]
+    pub spin: Option<AxisAngle>,
+}
+
+impl GridDesc {
+    /// 按先 X 后 Z 的顺序排列的实例变换
+    pub fn transforms(&self) -> impl Iterator<Item = Transform> + '_ {
+        let [columns, rows] = self.count;
+        (0..rows).flat_map(move |z| {
+            (0..columns).map(move |x| {
+                let x = self.spacing * (x as f32 - columns as f32 / 2.0);
+                let z = self.spacing * (z as f32 - rows as f32 / 2.0);
+                let pos = Vec3::new(x, 0.0, z);
+                let rotation = if pos.length() < f32::EPSILON {
+                    Quat::IDENTITY
+                } else {
+                    Quat::from_axis_angle(pos.normalize(), self.tilt.to_radians())
+                };
+                Transform::from_translation_rotation(pos, rotation)
+            })
+        })
+    }
+}
+
+/// 由 `SceneFile::populate` 生成的自转节点，每秒绕局部轴 `axis` 转过 `degrees` 度
+#[derive(Debug, Clone, Copy)]
+pub struct Spin {
+    pub node: NodeId,
+    pub axis: Vec3,
+    pub degrees: f32,
+}
+
+impl Spin {
+    fn new(node: NodeId, spin: AxisAngle) -> Self {
+        Self {
+            node,
+            axis: Vec3::from(spin.axis).normalize(),
+            degrees: spin.degrees,
+        }
+    }
+}
+
+fn default_clear_color() -> [f64; 3] {
+    [0.1, 0.2, 0.3]
+}
+
+fn default_up() -> [f32; 3] {
+    [0.0, 1.0, 0.0]
+}
+
+fn default_fovy() -> f32 {
+    45.0
+}
+
+fn default_znear() -> f32 {
+    0.1
+}
+
+fn default_zfar() -> f32 {
+    100.0
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_light_color() -> [f32; 3] {
+    [1.0; 3]
+}
+
+impl SceneFile {
+    /// 读取、解析并校验场景文件，错误信息包含文件路径以及出错的行列或字段
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let source = std::fs::read_to_string(path)
+            .with_context(|| format!("无法读取场景文件 {}", path.display()))?;
+        let scene = Self::parse(&source, path)?;
+        scene
+            .validate()
+            .with_context(|| format!("场景文件 {} 无效", path.display()))?;
+        Ok(scene)
+    }
+
+    /// 内置的默认场景 assets/scene.ron
+    pub fn builtin() -> Self {
+        let path = Path::new("assets/scene.ron");
+        Self::parse(include_str!("../assets/scene.ron"), path)
+            .and_then(|scene| scene.validate().map(|()| scene))
+            .expect("内置场景 assets/scene.ron 无效")
+    }
+
+    /// `path` 只用于判断格式和报告错误
+    fn parse(source: &str, path: &Path) -> anyhow::Result<Self> {
+        let extension = path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .map(str::to_ascii_lowercase);
+        match extension.as_deref() {
+            // ron 的错误以“行:列: ”开头
+            Some("ron") => ron::from_str(source).map_err(|err| anyhow!("{}:{err}", path.display())),
+            Some("json") => {
+                serde_json::from_str(source).map_err(|err| anyhow!("{}: {err}", path.display()))
+            }
+            _ => bail!(
+                "无法识别场景文件 {} 的格式，扩展名应为 .ron 或 .json",
+                path.display()
+            ),
+        }
+    }
+
+    /// 检查解析时无法发现的错误，一次列出所有问题
+    pub fn validate(&self) -> anyhow::Result<()> {
+        let mut errors = Vec::new();
+
+        let msaa = self.render.msaa;
+        if ![1, 2, 4, 8].contains(&msaa) {
+            errors.push(format!(
+                "render.msaa: 采样数应为 1、2、4 或 8，而不是 {msaa}"
+            ));
+        }
+        if !resources::asset_path(&self.render.environment).is_file() {
+            errors.push(format!(
+                "render.environment: 找不到环境贴图 {}",
+                self.render.environment
+            ));
+        }
+        if self.render.shadow_radius <= 0.0 {
+            errors.push("render.shadow_radius: 应大于 0".to_string());
+        }
+        if self.clear_color.iter().any(|c| !(0.0..=1.0).contains(c)) {
+            errors.push(format!(
+                "clear_color: 分量应在 0 到 1 之间: {:?}",
+                self.clear_color
+            ));
+        }
+
+        let camera = &self.camera;
+        let forward = Vec3::from(camera.target) - Vec3::from(camera.eye);
+        if forward.length() < f32::EPSILON {
+            errors.push("camera: eye 与 target 重合".to_string());
+        } else if forward.cross(camera.up.into()).length() < f32::EPSILON {
+            errors.push("camera.up: 不能与视线平行".to_string());
+        }
+        if !(camera.fovy > 0.0 && camera.fovy < 180.0) {
+            errors.push(format!(
+                "camera.fovy: 应在 0 到 180 度之间，而不是 {}",
+                camera.fovy
+            ));
+        }
+        if !(camera.znear > 0.0 && camera.znear < camera.zfar) {
+            errors.push(format!(
+                "camera: 需要 0 < znear < zfar，而不是 znear = {}、zfar = {}",
+                camera.znear, camera.zfar
+            ));
+        }
+
+        for (i, model) in self.models.iter().enumerate() {
+            let path = format!("models[{i}]");
+            if model.name.is_empty() {
+                errors.push(format!("{path}.name: 不能为空"));
+            } else if self.models[..i].iter().any(|m| m.name == model.name) {
+                errors.push(format!("{path}.name: 模型名 \"{}\" 重复", model.name));
+            }
+            match &model.source {
+                ModelSource::File(file) if !resources::asset_path(file).is_file() => {
+                    errors.push(format!("{path}.source: 找不到模型文件 {file}"));
+                }
+                ModelSource::Plane { half_size, .. } if *half_size <= 0.0 => {
+                    errors.push(format!("{path}.source: 平面的 half_size 应大于 0"));
+                }
+                _ => {}
+            }
+        }
+
+        let mut light_count = 0;
+        for (i, node) in self.nodes.iter().enumerate() {
+            self.validate_node(node, &format!("nodes[{i}]"), &mut light_count, &mut errors);
+        }
+        if light_count > MAX_LIGHTS {
+            errors.push(format!(
+                "nodes: 共有 {light_count} 个光源，最多支持 {MAX_LIGHTS} 个"
+            ));
+        }
+
+        if errors.is_empty() {
+            return Ok(());
+        }
+        bail!("{} 处错误:\n  {}", errors.len(), errors.join("\n  "))
+    }
+
+    fn validate_node(
+        &self,
+        node: &NodeDesc,
+        path: &str,
+        light_count: &mut usize,
+        errors: &mut Vec<String>,
+    ) {
+        // 带上节点名，便于在文件中定位
+        let path = if node.name.is_empty() {
+            path.to_string()
+        } else {
+            format!("{path} (\"{}\")", node.name)
+        };
+        let check_axis = |field: &str, rotation: &Option<AxisAngle>, errors: &mut Vec<String>| {
+            if rotation.is_some_and(|r| Vec3::from(r.axis).length() < f32::EPSILON) {
+                errors.push(format!("{path}.{field}: 旋转轴不能为零向量"));
+            }
+        };
+        let check_model = |field: &str, name: &str, errors: &mut Vec<String>| {
+            if self.model_id(name).is_none() {
+                let known = self
+                    .models
+                    .iter()
+                    .map(|m| format!("\"{}\"", m.name))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                errors.push(format!(
+                    "{path}.{field}: 未定义的模型 \"{name}\"，可用的模型: [{known}]"
+                ));
+            }
+        };
+
+        check_axis("rotation", &node.rotation, errors);
+        check_axis("spin", &node.spin, errors);
+        if node.scale.contains(&0.0) {
+            errors.push(format!("{path}.scale: 分量不能为 0: {:?}", node.scale));
+        }
+        match &node.content {
+            ContentDesc::Empty => {}
+            ContentDesc::Model(name) => check_model("content", name, errors),
+            ContentDesc::Light(light) => {
+                *light_count += 1;
+                let (intensity, range, direction, angles) = match *light {
+                    LightDesc::Point {
+                        intensity, range, ..
+                    } => (intensity, Some(range), None, None),
+                    LightDesc::Directional {
+                        direction,
+                        intensity,
+                        ..
+                    } => (intensity, None, Some(direction), None),
+                    LightDesc::Spot {
+                        direction,
+                        intensity,
+                        range,
+                        inner_angle,
+                        outer_angle,
+                        ..
+                    } => (
+                        intensity,
+                        Some(range),
+                        Some(direction),
+                        Some((inner_angle, outer_angle)),
+                    ),
+                };
+                if intensity < 0.0 {
+                    errors.push(format!("{path}.content: 光源强度不能为负"));
+                }
+                if range.is_some_and(|range| range <= 0.0) {
+                    errors.push(format!("{path}.content: 光源的 range 应大于 0"));
+                }
+                if direction.is_some_and(|d| Vec3::from(d).length() < f32::EPSILON) {
+                    errors.push(format!("{path}.content: 光源方向不能为零向量"));
+                }
+                if let Some((inner, outer)) = angles
+                    && !(0.0 <= inner && inner <= outer && outer < 90.0)
+                {
+                    errors.push(format!(
+                        "{path}.content: 聚光灯需要 0 <= inner_angle <= outer_angle < 90，而不是 {inner} 和 {outer}"
+                    ));
+                }
+            }
+        }
+        if let Some(grid) = &node.grid {
+            check_model("grid.model", &grid.model, errors);
+            check_axis("grid.spin", &grid.spin, errors);
+            if grid.count.contains(&0) {
+                errors.push(format!("{path}.grid.count: 不能为 0: {:?}", grid.count));
+            }
+            if grid.spacing <= 0.0 {
+                errors.push(format!("{path}.grid.spacing: 应大于 0"));
+            }
+        }
+        for (i, child) in node.children.iter().enumerate() {
+            self.validate_node(child, &format!("{path}.children[{i}]"), light_count, errors);
+        }
+    }
+
+    /// 把模型的来源换成 `file`，节点仍按原来的模型名引用。
+    ///
+    /// `name` 为 `None` 时替换第一个来自文件的模型；替换后需重新 `validate`。
+    pub fn override_model(&mut self, name: Option<&str>, file: String) -> anyhow::Result<()> {
+        let model = match name {
+            Some(name) => self
+                .models
+                .iter_mut()
+                .find(|model| model.name == name)
+                .with_context(|| format!("场景中没有名为 \"{name}\" 的模型"))?,
+            None => self
+                .models
+                .iter_mut()
+                .find(|model| matches!(model.source, ModelSource::File(_)))
+                .context("场景中没有来自文件的模型，请用 名字=文件 指定要替换的模型")?,
+        };
+        model.source = ModelSource::File(file);
+        Ok(())
+    }
+
+    /// 名为 `name` 的模型在 `models` 中的下标
+    pub fn model_id(&self, name: &str) -> Option<ModelId> {
+        self.models
+            .iter()
+            .position(|model| model.name == name)
+            .map(ModelId)
+    }
+
+    /// 宽高比固定为 4:3，与窗口大小无关
+    pub fn camera(&self) -> Camera {
+        Camera {
+            eye: self.camera.eye.into(),
+            target: self.camera.target.into(),
+            up: self.camera.up.into(),
+            aspect: 4.0 / 3.0,
+            fovy: self.camera.fovy,
+            znear: self.camera.znear,
+            zfar: self.camera.zfar,
+        }
+    }
+
+    pub fn clear_color(&self) -> wgpu::Color {
+        let [r, g, b] = self.clear_color;
+        wgpu::Color { r, g, b, a: 1.0 }
+    }
+
+    /// 把节点树和名为 "camera" 的相机节点添加到 `scene`，返回需要自转的节点。
+    ///
+    /// 需先通过 `validate`，引用了未定义模型的节点会被跳过。
+    pub fn populate(&self, scene: &mut SceneGraph) -> Vec<Spin> {
+        let camera = self.camera();
+        scene.add(
+            None,
+            "camera",
+            Transform::looking_at(camera.eye, camera.target, camera.up),
+            NodeContent::Camera,
+        );
+        let mut spins = Vec::new();
+        for node in &self.nodes {
+            self.add_node(scene, None, node, &mut spins);
+        }
+        spins
+    }
+
+    fn add_node(
+        &self,
+        scene: &mut SceneGraph,
+        parent: Option<NodeId>,
+        node: &NodeDesc,
+        spins: &mut Vec<Spin>,
+    ) {
+        let content = match &node.content {
+            ContentDesc::Empty => NodeContent::Empty,
+            ContentDesc::Model(name) => match self.model_id(name) {
+                Some(model) => NodeContent::Model(model),
+                None => return,
+            },
+            ContentDesc::Light(light) => NodeContent::Light(light.to_light()),
+        };
+        let local = Transform {
+            translation: node.translation.into(),
+            rotation: node.rotation.map_or(Quat::IDENTITY, AxisAngle::to_quat),
+            scale: node.scale.into(),
+        };
+        let Some(id) = scene.add(parent, node.name.clone(), local, content) else {
+            return;
+        };
+        if let Some(spin) = node.spin {
+            spins.push(Spin::new(id, spin));
+        }
+
+        if let Some(grid) = &node.grid
+            && let Some(model) = self.model_id(&grid.model)
+        {
+            for (i, local) in grid.transforms().enumerate() {
+                let name = format!("{}_{i}", grid.model);
+                let Some(instance) = scene.add(Some(id), name, local, NodeContent::Model(model))
+                else {
+                    continue;
+                };
+                if let Some(spin) = grid.spin {
+                    spins.push(Spin::new(instance, spin));
+                }
+            }
+        }
+        for child in &node.children {
+            self.add_node(scene, Some(id), child, spins);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(source: &str, file_name: &str) -> anyhow::Result<SceneFile> {
+        let path = Path::new(file_name);
+        SceneFile::parse(source, path).and_then(|scene| scene.validate().map(|()| scene))
+    }
+
+    #[test]
+    fn builtin_scene_populates_graph() {
+        let scene_file = SceneFile::builtin();
+        let mut scene = SceneGraph::new();
+        let spins = scene_file.populate(&mut scene);
+
+        let cube = scene_file.model_id("cube").unwrap();
+        let cubes = scene
+            .iter()
+            .filter(|(_, node)| matches!(node.content, NodeContent::Model(model) if model == cube))
+            .count();
+        assert_eq!(cubes, 100);
+        assert!(scene.find("camera").is_some());
+        // 每个立方体和点光源的支点各自转
+        assert_eq!(spins.len(), 101);
+    }
+
+    #[test]
+    fn json_and_ron_describe_the_same_scene() {
+        let ron = r#"(
+            camera: (eye: (0.0, 2.0, 5.0), target: (0.0, 0.0, 0.0)),
+            models: [(name: "ground", source: Plane(half_size: 4.0, color: (255, 255, 255, 255)))],
+            nodes: [(name: "floor", content: Model("ground"), scale: (2.0, 1.0, 2.0))],
+        )"#;
+        let json = r#"{
+            "camera": {"eye": [0.0, 2.0, 5.0], "target": [0.0, 0.0, 0.0]},
+            "models": [{"name": "ground", "source": {"Plane": {"half_size": 4.0, "color": [255, 255, 255, 255]}}}],
+            "nodes": [{"name": "floor", "content": {"Model": "ground"}, "scale": [2.0, 1.0, 2.0]}]
+        }"#;
+        for scene in [parse(ron, "a.ron").unwrap(), parse(json, "a.json").unwrap()] {
+            assert_eq!(scene.camera.fovy, 45.0);
+            assert_eq!(scene.render.msaa, 4);
+            assert_eq!(scene.nodes[0].scale, [2.0, 1.0, 2.0]);
+            assert_eq!(scene.nodes[0].translation, [0.0; 3]);
+            assert!(scene.models[0].casts_shadow);
+        }
+        assert!(
+            parse(ron, "a.yaml")
+                .unwrap_err()
+                .to_string()
+                .contains(".ron 或 .json")
+        );
+    }
+
+    #[test]
+    fn errors_point_at_the_offending_field() {
+        // 语法错误带有行列号
+        let err = parse("(\n  camera: (eye: (0.0, 0.0, 1.0)\n", "broken.ron").unwrap_err();
+        assert!(err.to_string().starts_with("broken.ron:"), "{err}");
+        let err = parse(r#"{"camera": {"eye": [0, 0, 1]}, "models": 3}"#, "a.json").unwrap_err();
+        assert!(err.to_string().contains("line 1"), "{err}");
+
+        let ron = r#"(
+            camera: (eye: (0.0, 0.0, 0.0), target: (0.0, 0.0, 0.0), znear: 10.0, zfar: 1.0),
+            render: (msaa: 3),
+            models: [(name: "cube", source: File("missing.obj"))],
+            nodes: [
+                (name: "grid", grid: Some((model: "cube", count: (0, 2), spacing: 1.0)), children: [
+                    (content: Model("cubee"), rotation: Some((axis: (0.0, 0.0, 0.0), degrees: 30.0))),
+                ]),
+            ],
+        )"#;
+        let message = parse(ron, "a.ron").unwrap_err().to_string();
+        for expected in [
+            "7 处错误",
+            "render.msaa",
+            "camera: eye 与 target 重合",
+            "znear = 10",
+            "找不到模型文件 missing.obj",
+            "nodes[0] (\"grid\").grid.count",
+            "nodes[0] (\"grid\").children[0].content: 未定义的模型 \"cubee\"，可用的模型: [\"cube\"]",
+            "nodes[0] (\"grid\").children[0].rotation: 旋转轴不能为零向量",
+        ] {
+            assert!(message.contains(expected), "缺少 {expected:?}:\n{message}");
+        }
+    }
+
+    #[test]
+    fn model_overrides_replace_sources() {
+        let mut scene = SceneFile::builtin();
+        scene.override_model(None, "box.glb".into()).unwrap();
+        scene
+            .override_model(Some("ground"), "cube.obj".into())
+            .unwrap();
+        let sources = scene
+            .models
+            .iter()
+            .map(|model| match &model.source {
+                ModelSource::File(file) => file.as_str(),
+                ModelSource::Plane { .. } => "plane",
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(sources, ["box.glb", "cube.obj"]);
+        assert!(scene.validate().is_ok());
+
+        let err = scene
+            .override_model(Some("teapot"), "cube.obj".into())
+            .unwrap_err();
+        assert!(err.to_string().contains("teapot"), "{err}");
+        scene
+            .override_model(Some("cube"), "missing.obj".into())
+            .unwrap();
+        let err = scene.validate().unwrap_err();
+        assert!(format!("{err:?}").contains("models[0].source"), "{err:?}");
+    }
+
+    #[test]
+    fn grid_tilts_instances_away_from_center() {
+        let grid = GridDesc {
+            model: "cube".to_string(),
+            count: [3, 2],
+            spacing: 2.0,
+            tilt: 90.0,
+            spin: None,
+        };
+        let transforms = grid.transforms().collect::<Vec<_>>();
+        assert_eq!(transforms.len(), 6);
+        assert_eq!(transforms[0].translation, Vec3::new(-3.0, 0.0, -2.0));
+        assert_eq!(transforms[5].translation, Vec3::new(1.0, 0.0, 0.0));
+        // 倾斜轴就是实例的方向，所以倾斜不改变实例在轴上的位置
+        let axis = transforms[0].translation.normalize();
+        assert!((transforms[0].rotation * axis - axis).length() < 1e-5);
+        // 位于中心的实例不旋转
+        let center = GridDesc {
+            count: [2, 2],
+            ..grid
+        };
+        let center = center.transforms().nth(3).unwrap();
+        assert_eq!(center.translation, Vec3::ZERO);
+        assert_eq!(center.rotation, Quat::IDENTITY);
+    }
+}
@@ -1,28 +1,90 @@
 
 struct CameraUniform {
     view_proj: mat4x4f,
+    view_position: vec4f,
 };
 @group(1)@binding(0)
 var<uniform> carmera:CameraUniform;
 
+// 需与 light.rs 中的 MAX_LIGHTS 保持一致
+const MAX_LIGHTS: u32 = 16u;
+const LIGHT_KIND_POINT: u32 = 0u;
+const LIGHT_KIND_DIRECTIONAL: u32 = 1u;
+const LIGHT_KIND_SPOT: u32 = 2u;
+
+struct Light {
+    position: vec3f,
+    kind: u32,
+    direction: vec3f,
+    range: f32,
+    color: vec3f,
+    intensity: f32,
+    inner_cos: f32,
+    outer_cos: f32,
+}
+struct Lights {
+    ambient: vec3f,
+    count: u32,
+    shadow_view_proj: mat4x4f,
+    // 投射阴影的光源下标，没有时为 0xffffffff
+    shadow_light: u32,
+    lights: array<Light, MAX_LIGHTS>,
+}
+@group(2) @binding(0)
+var<uniform> lights: Lights;
+@group(2) @binding(1)
+var shadow_map: texture_depth_2d;
+@group(2) @binding(2)
+var shadow_sampler: sampler_comparison;
+
+// 基于图像的光照，由 environment.rs 预计算
+@group(3) @binding(0)
+var irradiance_map: texture_cube<f32>;
+// 第 i 级对应粗糙度 i / (级数 - 1)
+@group(3) @binding(1)
+var prefiltered_map: texture_cube<f32>;
+// u 为 n·v，v 为粗糙度
+@group(3) @binding(2)
+var brdf_lut: texture_2d<f32>;
+@group(3) @binding(3)
+var environment_sampler: sampler;
+
 struct VertexInput {
     @location(0) position: vec3f,
     @location(1) tex_uv: vec2f,
+    @location(2) normal: vec3f,
+    @location(3) tangent: vec3f,
+    @location(4) bitangent: vec3f,
 }
 struct InstanceInput {
     @location(5) model_matrix_0: vec4f,
     @location(6) model_matrix_1: vec4f,
     @location(7) model_matrix_2: vec4f,
     @location(8) model_matrix_3: vec4f,
+    @location(9) tint: vec4f,
+    @location(10) normal_matrix_0: vec3f,
+    @location(11) normal_matrix_1: vec3f,
+    @location(12) normal_matrix_2: vec3f,
 }
 
+// 法线贴图在片元着色器中通过 TBN 矩阵变换到世界空间，再逐个累加光源
 struct VertexOutput {
     @builtin(position) pos: vec4<f32>,
     @location(0) tex_uv: vec2f,
+    @location(1) world_position: vec3f,
+    @location(2) world_normal: vec3f,
+    @location(3) world_tangent: vec3f,
+    @location(4) world_bitangent: vec3f,
+    @location(5) tint: vec4f,
 }
 struct FragmentInput {
     @builtin(position) pos: vec4<f32>,
     @location(0) tex_uv: vec2f,
+    @location(1) world_position: vec3f,
+    @location(2) world_normal: vec3f,
+    @location(3) world_tangent: vec3f,
+    @location(4) world_bitangent: vec3f,
+    @location(5) tint: vec4f,
 }
 
 @vertex
@@ -33,20 +95,216 @@ fn vs_main(vertex: VertexInput, instance: InstanceInput) -> VertexOutput {
         instance.model_matrix_2,
         instance.model_matrix_3,
     );
+    // 模型矩阵左上 3x3 的逆转置，在 CPU 端计算，长度在片元着色器中归一化
+    let normal_matrix = mat3x3f(
+        instance.normal_matrix_0,
+        instance.normal_matrix_1,
+        instance.normal_matrix_2,
+    );
+
+    let world_position = model_matrix * vec4<f32>(vertex.position, 1.0);
 
     var out: VertexOutput;
-    out.pos = carmera.view_proj * model_matrix * vec4<f32>(vertex.position, 1.0);
+    out.pos = carmera.view_proj * world_position;
     out.tex_uv = vertex.tex_uv;
+    out.world_position = world_position.xyz;
+    out.world_normal = normal_matrix * vertex.normal;
+    // 切线随表面一起变换，用模型矩阵而不是法线矩阵
+    out.world_tangent = (model_matrix * vec4f(vertex.tangent, 0.0)).xyz;
+    out.world_bitangent = (model_matrix * vec4f(vertex.bitangent, 0.0)).xyz;
+    out.tint = instance.tint;
     return out;
 }
 
 
+const PI: f32 = 3.14159265359;
+
+// 需与 model.rs 中的 MaterialUniform 保持一致
+struct MaterialUniform {
+    base_color: vec4f,
+    emissive: vec3f,
+    metallic: f32,
+    ambient: vec3f,
+    roughness: f32,
+    normal_scale: f32,
+    occlusion_strength: f32,
+    specular: f32,
+}
+
 @group(0) @binding(0)
-var the_texture:texture_2d<f32>;
+var base_color_texture: texture_2d<f32>;
 @group(0) @binding(1)
-var the_sampler:sampler;
+var base_color_sampler: sampler;
+@group(0) @binding(2)
+var normal_texture: texture_2d<f32>;
+@group(0) @binding(3)
+var normal_sampler: sampler;
+// G 通道为粗糙度，B 通道为金属度
+@group(0) @binding(4)
+var metallic_roughness_texture: texture_2d<f32>;
+@group(0) @binding(5)
+var metallic_roughness_sampler: sampler;
+@group(0) @binding(6)
+var occlusion_texture: texture_2d<f32>;
+@group(0) @binding(7)
+var occlusion_sampler: sampler;
+@group(0) @binding(8)
+var emissive_texture: texture_2d<f32>;
+@group(0) @binding(9)
+var emissive_sampler: sampler;
+@group(0) @binding(10)
+var<uniform> material: MaterialUniform;
+
+// 点光源和聚光灯在 range 内平滑衰减到 0
+fn distance_attenuation(distance: f32, range: f32) -> f32 {
+    let ratio = distance / range;
+    let window = clamp(1.0 - ratio * ratio * ratio * ratio, 0.0, 1.0);
+    return window * window / (1.0 + 0.09 * distance + 0.032 * distance * distance);
+}
+
+// 3x3 PCF：每次比较采样本身又做 2x2 双线性过滤，阴影边缘更平滑
+fn shadow_factor(world_position: vec3f) -> f32 {
+    let light_space = lights.shadow_view_proj * vec4f(world_position, 1.0);
+    let ndc = light_space.xyz / light_space.w;
+    // NDC 的 y 轴朝上，纹理坐标的 v 轴朝下
+    let uv = ndc.xy * vec2f(0.5, -0.5) + 0.5;
+    if any(uv < vec2f(0.0)) || any(uv > vec2f(1.0)) || ndc.z > 1.0 {
+        return 1.0;
+    }
+
+    let texel = 1.0 / vec2f(textureDimensions(shadow_map));
+    var visibility = 0.0;
+    for (var y = -1; y <= 1; y += 1) {
+        for (var x = -1; x <= 1; x += 1) {
+            let offset = vec2f(f32(x), f32(y)) * texel;
+            visibility += textureSampleCompareLevel(shadow_map, shadow_sampler, uv + offset, ndc.z);
+        }
+    }
+    return visibility / 9.0;
+}
+
+// GGX/Trowbridge-Reitz 法线分布函数
+fn distribution_ggx(n_dot_h: f32, roughness: f32) -> f32 {
+    let a = roughness * roughness;
+    let a2 = a * a;
+    let denom = n_dot_h * n_dot_h * (a2 - 1.0) + 1.0;
+    return a2 / (PI * denom * denom);
+}
+
+// Smith 几何遮蔽项，直接光照使用 k = (r + 1)^2 / 8
+fn geometry_smith(n_dot_v: f32, n_dot_l: f32, roughness: f32) -> f32 {
+    let r = roughness + 1.0;
+    let k = r * r / 8.0;
+    let ggx_v = n_dot_v / (n_dot_v * (1.0 - k) + k);
+    let ggx_l = n_dot_l / (n_dot_l * (1.0 - k) + k);
+    return ggx_v * ggx_l;
+}
+
+fn fresnel_schlick(cos_theta: f32, f0: vec3f) -> vec3f {
+    return f0 + (1.0 - f0) * pow(clamp(1.0 - cos_theta, 0.0, 1.0), 5.0);
+}
+
+// 环境光来自整个半球，粗糙表面在掠射角的菲涅耳增强要弱一些
+fn fresnel_schlick_roughness(cos_theta: f32, f0: vec3f, roughness: f32) -> vec3f {
+    let f90 = max(vec3f(1.0 - roughness), f0);
+    return f0 + (f90 - f0) * pow(clamp(1.0 - cos_theta, 0.0, 1.0), 5.0);
+}
+
+// 表面在着色点处的材质参数
+struct Surface {
+    albedo: vec3f,
+    metallic: f32,
+    roughness: f32,
+    f0: vec3f,
+}
+
+// split-sum 近似的环境光：漫反射取辐照度贴图，镜面反射取预滤波贴图并用 BRDF 查找表修正
+fn shade_environment(normal: vec3f, view_dir: vec3f, surface: Surface) -> vec3f {
+    let n_dot_v = max(dot(normal, view_dir), 1e-4);
+    let f = fresnel_schlick_roughness(n_dot_v, surface.f0, surface.roughness);
+    let k_d = (vec3f(1.0) - f) * (1.0 - surface.metallic);
+    // 辐照度贴图中已除以 π
+    let diffuse = k_d * surface.albedo * textureSample(irradiance_map, environment_sampler, normal).rgb;
+
+    let max_lod = f32(textureNumLevels(prefiltered_map) - 1u);
+    let reflected = reflect(-view_dir, normal);
+    let prefiltered = textureSampleLevel(prefiltered_map, environment_sampler, reflected, surface.roughness * max_lod).rgb;
+    let brdf = textureSample(brdf_lut, environment_sampler, vec2f(n_dot_v, surface.roughness)).rg;
+    let specular = prefiltered * (f * brdf.x + brdf.y);
+    return diffuse + specular;
+}
+
+// Cook-Torrance：返回单个光源出射的辐射度
+fn shade_light(light: Light, position: vec3f, normal: vec3f, view_dir: vec3f, surface: Surface, shadow: f32) -> vec3f {
+    var light_dir: vec3f;
+    var attenuation = 1.0;
+    if light.kind == LIGHT_KIND_DIRECTIONAL {
+        light_dir = -light.direction;
+    } else {
+        let to_light = light.position - position;
+        light_dir = normalize(to_light);
+        attenuation = distance_attenuation(length(to_light), light.range);
+        if light.kind == LIGHT_KIND_SPOT {
+            let cos_angle = dot(-light_dir, light.direction);
+            attenuation *= smoothstep(light.outer_cos, light.inner_cos, cos_angle);
+        }
+    }
+
+    let n_dot_l = max(dot(normal, light_dir), 0.0);
+    if n_dot_l <= 0.0 {
+        return vec3f(0.0);
+    }
+    let n_dot_v = max(dot(normal, view_dir), 1e-4);
+    let half_dir = normalize(view_dir + light_dir);
+    let n_dot_h = max(dot(normal, half_dir), 0.0);
+
+    let f = fresnel_schlick(max(dot(half_dir, view_dir), 0.0), surface.f0);
+    let d = distribution_ggx(n_dot_h, surface.roughness);
+    let g = geometry_smith(n_dot_v, n_dot_l, surface.roughness);
+    let specular = d * g * f / (4.0 * n_dot_v * n_dot_l + 1e-4);
+    // 金属没有漫反射，被镜面反射的能量也不再参与漫反射
+    let k_d = (vec3f(1.0) - f) * (1.0 - surface.metallic);
+    let diffuse = k_d * surface.albedo / PI;
+
+    let radiance = light.color * light.intensity * attenuation * shadow;
+    return (diffuse + specular) * radiance * n_dot_l;
+}
 
 @fragment
 fn fs_main(fragment_in: FragmentInput) -> @location(0) vec4<f32> {
-    return textureSample(the_texture, the_sampler, fragment_in.tex_uv);
-}
\ No newline at end of file
+    let uv = fragment_in.tex_uv;
+    let base_color = textureSample(base_color_texture, base_color_sampler, uv) * material.base_color * fragment_in.tint;
+    let metallic_roughness = textureSample(metallic_roughness_texture, metallic_roughness_sampler, uv);
+    let occlusion = textureSample(occlusion_texture, occlusion_sampler, uv).r;
+    let emissive = textureSample(emissive_texture, emissive_sampler, uv).rgb * material.emissive;
+    let object_normal = textureSample(normal_texture, normal_sampler, uv).xyz * 2.0 - 1.0;
+
+    var surface: Surface;
+    surface.albedo = base_color.rgb;
+    surface.metallic = clamp(material.metallic * metallic_roughness.b, 0.0, 1.0);
+    // 粗糙度过小时高光会退化成一个点，限制一个下限
+    surface.roughness = clamp(material.roughness * metallic_roughness.g, 0.04, 1.0);
+    // 非金属的基础反射率由 specular 决定（默认约 0.04），金属的反射率取自基础色
+    surface.f0 = mix(vec3f(0.08 * material.specular), surface.albedo, surface.metallic);
+
+    let tbn = mat3x3f(
+        normalize(fragment_in.world_tangent),
+        normalize(fragment_in.world_bitangent),
+        normalize(fragment_in.world_normal),
+    );
+    let normal = normalize(tbn * (object_normal * vec3f(material.normal_scale, material.normal_scale, 1.0)));
+    let view_dir = normalize(carmera.view_position.xyz - fragment_in.world_position);
+
+    let ambient_occlusion = mix(1.0, occlusion, material.occlusion_strength) * material.ambient;
+    var color = shade_environment(normal, view_dir, surface) * ambient_occlusion;
+    for (var i = 0u; i < min(lights.count, MAX_LIGHTS); i += 1u) {
+        var shadow = 1.0;
+        if i == lights.shadow_light {
+            shadow = shadow_factor(fragment_in.world_position);
+        }
+        color += shade_light(lights.lights[i], fragment_in.world_position, normal, view_dir, surface, shadow);
+    }
+
+    color += lights.ambient * surface.albedo * ambient_occlusion + emissive;
+    return vec4<f32>(color, base_color.a);
+}
@@ -0,0 +1,286 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use anyhow::{Context, bail};
+
+/// 磁盘上的着色器源文件，开发模式下监视它的改动
+pub const SHADER_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/src/shader.wgsl");
+
+/// 用 naga 解析并验证 WGSL，出错时返回带 `文件:行:列` 的诊断信息。
+///
+/// wgpu 遇到无效着色器会直接触发未捕获错误并 panic，所以热重载前先在这里检查一遍。
+pub fn validate_wgsl(source: &str, path: &Path) -> anyhow::Result<()> {
+    let module = naga::front::wgsl::parse_str(source).map_err(|err| {
+        let location = err.location(source);
+        anyhow::anyhow!(
+            "{}: {}\n{}",
+            format_location(path, location),
+            err.message(),
+            err.emit_to_string_with_path(source, path)
+        )
+    })?;
+
+    naga::valid::Validator::new(
+        naga::valid::ValidationFlags::all(),
+        naga::valid::Capabilities::all(),
+    )
+    .validate(&module)
+    .map_err(|err| {
+        let location = err.location(source);
+        anyhow::anyhow!(
+            "{}: {}\n{}",
+            format_location(path, location),
+            err.as_inner(),
+            err.emit_to_string_with_path(source, &path.display().to_string())
+        )
+    })?;
+    Ok(())
+}
+
+/// 检查顶点着色器入口 `entry_point` 的每个 `@location` 输入都能在 `layouts` 中找到
+/// 分量数和类型都匹配的属性，且属性没有超出各自缓冲区的步长。
+///
+/// wgpu 只会在创建管线时报出不易定位的错误，这里给出具体的输入名和格式，
+/// 启动时和热重载前都会调用。
+pub fn validate_vertex_layouts(
+    source: &str,
+    path: &Path,
+    entry_point: &str,
+    layouts: &[wgpu::VertexBufferLayout],
+) -> anyhow::Result<()> {
+    let module = naga::front::wgsl::parse_str(source)
+        .map_err(|err| anyhow::anyhow!("{}: {}", path.display(), err.message()))?;
+    let entry = module
+        .entry_points
+        .iter()
+        .find(|entry| entry.stage == naga::ShaderStage::Vertex && entry.name == entry_point)
+        .with_context(|| format!("{}: 找不到顶点着色器入口 `{entry_point}`", path.display()))?;
+
+    let mut attributes = HashMap::new();
+    for (buffer, layout) in layouts.iter().enumerate() {
+        for attribute in layout.attributes {
+            if attribute.offset + attribute.format.size() > layout.array_stride {
+                bail!(
+                    "第 {buffer} 个顶点缓冲区的 @location({}) 超出了步长 {}",
+                    attribute.shader_location,
+                    layout.array_stride
+                );
+            }
+            if attributes
+                .insert(attribute.shader_location, attribute.format)
+                .is_some()
+            {
+                bail!(
+                    "顶点缓冲区布局中 @location({}) 重复",
+                    attribute.shader_location
+                );
+            }
+        }
+    }
+
+    // 入口参数可以直接带 @location，也可以是成员带 @location 的结构体
+    let mut inputs = Vec::new();
+    for argument in &entry.function.arguments {
+        match (&argument.binding, &module.types[argument.ty].inner) {
+            (Some(binding), _) => inputs.push((binding, argument.ty, argument.name.as_deref())),
+            (None, naga::TypeInner::Struct { members, .. }) => {
+                for member in members {
+                    if let Some(binding) = &member.binding {
+                        inputs.push((binding, member.ty, member.name.as_deref()));
+                    }
+                }
+            }
+            (None, _) => {}
+        }
+    }
+
+    for (binding, ty, name) in inputs {
+        let naga::Binding::Location { location, .. } = *binding else {
+            continue;
+        };
+        let name = name.unwrap_or("?");
+        let Some(&format) = attributes.get(&location) else {
+            bail!(
+                "{}: 顶点输入 `{name}` @location({location}) 在顶点缓冲区布局中没有对应的属性",
+                path.display()
+            );
+        };
+        let expected = match module.types[ty].inner {
+            naga::TypeInner::Scalar(scalar) => (1, scalar.kind),
+            naga::TypeInner::Vector { size, scalar } => (size as u32, scalar.kind),
+            ref other => bail!(
+                "{}: 顶点输入 `{name}` 的类型 {other:?} 不受支持",
+                path.display()
+            ),
+        };
+        if vertex_format_shape(format) != expected {
+            bail!(
+                "{}: 顶点输入 `{name}` @location({location}) 在 WGSL 中为 {expected:?}，布局中为 {format:?}",
+                path.display()
+            );
+        }
+    }
+    Ok(())
+}
+
+/// 顶点格式在着色器中呈现的分量数和标量类型，归一化格式读出的是浮点数
+fn vertex_format_shape(format: wgpu::VertexFormat) -> (u32, naga::ScalarKind) {
+    use naga::ScalarKind::{Float, Sint, Uint};
+    use wgpu::VertexFormat as F;
+    match format {
+        F::Uint8 | F::Uint16 | F::Uint32 => (1, Uint),
+        F::Uint8x2 | F::Uint16x2 | F::Uint32x2 => (2, Uint),
+        F::Uint32x3 => (3, Uint),
+        F::Uint8x4 | F::Uint16x4 | F::Uint32x4 => (4, Uint),
+        F::Sint8 | F::Sint16 | F::Sint32 => (1, Sint),
+        F::Sint8x2 | F::Sint16x2 | F::Sint32x2 => (2, Sint),
+        F::Sint32x3 => (3, Sint),
+        F::Sint8x4 | F::Sint16x4 | F::Sint32x4 => (4, Sint),
+        F::Unorm8 | F::Snorm8 | F::Unorm16 | F::Snorm16 | F::Float16 | F::Float32 | F::Float64 => {
+            (1, Float)
+        }
+        F::Unorm8x2
+        | F::Snorm8x2
+        | F::Unorm16x2
+        | F::Snorm16x2
+        | F::Float16x2
+        | F::Float32x2
+        | F::Float64x2 => (2, Float),
+        F::Float32x3 | F::Float64x3 => (3, Float),
+        F::Unorm8x4
+        | F::Snorm8x4
+        | F::Unorm16x4
+        | F::Snorm16x4
+        | F::Float16x4
+        | F::Float32x4
+        | F::Float64x4
+        | F::Unorm10_10_10_2
+        | F::Unorm8x4Bgra => (4, Float),
+    }
+}
+
+fn format_location(path: &Path, location: Option<naga::SourceLocation>) -> String {
+    match location {
+        Some(location) => format!(
+            "{}:{}:{}",
+            path.display(),
+            location.line_number,
+            location.line_position
+        ),
+        None => path.display().to_string(),
+    }
+}
+
+/// 通过比较修改时间检测着色器文件的改动，在每帧更新时轮询
+pub struct ShaderWatcher {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+}
+
+impl ShaderWatcher {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        let path = path.into();
+        let modified = Self::modified_time(&path);
+        Self { path, modified }
+    }
+
+    /// 文件自上次调用以来被修改过时返回新的源码
+    pub fn poll(&mut self) -> Option<String> {
+        let modified = Self::modified_time(&self.path)?;
+        if self.modified == Some(modified) {
+            return None;
+        }
+        // 编辑器保存时文件可能暂时为空或不可读，读取失败就等下一次轮询
+        let source = std::fs::read_to_string(&self.path).ok()?;
+        self.modified = Some(modified);
+        Some(source)
+    }
+
+    fn modified_time(path: &Path) -> Option<SystemTime> {
+        std::fs::metadata(path)
+            .and_then(|meta| meta.modified())
+            .ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn shipped_shader_is_valid() {
+        let source = std::fs::read_to_string(SHADER_PATH).unwrap();
+        validate_wgsl(&source, Path::new(SHADER_PATH)).unwrap();
+    }
+
+    #[test]
+    fn shipped_shaders_match_vertex_layouts() {
+        use crate::instance::InstanceRaw;
+        use crate::model::{ModelVertex, Vertex};
+
+        let layouts = [ModelVertex::desc(), InstanceRaw::desc()];
+        let source = std::fs::read_to_string(SHADER_PATH).unwrap();
+        validate_vertex_layouts(&source, Path::new(SHADER_PATH), "vs_main", &layouts).unwrap();
+        let source = include_str!("shadow.wgsl");
+        validate_vertex_layouts(source, Path::new("shadow.wgsl"), "vs_main", &layouts).unwrap();
+    }
+
+    #[test]
+    fn vertex_layout_mismatches_are_reported() {
+        let layouts = [wgpu::VertexBufferLayout {
+            array_stride: 12,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &wgpu::vertex_attr_array![0 => Float32x3],
+        }];
+        let check = |source: &str| {
+            validate_vertex_layouts(source, Path::new("test.wgsl"), "vs_main", &layouts)
+        };
+        let shader = |input: &str| {
+            format!(
+                "struct In {{ {input} }}\n@vertex\nfn vs_main(in: In) -> @builtin(position) vec4f {{ return vec4f(0.0); }}\n"
+            )
+        };
+
+        check(&shader("@location(0) position: vec3f,")).unwrap();
+        let err = check(&shader("@location(0) position: vec4f,")).unwrap_err();
+        assert!(err.to_string().contains("`position` @location(0)"), "{err}");
+        let err = check(&shader(
+            "@location(0) position: vec3f, @location(1) normal: vec3f,",
+        ))
+        .unwrap_err();
+        assert!(err.to_string().contains("`normal` @location(1)"), "{err}");
+        assert!(check(&shader("@location(0) position: vec3u,")).is_err());
+    }
+
+    #[test]
+    fn errors_report_line_and_column() {
+        let source =
+            "@fragment\nfn fs_main() -> @location(0) vec4f {\n    return vec4f(1.0) +;\n}\n";
+        let err = validate_wgsl(source, Path::new("broken.wgsl")).unwrap_err();
+        assert!(err.to_string().starts_with("broken.wgsl:3:"), "{err}");
+
+        let source = "fn f() -> f32 {\n    return 1u;\n}\n";
+        let err = validate_wgsl(source, Path::new("typed.wgsl")).unwrap_err();
+        assert!(err.to_string().starts_with("typed.wgsl:"), "{err}");
+    }
+
+    #[test]
+    fn watcher_reports_each_change_once() {
+        let path = std::env::temp_dir().join(format!("shader_watch_{}.wgsl", std::process::id()));
+        std::fs::write(&path, "// v1").unwrap();
+        let mut watcher = ShaderWatcher::new(&path);
+        assert_eq!(watcher.poll(), None);
+
+        std::fs::write(&path, "// v2").unwrap();
+        // 文件系统的时间精度可能较粗，显式推后修改时间
+        let file = std::fs::File::options().write(true).open(&path).unwrap();
+        file.set_modified(SystemTime::now() + Duration::from_secs(2))
+            .unwrap();
+        assert_eq!(watcher.poll().as_deref(), Some("// v2"));
+        assert_eq!(watcher.poll(), None);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
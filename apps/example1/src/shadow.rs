@@ -0,0 +1,165 @@
+use crate::instance::{InstanceManager, InstanceRaw};
+use crate::model::{Model, ModelVertex, Vertex};
+use crate::shader_reload;
+use crate::texture;
+use anyhow::Context;
+use wgpu::util::DeviceExt;
+
+/// 平行光阴影贴图：从光源视角渲染实例的深度，主渲染通道中用比较采样器做 PCF 过滤
+pub struct ShadowMap {
+    pub texture: texture::Texture,
+    buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+    pipeline: wgpu::RenderPipeline,
+}
+
+impl ShadowMap {
+    pub const SIZE: u32 = 2048;
+
+    pub fn new(device: &wgpu::Device) -> anyhow::Result<Self> {
+        let texture = texture::Texture::create_shadow_map(device, Self::SIZE, "shadow_map");
+
+        let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Shadow Buffer"),
+            contents: bytemuck::cast_slice(&glam::Mat4::IDENTITY.to_cols_array_2d()),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("shadow_bind_group_layout"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("shadow_bind_group"),
+            layout: &bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: buffer.as_entire_binding(),
+            }],
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("shadow_pipeline_layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let source = include_str!("shadow.wgsl");
+        let buffers = [ModelVertex::desc(), InstanceRaw::desc()];
+        shader_reload::validate_vertex_layouts(
+            source,
+            std::path::Path::new("shadow.wgsl"),
+            "vs_main",
+            &buffers,
+        )
+        .context("shadow.wgsl 的顶点输入与顶点缓冲区布局不一致")?;
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("shadow_shader"),
+            source: wgpu::ShaderSource::Wgsl(std::borrow::Cow::Borrowed(source)),
+        });
+
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("shadow_pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                buffers: &buffers,
+                compilation_options: Default::default(),
+            },
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                ..Default::default()
+            },
+            // 只写深度，不需要片元着色器
+            fragment: None,
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: texture::Texture::DEPTH_FORMAT,
+                depth_write_enabled: true,
+                depth_compare: wgpu::CompareFunction::LessEqual,
+                stencil: wgpu::StencilState::default(),
+                // 深度偏移用于消除阴影痤疮（shadow acne）
+                bias: wgpu::DepthBiasState {
+                    constant: 2,
+                    slope_scale: 2.0,
+                    clamp: 0.0,
+                },
+            }),
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: None,
+        });
+
+        Ok(Self {
+            texture,
+            buffer,
+            bind_group,
+            pipeline,
+        })
+    }
+
+    /// 根据平行光方向计算覆盖以 `center` 为中心、半径为 `radius` 的场景的光源视图投影矩阵
+    pub fn light_view_proj(direction: glam::Vec3, center: glam::Vec3, radius: f32) -> glam::Mat4 {
+        let direction = direction.normalize_or(glam::Vec3::NEG_Y);
+        // 光线几乎竖直时换一个 up 向量，避免 look_at 退化
+        let up = if direction.y.abs() > 0.99 {
+            glam::Vec3::Z
+        } else {
+            glam::Vec3::Y
+        };
+        let eye = center - direction * radius * 2.0;
+        let view = glam::Mat4::look_at_rh(eye, center, up);
+        let proj = glam::Mat4::orthographic_rh(-radius, radius, -radius, radius, 0.1, radius * 4.0);
+        proj * view
+    }
+
+    pub fn update(&self, queue: &wgpu::Queue, light_view_proj: glam::Mat4) {
+        queue.write_buffer(
+            &self.buffer,
+            0,
+            bytemuck::cast_slice(&light_view_proj.to_cols_array_2d()),
+        );
+    }
+
+    /// 把每个模型的所有网格以其全部实例渲染到阴影贴图
+    pub fn render<'a>(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        casters: impl IntoIterator<Item = (&'a Model, &'a InstanceManager)>,
+    ) {
+        let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("shadow_pass"),
+            color_attachments: &[],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: &self.texture.view,
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(1.0),
+                    store: wgpu::StoreOp::Store,
+                }),
+                stencil_ops: None,
+            }),
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, &self.bind_group, &[]);
+        for (model, instances) in casters {
+            pass.set_vertex_buffer(1, instances.buffer().slice(..));
+            for mesh in &model.meshes {
+                pass.set_vertex_buffer(0, mesh.vertex_buffer.slice(..));
+                pass.set_index_buffer(mesh.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+                pass.draw_indexed(0..mesh.index_count, 0, instances.range());
+            }
+        }
+    }
+}
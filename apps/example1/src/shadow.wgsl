@@ -0,0 +1,28 @@
+// 阴影贴图生成：只从光源视角写入深度
+
+struct ShadowUniform {
+    light_view_proj: mat4x4f,
+};
+@group(0) @binding(0)
+var<uniform> shadow: ShadowUniform;
+
+struct VertexInput {
+    @location(0) position: vec3f,
+}
+struct InstanceInput {
+    @location(5) model_matrix_0: vec4f,
+    @location(6) model_matrix_1: vec4f,
+    @location(7) model_matrix_2: vec4f,
+    @location(8) model_matrix_3: vec4f,
+}
+
+@vertex
+fn vs_main(vertex: VertexInput, instance: InstanceInput) -> @builtin(position) vec4f {
+    let model_matrix = mat4x4f(
+        instance.model_matrix_0,
+        instance.model_matrix_1,
+        instance.model_matrix_2,
+        instance.model_matrix_3,
+    );
+    return shadow.light_view_proj * model_matrix * vec4f(vertex.position, 1.0);
+}
@@ -0,0 +1,174 @@
+use wgpu::util::DeviceExt;
+
+use crate::camera::Camera;
+use crate::environment::Environment;
+use crate::texture;
+
+/// 布局与 skybox.wgsl 中的 `SkyboxUniform` 一致
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct SkyboxUniform {
+    inv_view_proj: [[f32; 4]; 4],
+}
+
+/// 在场景通道的最后绘制环境立方体贴图，只使用相机的旋转，天空不随相机平移
+pub struct Skybox {
+    buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+    pipeline_layout: wgpu::PipelineLayout,
+    shader: wgpu::ShaderModule,
+    color_format: wgpu::TextureFormat,
+    pipeline: wgpu::RenderPipeline,
+}
+
+impl Skybox {
+    /// `sample_count` 需与场景通道的颜色和深度附件一致
+    pub fn new(
+        device: &wgpu::Device,
+        environment: &Environment,
+        color_format: wgpu::TextureFormat,
+        sample_count: u32,
+    ) -> Self {
+        let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Skybox Buffer"),
+            contents: bytemuck::cast_slice(&[SkyboxUniform {
+                inv_view_proj: glam::Mat4::IDENTITY.to_cols_array_2d(),
+            }]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("skybox_bind_group_layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                Environment::cube_entry(1),
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("skybox_bind_group"),
+            layout: &bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(&environment.cube_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::Sampler(&environment.sampler),
+                },
+            ],
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("skybox_pipeline_layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let shader = device.create_shader_module(wgpu::include_wgsl!("skybox.wgsl"));
+        let pipeline = Self::create_pipeline(
+            device,
+            &pipeline_layout,
+            &shader,
+            color_format,
+            sample_count,
+        );
+
+        Self {
+            buffer,
+            bind_group,
+            pipeline_layout,
+            shader,
+            color_format,
+            pipeline,
+        }
+    }
+
+    fn create_pipeline(
+        device: &wgpu::Device,
+        layout: &wgpu::PipelineLayout,
+        shader: &wgpu::ShaderModule,
+        color_format: wgpu::TextureFormat,
+        sample_count: u32,
+    ) -> wgpu::RenderPipeline {
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("skybox_pipeline"),
+            layout: Some(layout),
+            vertex: wgpu::VertexState {
+                module: shader,
+                entry_point: Some("vs_main"),
+                buffers: &[],
+                compilation_options: Default::default(),
+            },
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                ..Default::default()
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: shader,
+                entry_point: Some("fs_main"),
+                compilation_options: Default::default(),
+                targets: &[Some(color_format.into())],
+            }),
+            // 深度已被清为 1，天空盒位于远平面，只通过测试而不写入
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: texture::Texture::DEPTH_FORMAT,
+                depth_write_enabled: false,
+                depth_compare: wgpu::CompareFunction::LessEqual,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState {
+                count: sample_count,
+                ..Default::default()
+            },
+            multiview: None,
+            cache: None,
+        })
+    }
+
+    pub fn set_sample_count(&mut self, device: &wgpu::Device, sample_count: u32) {
+        self.pipeline = Self::create_pipeline(
+            device,
+            &self.pipeline_layout,
+            &self.shader,
+            self.color_format,
+            sample_count,
+        );
+    }
+
+    pub fn update(&self, queue: &wgpu::Queue, camera: &Camera) {
+        // 去掉观察矩阵中的平移
+        let rotation = glam::Mat4::from_mat3(glam::Mat3::from_mat4(camera.build_view_matrix()));
+        let view_proj = camera.build_projection_matrix() * rotation;
+        queue.write_buffer(
+            &self.buffer,
+            0,
+            bytemuck::cast_slice(&[SkyboxUniform {
+                inv_view_proj: view_proj.inverse().to_cols_array_2d(),
+            }]),
+        );
+    }
+
+    pub fn render(&self, pass: &mut wgpu::RenderPass) {
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, &self.bind_group, &[]);
+        pass.draw(0..3, 0..1);
+    }
+}
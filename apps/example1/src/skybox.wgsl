@@ -0,0 +1,37 @@
+// 天空盒：全屏三角形放在远平面上，按相机的旋转把每个像素还原成世界空间中的方向
+
+// 与 skybox.rs 中的 `SkyboxUniform` 一致
+struct SkyboxUniform {
+    // 只含旋转的观察矩阵与投影矩阵乘积的逆矩阵
+    inv_view_proj: mat4x4f,
+}
+
+@group(0) @binding(0)
+var<uniform> skybox: SkyboxUniform;
+@group(0) @binding(1)
+var environment_texture: texture_cube<f32>;
+@group(0) @binding(2)
+var environment_sampler: sampler;
+
+struct VertexOutput {
+    @builtin(position) pos: vec4f,
+    @location(0) ndc: vec2f,
+}
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    let uv = vec2f(f32((index << 1u) & 2u), f32(index & 2u));
+    let ndc = uv * vec2f(2.0, -2.0) + vec2f(-1.0, 1.0);
+    var out: VertexOutput;
+    // 深度为 1，配合 LessEqual 只填充没有被模型覆盖的像素
+    out.pos = vec4f(ndc, 1.0, 1.0);
+    out.ndc = ndc;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4f {
+    let world = skybox.inv_view_proj * vec4f(in.ndc, 1.0, 1.0);
+    let dir = normalize(world.xyz / world.w);
+    return vec4f(textureSample(environment_texture, environment_sampler, dir).rgb, 1.0);
+}
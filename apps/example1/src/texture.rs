@@ -1,7 +1,172 @@
 use anyhow::*;
 use image::GenericImageView;
 
+use crate::{compressed, mipmap};
+
+/// 纹理数据的色彩空间
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorSpace {
+    /// 颜色贴图（基础色、自发光），采样时由硬件做 sRGB 解码
+    Srgb,
+    /// 数据贴图（法线、金属度-粗糙度、遮蔽），按原值采样
+    Linear,
+}
+
+/// 纹理的格式与采样器设置，通过链式调用构建：
+///
+/// ```ignore
+/// let options = TextureOptions::default()
+///     .color_space(ColorSpace::Linear)
+///     .address_mode(wgpu::AddressMode::Repeat);
+/// ```
+///
+/// 默认是 sRGB、重复寻址、完整 mip 链加 16 倍各向异性过滤。
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TextureOptions {
+    pub color_space: ColorSpace,
+    pub address_mode_u: wgpu::AddressMode,
+    pub address_mode_v: wgpu::AddressMode,
+    pub address_mode_w: wgpu::AddressMode,
+    pub mag_filter: wgpu::FilterMode,
+    pub min_filter: wgpu::FilterMode,
+    pub mipmap_filter: wgpu::FilterMode,
+    /// 是否生成完整 mip 链；KTX2/DDS 自带的 mipmap 不受影响
+    pub mipmaps: bool,
+    /// 最大各向异性（1..=16），1 表示关闭
+    pub anisotropy: u16,
+    /// 寻址模式为 `ClampToBorder` 时的边框颜色
+    pub border_color: Option<wgpu::SamplerBorderColor>,
+}
+
+impl Default for TextureOptions {
+    fn default() -> Self {
+        Self {
+            color_space: ColorSpace::Srgb,
+            address_mode_u: wgpu::AddressMode::Repeat,
+            address_mode_v: wgpu::AddressMode::Repeat,
+            address_mode_w: wgpu::AddressMode::Repeat,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Linear,
+            mipmaps: true,
+            anisotropy: 16,
+            border_color: None,
+        }
+    }
+}
+
+impl TextureOptions {
+    pub fn color_space(mut self, color_space: ColorSpace) -> Self {
+        self.color_space = color_space;
+        self
+    }
+
+    /// 三个方向使用同一种寻址模式
+    pub fn address_mode(self, mode: wgpu::AddressMode) -> Self {
+        self.address_mode_uv(mode, mode)
+    }
+
+    pub fn address_mode_uv(mut self, u: wgpu::AddressMode, v: wgpu::AddressMode) -> Self {
+        self.address_mode_u = u;
+        self.address_mode_v = v;
+        self.address_mode_w = u;
+        self
+    }
+
+    /// 同时设置放大和缩小过滤
+    #[allow(dead_code)]
+    pub fn filter(mut self, filter: wgpu::FilterMode) -> Self {
+        self.mag_filter = filter;
+        self.min_filter = filter;
+        self
+    }
+
+    pub fn mag_filter(mut self, filter: wgpu::FilterMode) -> Self {
+        self.mag_filter = filter;
+        self
+    }
+
+    pub fn min_filter(mut self, filter: wgpu::FilterMode) -> Self {
+        self.min_filter = filter;
+        self
+    }
+
+    pub fn mipmap_filter(mut self, filter: wgpu::FilterMode) -> Self {
+        self.mipmap_filter = filter;
+        self
+    }
+
+    pub fn mipmaps(mut self, mipmaps: bool) -> Self {
+        self.mipmaps = mipmaps;
+        self
+    }
+
+    #[allow(dead_code)]
+    pub fn anisotropy(mut self, anisotropy: u16) -> Self {
+        self.anisotropy = anisotropy.clamp(1, 16);
+        self
+    }
+
+    /// 设置边框颜色，并把寻址模式改为 `ClampToBorder`
+    #[allow(dead_code)]
+    pub fn border_color(mut self, color: wgpu::SamplerBorderColor) -> Self {
+        self.border_color = Some(color);
+        self.address_mode(wgpu::AddressMode::ClampToBorder)
+    }
+
+    /// 按色彩空间选择的 RGBA8 格式
+    pub fn format(&self) -> wgpu::TextureFormat {
+        match self.color_space {
+            ColorSpace::Srgb => wgpu::TextureFormat::Rgba8UnormSrgb,
+            ColorSpace::Linear => wgpu::TextureFormat::Rgba8Unorm,
+        }
+    }
+
+    /// 生成采样器描述，并按设备特性和 wgpu 的校验规则降级：
+    /// 不支持边框寻址时退回 `ClampToEdge`，过滤不全是线性时关闭各向异性过滤。
+    pub fn sampler_descriptor(&self, features: wgpu::Features) -> wgpu::SamplerDescriptor<'static> {
+        let border_supported = match self.border_color {
+            Some(wgpu::SamplerBorderColor::Zero) => {
+                features.contains(wgpu::Features::ADDRESS_MODE_CLAMP_TO_ZERO)
+            }
+            Some(_) => features.contains(wgpu::Features::ADDRESS_MODE_CLAMP_TO_BORDER),
+            None => false,
+        };
+        let address_mode = |mode| match mode {
+            wgpu::AddressMode::ClampToBorder if !border_supported => wgpu::AddressMode::ClampToEdge,
+            mode => mode,
+        };
+        // 没有 mip 链时 mipmap_filter 无意义，按线性处理以便仍可使用各向异性过滤
+        let mipmap_filter = if self.mipmaps {
+            self.mipmap_filter
+        } else {
+            wgpu::FilterMode::Linear
+        };
+        let all_linear = self.mag_filter == wgpu::FilterMode::Linear
+            && self.min_filter == wgpu::FilterMode::Linear
+            && mipmap_filter == wgpu::FilterMode::Linear;
+
+        wgpu::SamplerDescriptor {
+            label: None,
+            address_mode_u: address_mode(self.address_mode_u),
+            address_mode_v: address_mode(self.address_mode_v),
+            address_mode_w: address_mode(self.address_mode_w),
+            mag_filter: self.mag_filter,
+            min_filter: self.min_filter,
+            mipmap_filter,
+            anisotropy_clamp: if all_linear {
+                self.anisotropy.clamp(1, 16)
+            } else {
+                1
+            },
+            border_color: self.border_color.filter(|_| border_supported),
+            ..Default::default()
+        }
+    }
+}
+
 pub struct Texture {
+    #[allow(dead_code)]
     pub texture: wgpu::Texture,
     pub view: wgpu::TextureView,
     pub sampler: wgpu::Sampler,
@@ -10,25 +175,63 @@ pub struct Texture {
 impl Texture {
     pub const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
 
+    /// `sample_count` 需与同一渲染通道中颜色附件的采样数一致
     pub fn create_depth_texture(
         device: &wgpu::Device,
         config: &wgpu::SurfaceConfiguration,
+        sample_count: u32,
+        label: &str,
+    ) -> Self {
+        Self::create_depth(
+            device,
+            config.width,
+            config.height,
+            sample_count,
+            None,
+            label,
+        )
+    }
+
+    /// 创建正方形的阴影贴图，采样器带比较函数，供着色器中的 PCF 使用
+    pub fn create_shadow_map(device: &wgpu::Device, size: u32, label: &str) -> Self {
+        Self::create_depth(
+            device,
+            size,
+            size,
+            1,
+            Some(wgpu::CompareFunction::LessEqual),
+            label,
+        )
+    }
+
+    fn create_depth(
+        device: &wgpu::Device,
+        width: u32,
+        height: u32,
+        sample_count: u32,
+        compare: Option<wgpu::CompareFunction>,
         label: &str,
     ) -> Self {
         let size = wgpu::Extent3d {
-            width: config.width,
-            height: config.height,
+            width,
+            height,
             depth_or_array_layers: 1,
         };
 
+        // 多重采样的深度纹理只用作附件，不需要采样（GL 后端也不支持对其采样）
+        let usage = if sample_count == 1 {
+            wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING
+        } else {
+            wgpu::TextureUsages::RENDER_ATTACHMENT
+        };
         let desc = wgpu::TextureDescriptor {
             label: Some(label),
             size,
             mip_level_count: 1,
-            sample_count: 1,
+            sample_count,
             dimension: wgpu::TextureDimension::D2,
             format: Self::DEPTH_FORMAT,
-            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            usage,
             view_formats: &[],
         };
 
@@ -42,6 +245,7 @@ impl Texture {
             mag_filter: wgpu::FilterMode::Linear,
             min_filter: wgpu::FilterMode::Linear,
             mipmap_filter: wgpu::FilterMode::Nearest,
+            compare,
             lod_min_clamp: 0.0,
             lod_max_clamp: 200.0,
             ..Default::default()
@@ -57,21 +261,60 @@ impl Texture {
     pub fn from_bytes(
         device: &wgpu::Device,
         queue: &wgpu::Queue,
+        mipmaps: Option<&mipmap::MipmapGenerator>,
         bytes: &[u8],
         label: &str,
+        options: &TextureOptions,
     ) -> Result<Self> {
+        // KTX2/DDS 自带 mipmap 和 GPU 格式，sRGB 与否由文件决定，只使用 options 中的采样器设置
+        if compressed::is_container(bytes) {
+            let image = compressed::parse(bytes).with_context(|| label.to_string())?;
+            return Self::from_compressed(device, queue, &image, label, options);
+        }
         let img = image::load_from_memory(bytes)?;
-        Self::from_image(device, queue, &img, Some(label))
+        Self::from_image(device, queue, mipmaps, &img, Some(label), options)
+    }
+
+    /// 创建 1x1 的纯色纹理，用作材质缺少贴图时的默认值
+    pub fn from_color(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        rgba: [u8; 4],
+        label: &str,
+        color_space: ColorSpace,
+    ) -> Result<Self> {
+        let img =
+            image::DynamicImage::ImageRgba8(image::RgbaImage::from_pixel(1, 1, image::Rgba(rgba)));
+        let options = TextureOptions::default()
+            .color_space(color_space)
+            .mipmaps(false);
+        Self::from_image(device, queue, None, &img, Some(label), &options)
     }
 
+    /// 需要 mipmap 时优先用 `mipmaps` 在 GPU 上逐级渲染生成，
+    /// 没有传入生成器或格式不支持作为渲染目标时退回 CPU 缩放
     pub fn from_image(
         derive: &wgpu::Device,
         queue: &wgpu::Queue,
+        mipmaps: Option<&mipmap::MipmapGenerator>,
         img: &image::DynamicImage,
         label: Option<&str>,
+        options: &TextureOptions,
     ) -> Result<Self> {
         let rgba = img.to_rgba8();
         let dimensions = img.dimensions();
+        let format = options.format();
+        let mip_level_count = if options.mipmaps {
+            mipmap::mip_level_count(dimensions.0, dimensions.1)
+        } else {
+            1
+        };
+        let gpu_mipmaps =
+            mipmaps.filter(|_| mip_level_count > 1 && mipmap::supports_gpu(derive, format));
+        let mut usage = wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST;
+        if gpu_mipmaps.is_some() {
+            usage |= wgpu::TextureUsages::RENDER_ATTACHMENT;
+        }
 
         let size = wgpu::Extent3d {
             width: dimensions.0,
@@ -82,45 +325,170 @@ impl Texture {
         let texture = derive.create_texture(&wgpu::TextureDescriptor {
             label,
             size,
-            mip_level_count: 1,
+            mip_level_count,
             sample_count: 1,
             dimension: wgpu::TextureDimension::D2,
-            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            format,
+            usage,
+            view_formats: &[],
+        });
+
+        Self::write_level(queue, &texture, 0, &rgba);
+        if let Some(mipmaps) = gpu_mipmaps {
+            mipmaps.generate(derive, queue, &texture);
+        } else {
+            for (level, img) in mipmap::generate_cpu(&rgba, mip_level_count)
+                .iter()
+                .enumerate()
+            {
+                Self::write_level(queue, &texture, level as u32 + 1, img);
+            }
+        }
+
+        let view = texture.create_view(&wgpu::wgt::TextureViewDescriptor::default());
+        let sampler = derive.create_sampler(&options.sampler_descriptor(derive.features()));
+        Ok(Self {
+            texture,
+            view,
+            sampler,
+        })
+    }
+
+    /// 上传 KTX2/DDS 中预先生成的各级 mipmap；设备不支持该压缩格式时先转码为 RGBA8
+    pub fn from_compressed(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        image: &compressed::CompressedImage,
+        label: &str,
+        options: &TextureOptions,
+    ) -> Result<Self> {
+        let (block_width, block_height) = image.format.block_dimensions();
+        // 压缩纹理第 0 级的尺寸必须是块尺寸的整数倍
+        let aligned =
+            image.width.is_multiple_of(block_width) && image.height.is_multiple_of(block_height);
+        let transcoded;
+        let image = if compressed::is_supported(device, image.format) && aligned {
+            image
+        } else {
+            log::info!("{label}: 设备不支持 {:?}，转码为 RGBA8", image.format);
+            transcoded = image.transcode_rgba8()?;
+            &transcoded
+        };
+
+        let size = wgpu::Extent3d {
+            width: image.width,
+            height: image.height,
+            depth_or_array_layers: 1,
+        };
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(label),
+            size,
+            mip_level_count: image.levels.len() as u32,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: image.format,
             usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
             view_formats: &[],
         });
 
+        for (level, data) in image.levels.iter().enumerate() {
+            let level = level as u32;
+            let (bytes_per_row, rows) =
+                compressed::level_layout(image.format, image.width, image.height, level);
+            queue.write_texture(
+                wgpu::TexelCopyTextureInfo {
+                    aspect: wgpu::TextureAspect::All,
+                    texture: &texture,
+                    mip_level: level,
+                    origin: wgpu::Origin3d::ZERO,
+                },
+                data,
+                wgpu::TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: Some(bytes_per_row),
+                    rows_per_image: Some(rows),
+                },
+                size.mip_level_size(level, wgpu::TextureDimension::D2)
+                    .physical_size(image.format),
+            );
+        }
+
+        let view = texture.create_view(&wgpu::wgt::TextureViewDescriptor::default());
+        let options = options.mipmaps(image.levels.len() > 1);
+        let sampler = device.create_sampler(&options.sampler_descriptor(device.features()));
+        Ok(Self {
+            texture,
+            view,
+            sampler,
+        })
+    }
+
+    fn write_level(
+        queue: &wgpu::Queue,
+        texture: &wgpu::Texture,
+        level: u32,
+        img: &image::RgbaImage,
+    ) {
+        let (width, height) = img.dimensions();
         queue.write_texture(
             wgpu::TexelCopyTextureInfo {
                 aspect: wgpu::TextureAspect::All,
-                texture: &texture,
-                mip_level: 0,
+                texture,
+                mip_level: level,
                 origin: wgpu::Origin3d::ZERO,
             },
-            &rgba,
+            img,
             wgpu::TexelCopyBufferLayout {
                 offset: 0,
-                bytes_per_row: Some(4 * dimensions.0),
-                rows_per_image: Some(dimensions.1),
+                bytes_per_row: Some(4 * width),
+                rows_per_image: Some(height),
+            },
+            wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
             },
-            size,
         );
+    }
+}
 
-        let view = texture.create_view(&wgpu::wgt::TextureViewDescriptor::default());
-        let sampler = derive.create_sampler(&wgpu::wgt::SamplerDescriptor {
-            label: None,
-            address_mode_u: wgpu::AddressMode::ClampToEdge,
-            address_mode_v: wgpu::AddressMode::ClampToEdge,
-            address_mode_w: wgpu::AddressMode::ClampToEdge,
-            mag_filter: wgpu::FilterMode::Linear,
-            min_filter: wgpu::FilterMode::Nearest,
-            mipmap_filter: wgpu::FilterMode::Nearest,
-            ..Default::default()
-        });
-        Ok(Self {
-            texture,
-            view,
-            sampler,
-        })
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn data_textures_use_linear_format() {
+        assert_eq!(
+            TextureOptions::default().format(),
+            wgpu::TextureFormat::Rgba8UnormSrgb
+        );
+        let options = TextureOptions::default().color_space(ColorSpace::Linear);
+        assert_eq!(options.format(), wgpu::TextureFormat::Rgba8Unorm);
+    }
+
+    #[test]
+    fn anisotropy_requires_linear_filtering() {
+        let linear = TextureOptions::default().sampler_descriptor(wgpu::Features::empty());
+        assert_eq!(linear.anisotropy_clamp, 16);
+        // 像素风贴图：放大用最近点，wgpu 不允许此时开启各向异性过滤
+        let nearest = TextureOptions::default()
+            .mag_filter(wgpu::FilterMode::Nearest)
+            .sampler_descriptor(wgpu::Features::empty());
+        assert_eq!(nearest.anisotropy_clamp, 1);
+    }
+
+    #[test]
+    fn border_color_falls_back_without_feature() {
+        let options = TextureOptions::default().border_color(wgpu::SamplerBorderColor::OpaqueBlack);
+        let supported = options.sampler_descriptor(wgpu::Features::ADDRESS_MODE_CLAMP_TO_BORDER);
+        assert_eq!(supported.address_mode_u, wgpu::AddressMode::ClampToBorder);
+        assert_eq!(
+            supported.border_color,
+            Some(wgpu::SamplerBorderColor::OpaqueBlack)
+        );
+
+        let fallback = options.sampler_descriptor(wgpu::Features::empty());
+        assert_eq!(fallback.address_mode_u, wgpu::AddressMode::ClampToEdge);
+        assert_eq!(fallback.border_color, None);
     }
 }
@@ -0,0 +1,209 @@
+use wgpu::util::DeviceExt;
+use winit::event::{ElementState, KeyEvent};
+use winit::keyboard::{KeyCode, PhysicalKey};
+
+use crate::postprocess;
+
+/// 色调映射曲线
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize)]
+pub enum Tonemapper {
+    Reinhard,
+    /// ACES 电影曲线的拟合，对比度较高
+    Aces,
+    /// 高光向白色过渡更自然，饱和颜色不易偏色
+    Agx,
+}
+
+impl Tonemapper {
+    fn next(self) -> Self {
+        match self {
+            Tonemapper::Reinhard => Tonemapper::Aces,
+            Tonemapper::Aces => Tonemapper::Agx,
+            Tonemapper::Agx => Tonemapper::Reinhard,
+        }
+    }
+
+    /// tonemap.wgsl 中 `curve` 的取值
+    fn id(self) -> u32 {
+        match self {
+            Tonemapper::Reinhard => 0,
+            Tonemapper::Aces => 1,
+            Tonemapper::Agx => 2,
+        }
+    }
+}
+
+/// 布局与 tonemap.wgsl 中的 `TonemapUniform` 一致
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct TonemapUniform {
+    exposure: f32,
+    curve: u32,
+    encode_srgb: u32,
+    _padding: u32,
+}
+
+/// 色调映射通道，位于后处理链的 HDR 和 LDR 阶段之间，见 `PostProcess::render`。
+///
+/// 把 HDR 颜色按曝光缩放、映射到 [0, 1] 后写入交换链格式的纹理。
+/// `=`/`-` 以半档调整曝光，`0` 恢复默认曝光，`T` 切换映射曲线。
+pub struct ToneMapping {
+    pub tonemapper: Tonemapper,
+    /// 曝光值（EV），颜色乘以 2^EV
+    pub exposure: f32,
+    output_format: wgpu::TextureFormat,
+    buffer: wgpu::Buffer,
+    bind_group_layout: wgpu::BindGroupLayout,
+    pipeline: wgpu::RenderPipeline,
+}
+
+impl ToneMapping {
+    /// 每次按键调整的曝光档数
+    const EXPOSURE_STEP: f32 = 0.5;
+    const MAX_EXPOSURE: f32 = 10.0;
+
+    /// `output_format` 为交换链（或离屏纹理）的格式
+    pub fn new(device: &wgpu::Device, output_format: wgpu::TextureFormat) -> Self {
+        let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Tonemap Buffer"),
+            contents: bytemuck::cast_slice(&[TonemapUniform {
+                exposure: 1.0,
+                curve: Tonemapper::Aces.id(),
+                encode_srgb: 0,
+                _padding: 0,
+            }]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("tonemap_bind_group_layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                postprocess::uniform_entry(1),
+            ],
+        });
+
+        let shader =
+            postprocess::shader_module(device, "tonemap_shader", include_str!("tonemap.wgsl"));
+        let pipeline = postprocess::fullscreen_pipeline(
+            device,
+            "tonemap_pipeline",
+            &shader,
+            "fs_main",
+            &[&bind_group_layout],
+            output_format,
+            None,
+        );
+
+        Self {
+            tonemapper: Tonemapper::Aces,
+            exposure: 0.0,
+            output_format,
+            buffer,
+            bind_group_layout,
+            pipeline,
+        }
+    }
+
+    pub fn process_events(&mut self, event: &KeyEvent) -> bool {
+        if event.state != ElementState::Pressed {
+            return false;
+        }
+        match event.physical_key {
+            PhysicalKey::Code(KeyCode::Equal | KeyCode::NumpadAdd) => {
+                self.adjust_exposure(Self::EXPOSURE_STEP)
+            }
+            PhysicalKey::Code(KeyCode::Minus | KeyCode::NumpadSubtract) => {
+                self.adjust_exposure(-Self::EXPOSURE_STEP)
+            }
+            PhysicalKey::Code(KeyCode::Digit0 | KeyCode::Numpad0) => self.exposure = 0.0,
+            PhysicalKey::Code(KeyCode::KeyT) if !event.repeat => {
+                self.tonemapper = self.tonemapper.next();
+                log::info!("色调映射: {:?}", self.tonemapper);
+            }
+            _ => return false,
+        }
+        true
+    }
+
+    fn adjust_exposure(&mut self, stops: f32) {
+        self.exposure = (self.exposure + stops).clamp(-Self::MAX_EXPOSURE, Self::MAX_EXPOSURE);
+        log::info!("曝光: {:+.1} EV", self.exposure);
+    }
+
+    pub fn update(&self, queue: &wgpu::Queue) {
+        queue.write_buffer(
+            &self.buffer,
+            0,
+            bytemuck::cast_slice(&[TonemapUniform {
+                exposure: self.exposure.exp2(),
+                curve: self.tonemapper.id(),
+                encode_srgb: !self.output_format.is_srgb() as u32,
+                _padding: 0,
+            }]),
+        );
+    }
+
+    /// 把 HDR 纹理 `input` 映射后写入 `output`，两者尺寸相同
+    pub fn render(
+        &self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        input: &wgpu::TextureView,
+        output: &wgpu::TextureView,
+    ) {
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("tonemap_bind_group"),
+            layout: &self.bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(input),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: self.buffer.as_entire_binding(),
+                },
+            ],
+        });
+        postprocess::fullscreen_pass(
+            encoder,
+            "tonemap_pass",
+            output,
+            wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+            &self.pipeline,
+            &[&bind_group],
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tonemap_uniform_matches_wgsl() {
+        assert_eq!(size_of::<TonemapUniform>(), 16);
+    }
+
+    #[test]
+    fn tonemapper_ids_cycle_through_all_curves() {
+        let mut tonemapper = Tonemapper::Reinhard;
+        let mut ids = Vec::new();
+        for _ in 0..3 {
+            ids.push(tonemapper.id());
+            tonemapper = tonemapper.next();
+        }
+        assert_eq!(ids, [0, 1, 2]);
+        assert_eq!(tonemapper, Tonemapper::Reinhard);
+    }
+}
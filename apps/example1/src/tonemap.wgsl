@@ -0,0 +1,87 @@
+// 色调映射：把 HDR 颜色按曝光缩放后压缩到 [0, 1]，再写入交换链
+// 顶点着色器见 fullscreen.wgsl
+
+// 与 tonemap.rs 中的 `TonemapUniform` 一致
+struct TonemapUniform {
+    // 线性曝光倍数，即 2^EV
+    exposure: f32,
+    // 0 = Reinhard，1 = ACES，2 = AgX
+    curve: u32,
+    // 输出格式不是 sRGB 时需要在着色器里做 gamma 编码
+    encode_srgb: u32,
+    _padding: u32,
+}
+
+@group(0) @binding(0)
+var hdr_texture: texture_2d<f32>;
+@group(0) @binding(1)
+var<uniform> tonemap: TonemapUniform;
+
+fn reinhard(color: vec3f) -> vec3f {
+    return color / (1.0 + color);
+}
+
+// Krzysztof Narkowicz 对 ACES 电影曲线的拟合
+fn aces(color: vec3f) -> vec3f {
+    let a = color * (2.51 * color + 0.03);
+    let b = color * (2.43 * color + 0.59) + 0.14;
+    return clamp(a / b, vec3f(0.0), vec3f(1.0));
+}
+
+// AgX 的最小实现：先转到 AgX 基，在 log2 空间用多项式近似默认对比度曲线，再转回线性
+fn agx_contrast(x: vec3f) -> vec3f {
+    let x2 = x * x;
+    let x4 = x2 * x2;
+    return 15.5 * x4 * x2 - 40.14 * x4 * x + 31.96 * x4 - 6.868 * x2 * x + 0.4298 * x2
+        + 0.1191 * x - 0.00232;
+}
+
+fn agx(color: vec3f) -> vec3f {
+    let inset = mat3x3f(
+        0.842479062253094, 0.0423282422610123, 0.0423756549057051,
+        0.0784335999999992, 0.878468636469772, 0.0784336,
+        0.0792237451477643, 0.0791661274605434, 0.879142973793104,
+    );
+    let outset = mat3x3f(
+        1.19687900512017, -0.0528968517574562, -0.0529716355144438,
+        -0.0980208811401368, 1.15190312990417, -0.0980434501171241,
+        -0.0990297440797205, -0.0989611768448433, 1.15107367264116,
+    );
+    let min_ev = -12.47393;
+    let max_ev = 4.026069;
+
+    var v = inset * color;
+    v = clamp(log2(max(v, vec3f(1e-10))), vec3f(min_ev), vec3f(max_ev));
+    v = (v - min_ev) / (max_ev - min_ev);
+    v = agx_contrast(v);
+    // 曲线输出的是显示编码值，按 2.2 gamma 还原为线性，交给 sRGB 目标重新编码
+    v = outset * v;
+    return pow(max(v, vec3f(0.0)), vec3f(2.2));
+}
+
+fn linear_to_srgb(color: vec3f) -> vec3f {
+    let low = color * 12.92;
+    let high = 1.055 * pow(color, vec3f(1.0 / 2.4)) - 0.055;
+    return select(high, low, color <= vec3f(0.0031308));
+}
+
+@fragment
+fn fs_main(in: FullscreenOutput) -> @location(0) vec4f {
+    let hdr = textureLoad(hdr_texture, vec2i(in.pos.xy), 0).rgb * tonemap.exposure;
+    var color: vec3f;
+    switch tonemap.curve {
+        case 0u: {
+            color = reinhard(hdr);
+        }
+        case 1u: {
+            color = aces(hdr);
+        }
+        default: {
+            color = agx(hdr);
+        }
+    }
+    if tonemap.encode_srgb != 0u {
+        color = linear_to_srgb(color);
+    }
+    return vec4f(color, 1.0);
+}
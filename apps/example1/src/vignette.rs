@@ -0,0 +1,112 @@
+use wgpu::util::DeviceExt;
+
+use crate::postprocess::{self, PostEffect, Stage};
+
+/// 布局与 vignette.wgsl 中的 `VignetteUniform` 一致
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct VignetteUniform {
+    intensity: f32,
+    radius: f32,
+    softness: f32,
+    _padding: f32,
+}
+
+/// 暗角，在 LDR 阶段运行
+pub struct Vignette {
+    sampler: wgpu::Sampler,
+    buffer: wgpu::Buffer,
+    bind_group_layout: wgpu::BindGroupLayout,
+    pipeline: wgpu::RenderPipeline,
+}
+
+impl Vignette {
+    const INTENSITY: f32 = 0.35;
+    const RADIUS: f32 = 0.55;
+    const SOFTNESS: f32 = 0.45;
+
+    /// `format` 为 LDR 阶段的纹理格式，即交换链格式
+    pub fn new(device: &wgpu::Device, format: wgpu::TextureFormat) -> Self {
+        let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Vignette Buffer"),
+            contents: bytemuck::cast_slice(&[VignetteUniform {
+                intensity: Self::INTENSITY,
+                radius: Self::RADIUS,
+                softness: Self::SOFTNESS,
+                _padding: 0.0,
+            }]),
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("vignette_bind_group_layout"),
+            entries: &[
+                postprocess::texture_entry(0),
+                postprocess::sampler_entry(1),
+                postprocess::uniform_entry(2),
+            ],
+        });
+        let shader =
+            postprocess::shader_module(device, "vignette_shader", include_str!("vignette.wgsl"));
+        let pipeline = postprocess::fullscreen_pipeline(
+            device,
+            "vignette_pipeline",
+            &shader,
+            "fs_main",
+            &[&bind_group_layout],
+            format,
+            None,
+        );
+
+        Self {
+            sampler: postprocess::linear_sampler(device),
+            buffer,
+            bind_group_layout,
+            pipeline,
+        }
+    }
+}
+
+impl PostEffect for Vignette {
+    fn name(&self) -> &'static str {
+        "vignette"
+    }
+
+    fn stage(&self) -> Stage {
+        Stage::Ldr
+    }
+
+    fn render(
+        &self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        input: &wgpu::TextureView,
+        output: &wgpu::TextureView,
+    ) {
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("vignette_bind_group"),
+            layout: &self.bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(input),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&self.sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: self.buffer.as_entire_binding(),
+                },
+            ],
+        });
+        postprocess::fullscreen_pass(
+            encoder,
+            "vignette_pass",
+            output,
+            wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+            &self.pipeline,
+            &[&bind_group],
+        );
+    }
+}
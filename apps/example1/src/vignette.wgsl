@@ -0,0 +1,27 @@
+// 暗角：按到画面中心的距离压暗四周；顶点着色器见 fullscreen.wgsl
+
+// 与 vignette.rs 中的 `VignetteUniform` 一致
+struct VignetteUniform {
+    // 四角最多压暗的比例
+    intensity: f32,
+    // 从中心到该距离（中心到角为 1）之内不压暗
+    radius: f32,
+    // 从开始压暗到压暗最强的过渡宽度
+    softness: f32,
+    _padding: f32,
+}
+
+@group(0) @binding(0)
+var source_texture: texture_2d<f32>;
+@group(0) @binding(1)
+var source_sampler: sampler;
+@group(0) @binding(2)
+var<uniform> vignette: VignetteUniform;
+
+@fragment
+fn fs_main(in: FullscreenOutput) -> @location(0) vec4f {
+    let color = textureSample(source_texture, source_sampler, in.uv);
+    let distance = length(in.uv - 0.5) * sqrt(2.0);
+    let falloff = smoothstep(vignette.radius, vignette.radius + vignette.softness, distance);
+    return vec4f(color.rgb * (1.0 - vignette.intensity * falloff), color.a);
+}
@@ -1,56 +1,150 @@
+use crate::bloom::Bloom;
 use crate::camera_controller::CameraController;
 use crate::camera_uniform::CameraUniform;
-use crate::instance::{Instance, InstanceRaw};
-use crate::vertex::{VERTEX_INDEX_LIST, VERTEX_LIST};
+use crate::color_grading::{ColorGrading, Lut};
+use crate::culling::GpuCulling;
+use crate::environment::Environment;
+use crate::frustum::CullingStats;
+use crate::fxaa::Fxaa;
+use crate::instance::{Instance, InstanceId, InstanceManager, InstanceRaw};
+use crate::light::{LightId, LightManager};
+use crate::mipmap::MipmapGenerator;
+use crate::postprocess::PostProcess;
+use crate::scene::{ModelId, NodeContent, NodeId, SceneGraph};
+use crate::scene_file::{ModelSource, SceneFile, Spin};
+use crate::shader_reload::{self, ShaderWatcher};
+use crate::skybox::Skybox;
+use crate::tonemap::ToneMapping;
+use crate::vignette::Vignette;
 use crate::{
     camera,
-    model::{DrawModel, Model, ModelVertex, Vertex},
+    model::{DrawModel, Material, Model, ModelVertex, Vertex},
     resources, texture,
 };
 
+use std::collections::HashMap;
 use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
 
+use anyhow::Context;
 use log::info;
 use wgpu::MemoryHints::Performance;
 use wgpu::Trace;
-use wgpu::util::{BufferInitDescriptor, DeviceExt};
+use wgpu::util::DeviceExt;
 use winit::dpi::PhysicalSize;
 use winit::window::Window;
 
+/// 由命令行决定的后端、适配器和交换链选项
+#[derive(Debug, Clone, Copy)]
+pub struct ContextOptions {
+    pub backends: wgpu::Backends,
+    pub power_preference: wgpu::PowerPreference,
+    /// 离屏模式下不使用
+    pub present_mode: wgpu::PresentMode,
+}
+
+impl Default for ContextOptions {
+    fn default() -> Self {
+        Self {
+            backends: wgpu::Backends::all(),
+            power_preference: wgpu::PowerPreference::default(),
+            present_mode: wgpu::PresentMode::Fifo,
+        }
+    }
+}
+
+/// 场景文件中声明的一个模型及其全部实例
+struct SceneModel {
+    model: Model,
+    instances: InstanceManager,
+    /// 实例的视锥剔除，适配器不支持计算着色器时为 `None`，改在 CPU 上剔除
+    culling: Option<GpuCulling>,
+    casts_shadow: bool,
+}
+
+/// 场景节点在渲染端对应的对象，由 `WgpuCtx::sync_scene` 按需创建
+#[derive(Clone, Copy)]
+enum SceneBinding {
+    Instance(ModelId, InstanceId),
+    Light(LightId),
+}
+
 pub struct WgpuCtx<'window> {
-    surface: wgpu::Surface<'window>,
+    /// 离屏模式下为 `None`，此时渲染到 `offscreen_texture`
+    surface: Option<wgpu::Surface<'window>>,
     surface_config: wgpu::SurfaceConfiguration,
+    /// 窗口最小化（大小为零）时暂停渲染，交换链保持原来的配置
+    minimized: bool,
+    /// 设备丢失后用来重新请求适配器，见 `recover_device`
+    instance: wgpu::Instance,
+    options: ContextOptions,
     adapter: wgpu::Adapter,
     device: wgpu::Device,
     queue: wgpu::Queue,
+    /// 由 `device` 的丢失回调置位；每个设备各有一个，旧设备被释放时不会影响新设备
+    device_lost: Arc<AtomicBool>,
+    /// 重建全部 GPU 资源时用到的场景描述
+    scene_file: SceneFile,
     render_pipeline: wgpu::RenderPipeline,
-    vertex_buffer: wgpu::Buffer,
-    vertex_index_buffer: wgpu::Buffer,
-    diffuse_textures: texture::Texture,
-    //  texture_image: RgbaImg,
-    // texture_size: wgpu::Extent3d,
-    //sample: wgpu::Sampler,
-    bind_group: wgpu::BindGroup,
+    render_pipeline_layout: wgpu::PipelineLayout,
+    /// 当前管线使用的着色器源码，更改采样数时据此重建管线
+    shader_source: String,
+    /// 开发模式下监视 shader.wgsl，改动后重建 `render_pipeline`
+    shader_watcher: Option<ShaderWatcher>,
     camera: camera::Camera,
     camera_uniform: CameraUniform,
     camera_buffer: wgpu::Buffer,
     camera_bind_group: wgpu::BindGroup,
     pub camera_controller: CameraController,
-    instances: Vec<Instance>,
-    instance_buffer: wgpu::Buffer,
+    pub lights: LightManager,
+    /// 实例、光源和相机的层级变换，每帧同步到下面的实例管理器、`lights` 和 `camera`
+    pub scene: SceneGraph,
+    scene_bindings: HashMap<NodeId, SceneBinding>,
+    /// 场景文件中声明了自转的节点，见 `simulate`
+    spins: Vec<Spin>,
+    /// 按 `ModelId` 排列
+    models: Vec<SceneModel>,
+    /// 最近一帧 CPU 剔除的结果；GPU 剔除的实例不在其中
+    pub culling_stats: CullingStats,
     depth_texture: texture::Texture,
-    obj_model: Model,
+    clear_color: wgpu::Color,
+    /// 场景通道的采样数，为 1 时不开启多重采样
+    sample_count: u32,
+    /// 多重采样的颜色附件，解析到 `post_process.scene_view()`；不开启时为 `None`
+    msaa_view: Option<wgpu::TextureView>,
+    /// 基于图像的光照，绑定到主管线的第 3 组
+    environment: Environment,
+    skybox: Skybox,
+    offscreen_texture: Option<wgpu::Texture>,
+    /// 场景先渲染到其中的 HDR 纹理，经过后处理链后写入交换链
+    pub post_process: PostProcess,
+    pub tone_mapping: ToneMapping,
 }
 
 impl<'window> WgpuCtx<'window> {
-    pub async fn new_async(window: Arc<Window>) -> Self {
+    /// 离屏渲染使用的颜色格式，与 PNG 的 RGBA8 字节布局一致
+    pub const OFFSCREEN_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
+    /// 离屏渲染和测试中模拟的帧间隔
+    pub const HEADLESS_FRAME_TIME: Duration = Duration::from_nanos(1_000_000_000 / 60);
+    /// 场景管线的顶点缓冲区：槽位 0 为模型顶点，槽位 1 为实例
+    fn vertex_layouts() -> [wgpu::VertexBufferLayout<'static>; 2] {
+        [ModelVertex::desc(), InstanceRaw::desc()]
+    }
+
+    /// `scene` 需已通过 `SceneFile::validate`
+    pub async fn new_async(
+        window: Arc<Window>,
+        scene: &SceneFile,
+        options: ContextOptions,
+    ) -> anyhow::Result<Self> {
         let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor {
-            backends: wgpu::Backends::all(),
+            backends: options.backends,
             ..Default::default()
         });
 
         // 枚举所有可用的适配器
-        let adapters = instance.enumerate_adapters(wgpu::Backends::all());
+        let adapters = instance.enumerate_adapters(options.backends);
 
         info!("找到 {} 个图形适配器:", adapters.len());
         for (index, adapter) in adapters.iter().enumerate() {
@@ -61,130 +155,188 @@ impl<'window> WgpuCtx<'window> {
             info!("   ---");
         }
 
-        let surface = instance.create_surface(window.clone()).unwrap();
+        let surface = instance
+            .create_surface(window.clone())
+            .context("无法为窗口创建 Surface")?;
 
-        let adapter = instance
-            .request_adapter(&wgpu::RequestAdapterOptions {
-                power_preference: wgpu::PowerPreference::default(),
-                force_fallback_adapter: false,
-                compatible_surface: Some(&surface),
-            })
-            .await
-            .expect("Failed to find an appropriate adapter");
+        let adapter =
+            Self::request_adapter(&instance, options.power_preference, Some(&surface)).await?;
 
-        let (device, queue) = adapter
-            .request_device(&wgpu::DeviceDescriptor {
-                label: None,
-                required_features: wgpu::Features::empty(),
-                required_limits: wgpu::Limits::downlevel_webgl2_defaults()
-                    .using_resolution(adapter.limits()),
-                experimental_features: Default::default(),
-                memory_hints: Performance,
-                trace: Trace::Off,
-            })
-            .await
-            .expect("Failed to create device");
+        let (device, queue) = Self::request_device(&adapter).await?;
 
-        let mut size = window.inner_size();
+        let size = window.inner_size();
         let width = size.width.max(1);
         let height = size.height.max(1);
 
-        let surface_config = surface.get_default_config(&adapter, width, height).unwrap();
+        let mut surface_config = surface
+            .get_default_config(&adapter, width, height)
+            .context("所选适配器不支持在该窗口上呈现")?;
+        let present_modes = surface.get_capabilities(&adapter).present_modes;
+        if present_modes.contains(&options.present_mode) {
+            surface_config.present_mode = options.present_mode;
+        } else {
+            log::warn!(
+                "交换链不支持 {:?}，可用: {present_modes:?}，改用 {:?}",
+                options.present_mode,
+                surface_config.present_mode
+            );
+        }
         surface.configure(&device, &surface_config);
 
-        let bytes: &[u8] = bytemuck::cast_slice(&VERTEX_LIST);
-        let vertex_buffer = device.create_buffer_init(&BufferInitDescriptor {
-            label: None,
-            contents: bytes,
-            usage: wgpu::BufferUsages::VERTEX,
-        });
+        Self::from_parts(
+            Some(surface),
+            surface_config,
+            instance,
+            adapter,
+            (device, queue),
+            scene,
+            options,
+        )
+        .await
+    }
 
-        let vertex_index_bytes = bytemuck::cast_slice(&VERTEX_INDEX_LIST);
-        let vertex_index_buffer = device.create_buffer_init(&BufferInitDescriptor {
-            label: None,
-            contents: vertex_index_bytes,
-            usage: wgpu::BufferUsages::INDEX,
-        });
+    pub fn new(
+        window: Arc<Window>,
+        scene: &SceneFile,
+        options: ContextOptions,
+    ) -> anyhow::Result<WgpuCtx<'window>> {
+        pollster::block_on(WgpuCtx::new_async(window, scene, options))
+    }
 
-        // let img = RgbaImg::new("assets/example-img.png").unwrap();
-        let diffuse_bytes = include_bytes!("./../assets/example-img.png");
-        // let texture_size = wgpu::Extent3d {
-        //     width: img.width,
-        //     height: img.height,
-        //     depth_or_array_layers: 1, //1表示2d纹理
-        // };
-        // let texture = device.create_texture(&wgpu::wgt::TextureDescriptor {
-        //     label: None,
-        //     size: texture_size,
-        //     mip_level_count: 1,
-        //     sample_count: 1,
-        //     dimension: wgpu::TextureDimension::D2,
-        //     format: wgpu::TextureFormat::Rgba8UnormSrgb,
-        //     usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
-        //     view_formats: &[],
-        // });
-
-        // let sample = device.create_sampler(&SamplerDescriptor {
-        //     address_mode_u: wgpu::AddressMode::ClampToEdge,
-        //     address_mode_v: wgpu::AddressMode::ClampToEdge,
-        //     address_mode_w: wgpu::AddressMode::ClampToEdge,
-        //     ..Default::default()
-        // });
-
-        let diffuse_textures =
-            texture::Texture::from_bytes(&device, &queue, diffuse_bytes, "example-img.png")
-                .unwrap();
-
-        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-            label: None,
-            entries: &[
-                wgpu::BindGroupLayoutEntry {
-                    binding: 0,
-                    visibility: wgpu::ShaderStages::FRAGMENT,
-                    ty: wgpu::BindingType::Texture {
-                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
-                        view_dimension: wgpu::TextureViewDimension::D2,
-                        multisampled: false,
-                    },
-                    count: None,
-                },
-                wgpu::BindGroupLayoutEntry {
-                    binding: 1,
-                    visibility: wgpu::ShaderStages::FRAGMENT,
-                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
-                    count: None,
-                },
-            ],
+    /// 创建不依赖窗口的上下文，场景渲染到离屏纹理中。
+    ///
+    /// 优先使用软件（fallback）适配器，以便在没有 GPU 的 CI 机器上也能运行；
+    /// 找不到时再退回到任意可用适配器。
+    pub async fn new_headless_async(
+        width: u32,
+        height: u32,
+        scene: &SceneFile,
+        options: ContextOptions,
+    ) -> anyhow::Result<Self> {
+        let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor {
+            backends: options.backends,
+            ..Default::default()
         });
 
-        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            label: Some("diffuse_bind_group"),
-            layout: &bind_group_layout,
-            entries: &[
-                wgpu::BindGroupEntry {
-                    binding: 0,
-                    resource: wgpu::BindingResource::TextureView(&diffuse_textures.view),
-                },
-                wgpu::BindGroupEntry {
-                    binding: 1,
-                    resource: wgpu::BindingResource::Sampler(&diffuse_textures.sampler),
-                },
-            ],
-        });
+        let adapter = Self::request_adapter(&instance, options.power_preference, None).await?;
+        let adapter_info = adapter.get_info();
+        info!(
+            "离屏渲染使用适配器: {} ({:?}, {:?})",
+            adapter_info.name, adapter_info.backend, adapter_info.device_type
+        );
+
+        let (device, queue) = Self::request_device(&adapter).await?;
 
-        let camera = camera::Camera {
-            eye: (0.0, -2.0, 4.0).into(),
-            // 摄像机看向原点
-            target: (0.0, 0.0, 0.0).into(),
-            // 定义哪个方向朝上
-            up: glam::Vec3::Y,
-            // aspect: config.width as f32 / config.height as f32,
-            aspect: 4.0 / 3.0,
-            fovy: 45.0,
-            znear: 0.1,
-            zfar: 100.0,
+        // 离屏模式没有 Surface，但深度纹理等资源仍按这份配置创建
+        let surface_config = wgpu::SurfaceConfiguration {
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            format: Self::OFFSCREEN_FORMAT,
+            width: width.max(1),
+            height: height.max(1),
+            present_mode: wgpu::PresentMode::Fifo,
+            desired_maximum_frame_latency: 2,
+            alpha_mode: wgpu::CompositeAlphaMode::Opaque,
+            view_formats: vec![],
         };
 
+        Self::from_parts(
+            None,
+            surface_config,
+            instance,
+            adapter,
+            (device, queue),
+            scene,
+            options,
+        )
+        .await
+    }
+
+    pub fn new_headless(
+        width: u32,
+        height: u32,
+        scene: &SceneFile,
+        options: ContextOptions,
+    ) -> anyhow::Result<WgpuCtx<'window>> {
+        pollster::block_on(WgpuCtx::new_headless_async(width, height, scene, options))
+    }
+
+    /// 有 `surface` 时请求与之兼容的适配器；离屏模式优先使用软件（fallback）适配器，
+    /// 找不到时再退回到任意可用适配器
+    async fn request_adapter(
+        instance: &wgpu::Instance,
+        power_preference: wgpu::PowerPreference,
+        surface: Option<&wgpu::Surface<'_>>,
+    ) -> anyhow::Result<wgpu::Adapter> {
+        let fallback_first: &[bool] = if surface.is_some() {
+            &[false]
+        } else {
+            &[true, false]
+        };
+        for &force_fallback_adapter in fallback_first {
+            let adapter = instance
+                .request_adapter(&wgpu::RequestAdapterOptions {
+                    power_preference,
+                    force_fallback_adapter,
+                    compatible_surface: surface,
+                })
+                .await;
+            if let Ok(adapter) = adapter {
+                return Ok(adapter);
+            }
+        }
+        anyhow::bail!("找不到可用的图形适配器")
+    }
+
+    async fn request_device(
+        adapter: &wgpu::Adapter,
+    ) -> Result<(wgpu::Device, wgpu::Queue), wgpu::RequestDeviceError> {
+        adapter
+            .request_device(&wgpu::DeviceDescriptor {
+                label: None,
+                // 适配器支持哪些压缩纹理格式和边框寻址就启用哪些，
+                // 见 `Texture::from_compressed` 和 `TextureOptions::sampler_descriptor`；
+                // 2x、8x 等多重采样需要适配器特有的格式特性，见 `supported_sample_counts`
+                required_features: adapter.features()
+                    & (wgpu::Features::TEXTURE_COMPRESSION_BC
+                        | wgpu::Features::TEXTURE_COMPRESSION_ETC2
+                        | wgpu::Features::TEXTURE_COMPRESSION_ASTC
+                        | wgpu::Features::ADDRESS_MODE_CLAMP_TO_BORDER
+                        | wgpu::Features::ADDRESS_MODE_CLAMP_TO_ZERO
+                        | wgpu::Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES),
+                // 支持计算着色器时提高到 downlevel 限制，GPU 剔除需要存储缓冲区
+                required_limits: if GpuCulling::is_supported(adapter) {
+                    wgpu::Limits::downlevel_defaults()
+                } else {
+                    wgpu::Limits::downlevel_webgl2_defaults()
+                }
+                .using_resolution(adapter.limits()),
+                experimental_features: Default::default(),
+                memory_hints: Performance,
+                trace: Trace::Off,
+            })
+            .await
+    }
+
+    async fn from_parts(
+        surface: Option<wgpu::Surface<'window>>,
+        surface_config: wgpu::SurfaceConfiguration,
+        instance: wgpu::Instance,
+        adapter: wgpu::Adapter,
+        (device, queue): (wgpu::Device, wgpu::Queue),
+        scene_file: &SceneFile,
+        options: ContextOptions,
+    ) -> anyhow::Result<Self> {
+        let device_lost = Arc::new(AtomicBool::new(false));
+        device.set_device_lost_callback({
+            let device_lost = device_lost.clone();
+            move |reason, message| {
+                log::error!("图形设备丢失 ({reason:?}): {message}");
+                device_lost.store(true, Ordering::Release);
+            }
+        });
+
+        let camera = scene_file.camera();
+
         let mut camera_uniform = CameraUniform::new();
         camera_uniform.update_view_proj(&camera);
 
@@ -199,7 +351,8 @@ impl<'window> WgpuCtx<'window> {
                 label: Some("camera_bind_group_layout"),
                 entries: &[wgpu::BindGroupLayoutEntry {
                     binding: 0,
-                    visibility: wgpu::ShaderStages::VERTEX,
+                    // 片元着色器需要相机位置计算高光
+                    visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
                     ty: wgpu::BindingType::Buffer {
                         ty: wgpu::BufferBindingType::Uniform,
                         has_dynamic_offset: false,
@@ -218,104 +371,389 @@ impl<'window> WgpuCtx<'window> {
             }],
         });
 
-        let camera_controller = CameraController::new(0.2);
-        const NUM_INSTANCES_PRE_ROW: u32 = 10;
-        const INSTANCE_DISPLACEMENT: glam::Vec3 = glam::Vec3::new(
-            NUM_INSTANCES_PRE_ROW as f32 * 0.5,
-            0.0,
-            NUM_INSTANCES_PRE_ROW as f32 * 0.5,
-        );
-
-        const SPACE_BETWEEN: f32 = 3.0;
-        let instances = (0..NUM_INSTANCES_PRE_ROW)
-            .flat_map(|z| {
-                (0..NUM_INSTANCES_PRE_ROW).map(move |x| {
-                    let x = SPACE_BETWEEN * (x as f32 - NUM_INSTANCES_PRE_ROW as f32 / 2.0);
-                    let z = SPACE_BETWEEN * (z as f32 - NUM_INSTANCES_PRE_ROW as f32 / 2.0);
+        // 环境光改由天空的 IBL 提供，不再额外加常量
+        let mut lights = LightManager::new(&device, glam::Vec3::ZERO)?;
+        // 光源、相机和实例都作为场景节点添加，由 `sync_scene` 创建
+        let mut scene = SceneGraph::new();
+        let spins = scene_file.populate(&mut scene);
 
-                    let pos = glam::Vec3 { x: x, y: 0.0, z: z };
+        let camera_controller = CameraController::new(12.0);
+        let sample_count = Self::nearest_sample_count(
+            &Self::query_sample_counts(&adapter, &device),
+            scene_file.render.msaa,
+        );
+        info!("场景使用 {sample_count}x MSAA");
+        let depth_texture = texture::Texture::create_depth_texture(
+            &device,
+            &surface_config,
+            sample_count,
+            "depth_texture",
+        );
+        let msaa_view = Self::create_msaa_view(&device, &surface_config, sample_count);
 
-                    let rotation = if pos.length().abs() < f32::EPSILON {
-                        glam::Quat::from_axis_angle(glam::Vec3::Z, 0.0)
-                    } else {
-                        glam::Quat::from_axis_angle(pos.normalize(), std::f32::consts::FRAC_PI_4)
-                    };
-                    Instance { pos, rotation }
-                })
-            })
-            .collect::<Vec<_>>();
+        lights.set_shadow_bounds(
+            scene_file.render.shadow_center.into(),
+            scene_file.render.shadow_radius,
+        );
 
-        let instance_data = instances.iter().map(Instance::to_raw).collect::<Vec<_>>();
-        let instance_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Instance Buffer"),
-            contents: bytemuck::cast_slice(&instance_data),
-            usage: wgpu::BufferUsages::VERTEX,
-        });
-        let depth_texture =
-            texture::Texture::create_depth_texture(&device, &surface_config, "depth_texture");
+        let material_bind_group_layout = Material::bind_group_layout(&device);
+        let gpu_culling = GpuCulling::is_supported(&adapter);
+        if !gpu_culling {
+            log::warn!("适配器不支持计算着色器或间接绘制，改用 CPU 视锥剔除");
+        }
+        let mipmaps = MipmapGenerator::new(&device);
+        let mut models = Vec::with_capacity(scene_file.models.len());
+        for desc in &scene_file.models {
+            let model = match &desc.source {
+                ModelSource::File(file_name) => {
+                    resources::load_model(
+                        file_name,
+                        &device,
+                        &queue,
+                        &mipmaps,
+                        &material_bind_group_layout,
+                    )
+                    .await
+                }
+                ModelSource::Plane { half_size, color } => resources::create_plane(
+                    &device,
+                    &queue,
+                    *half_size,
+                    *color,
+                    &material_bind_group_layout,
+                ),
+            }
+            .with_context(|| format!("无法加载模型 \"{}\"", desc.name))?;
+            let instances = InstanceManager::new(&device, "Instance Buffer");
+            let culling = gpu_culling.then(|| GpuCulling::new(&device, &model, &instances));
+            models.push(SceneModel {
+                model,
+                instances,
+                culling,
+                casts_shadow: desc.casts_shadow,
+            });
+        }
 
-        let obj_model = resources::load_model("cube.obj", &device, &queue, &bind_group_layout)
+        let sky = resources::load_binary(&scene_file.render.environment)
             .await
-            .unwrap();
+            .with_context(|| format!("无法加载环境贴图 {}", scene_file.render.environment))?;
+        let environment = Environment::new(&device, &queue, &Environment::decode_equirect(&sky)?);
+        let skybox = Skybox::new(&device, &environment, PostProcess::HDR_FORMAT, sample_count);
+        skybox.update(&queue, &camera);
+
         let render_pipeline_layout =
             device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
                 label: None,
-                bind_group_layouts: &[&bind_group_layout, &camera_bind_group_layout],
+                bind_group_layouts: &[
+                    &material_bind_group_layout,
+                    &camera_bind_group_layout,
+                    &lights.bind_group_layout,
+                    &environment.bind_group_layout,
+                ],
                 push_constant_ranges: &[],
             });
 
-        let render_pipeline =
-            Self::create_pipeline(&device, surface_config.format, &render_pipeline_layout);
+        let shader_source = include_str!("shader.wgsl").to_string();
+        // 顶点缓冲区布局与着色器输入不一致时 wgpu 只会报出笼统的错误，启动时先逐个属性核对
+        shader_reload::validate_vertex_layouts(
+            &shader_source,
+            std::path::Path::new(shader_reload::SHADER_PATH),
+            "vs_main",
+            &Self::vertex_layouts(),
+        )
+        .context("shader.wgsl 的顶点输入与顶点缓冲区布局不一致")?;
+        let render_pipeline = Self::create_pipeline(
+            &device,
+            PostProcess::HDR_FORMAT,
+            sample_count,
+            &render_pipeline_layout,
+            &shader_source,
+        );
+
+        // 数字键 1..4 按这里的顺序开关各个效果
+        let (width, height, format) = (
+            surface_config.width,
+            surface_config.height,
+            surface_config.format,
+        );
+        let lut = resources::load_string("grading.cube")
+            .await
+            .and_then(|text| Lut::parse_cube(&text))
+            .context("无法加载调色 LUT grading.cube")?;
+        let mut post_process = PostProcess::new(&device, width, height, format);
+        post_process.push(Bloom::new(&device, width, height), true);
+        post_process.push(ColorGrading::new(&device, &queue, format, &lut), false);
+        post_process.push(Vignette::new(&device, format), true);
+        post_process.push(Fxaa::new(&device, format), true);
+        let mut tone_mapping = ToneMapping::new(&device, format);
+        tone_mapping.tonemapper = scene_file.render.tonemapper;
+        tone_mapping.exposure = scene_file.render.exposure;
 
-        WgpuCtx {
+        let offscreen_texture = surface
+            .is_none()
+            .then(|| Self::create_offscreen_texture(&device, &surface_config));
+
+        let mut wgpu_ctx = WgpuCtx {
             surface,
             surface_config,
+            minimized: false,
+            instance,
+            options,
             adapter,
             device,
             queue,
+            device_lost,
+            scene_file: scene_file.clone(),
             render_pipeline,
-            vertex_buffer,
-            vertex_index_buffer,
-            // texture_size,
-            // texture_image: img,
-            diffuse_textures,
-            // sample,
-            bind_group,
+            render_pipeline_layout,
+            shader_source,
+            shader_watcher: None,
             camera,
             camera_uniform,
             camera_buffer,
             camera_bind_group,
             camera_controller,
-            instances,
-            instance_buffer,
+            lights,
+            scene,
+            scene_bindings: HashMap::new(),
+            spins,
+            models,
+            culling_stats: CullingStats::default(),
             depth_texture,
-            obj_model,
+            clear_color: scene_file.clear_color(),
+            sample_count,
+            msaa_view,
+            environment,
+            skybox,
+            offscreen_texture,
+            post_process,
+            tone_mapping,
+        };
+        // 构造完成即可绘制，不必先调用 `update`
+        wgpu_ctx.upload_scene();
+        Ok(wgpu_ctx)
+    }
+
+    /// 同步场景图并把实例和光源上传到 GPU
+    fn upload_scene(&mut self) {
+        self.sync_scene();
+        self.lights.update(&self.queue);
+        for model in &mut self.models {
+            model.instances.update(&self.device, &self.queue);
         }
     }
 
-    pub fn new(window: Arc<Window>) -> WgpuCtx<'window> {
-        pollster::block_on(WgpuCtx::new_async(window))
+    /// 设备是否已丢失且尚未重建，见 `recover_device`
+    pub fn is_device_lost(&self) -> bool {
+        self.device_lost.load(Ordering::Acquire)
+    }
+
+    /// 窗口最小化时为 `true`，此时 `draw` 不做任何事
+    pub fn is_minimized(&self) -> bool {
+        self.minimized
+    }
+
+    /// 设备丢失后重新请求适配器和设备，重建全部 GPU 资源。
+    ///
+    /// 场景图、运行时添加的光源和实例（句柄不变）、相机、MSAA、色调映射、后处理开关
+    /// 和热重载的着色器等状态都会保留，重建后重新上传；失败时保持原样，下一帧再试。
+    pub fn recover_device(&mut self) -> anyhow::Result<()> {
+        info!("正在重建图形设备");
+        let adapter = pollster::block_on(Self::request_adapter(
+            &self.instance,
+            self.options.power_preference,
+            self.surface.as_ref(),
+        ))?;
+        let (device, queue) = pollster::block_on(Self::request_device(&adapter))?;
+        // 先以离屏模式重建，全部成功后再把 Surface 移过去，失败时 Surface 仍留在原处
+        let mut fresh = pollster::block_on(Self::from_parts(
+            None,
+            self.surface_config.clone(),
+            self.instance.clone(),
+            adapter,
+            (device, queue),
+            &self.scene_file,
+            self.options,
+        ))?;
+        if let Some(surface) = self.surface.take() {
+            surface.configure(&fresh.device, &fresh.surface_config);
+            fresh.surface = Some(surface);
+            fresh.offscreen_texture = None;
+        }
+
+        // 丢掉按场景文件新建的节点，换成当前的场景图、光源和实例并整体重新同步。
+        // 不属于场景图、运行时直接添加的光源和实例也一并保留，原有句柄依然有效
+        fresh.lights.adopt(&mut self.lights);
+        for (model, old) in fresh.models.iter_mut().zip(&mut self.models) {
+            model.instances.adopt(&mut old.instances);
+        }
+        fresh.scene_bindings = std::mem::take(&mut self.scene_bindings);
+        fresh.scene = std::mem::take(&mut self.scene);
+        fresh.scene.mark_all_dirty();
+        fresh.spins = std::mem::take(&mut self.spins);
+        fresh.upload_scene();
+        // 场景图里的相机节点只决定位置，控制器调整过的视角以旧相机为准
+        std::mem::swap(&mut fresh.camera, &mut self.camera);
+        std::mem::swap(&mut fresh.camera_controller, &mut self.camera_controller);
+
+        fresh.set_sample_count(self.sample_count);
+        fresh.tone_mapping.tonemapper = self.tone_mapping.tonemapper;
+        fresh.tone_mapping.exposure = self.tone_mapping.exposure;
+        fresh.post_process.set_enabled(&self.post_process.enabled());
+        fresh.shader_watcher = self.shader_watcher.take();
+        if fresh.shader_source != self.shader_source {
+            match fresh.try_create_pipeline(&self.shader_source) {
+                Ok(pipeline) => {
+                    fresh.render_pipeline = pipeline;
+                    fresh.shader_source = std::mem::take(&mut self.shader_source);
+                }
+                Err(err) => log::error!("无法重建热重载的着色器，改用内置着色器:\n{err:#}"),
+            }
+        }
+        fresh.minimized = self.minimized;
+        fresh.culling_stats = self.culling_stats;
+
+        *self = fresh;
+        info!("图形设备已重建");
+        Ok(())
+    }
+
+    fn create_offscreen_texture(
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+    ) -> wgpu::Texture {
+        device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("offscreen_texture"),
+            size: wgpu::Extent3d {
+                width: config.width,
+                height: config.height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: config.format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        })
+    }
+
+    /// 适配器对场景颜色格式和深度格式都支持的采样数，升序排列。
+    ///
+    /// 场景先渲染到 HDR 纹理再经后处理写入交换链，所以查询的是 `PostProcess::HDR_FORMAT`
+    /// 而不是交换链格式；多重采样的颜色附件还要求该格式支持解析。
+    fn query_sample_counts(adapter: &wgpu::Adapter, device: &wgpu::Device) -> Vec<u32> {
+        // 没有启用适配器特有的格式特性时，只能使用 WebGPU 保证的采样数（1 和 4）
+        let format_flags = |format: wgpu::TextureFormat| {
+            if device
+                .features()
+                .contains(wgpu::Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES)
+            {
+                adapter.get_texture_format_features(format).flags
+            } else {
+                format.guaranteed_format_features(device.features()).flags
+            }
+        };
+        let color = format_flags(PostProcess::HDR_FORMAT);
+        let depth = format_flags(texture::Texture::DEPTH_FORMAT);
+        color
+            .supported_sample_counts()
+            .into_iter()
+            .filter(|&count| {
+                depth.sample_count_supported(count)
+                    && (count == 1
+                        || color.contains(wgpu::TextureFormatFeatureFlags::MULTISAMPLE_RESOLVE))
+            })
+            .collect()
+    }
+
+    /// 不超过 `requested` 的最大可用采样数，至少为 1
+    fn nearest_sample_count(supported: &[u32], requested: u32) -> u32 {
+        supported
+            .iter()
+            .copied()
+            .filter(|&count| count <= requested)
+            .max()
+            .unwrap_or(1)
+    }
+
+    fn create_msaa_view(
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        sample_count: u32,
+    ) -> Option<wgpu::TextureView> {
+        if sample_count == 1 {
+            return None;
+        }
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("msaa_texture"),
+            size: wgpu::Extent3d {
+                width: config.width,
+                height: config.height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count,
+            dimension: wgpu::TextureDimension::D2,
+            format: PostProcess::HDR_FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        });
+        Some(texture.create_view(&wgpu::TextureViewDescriptor::default()))
+    }
+
+    /// 设置场景通道的 MSAA 采样数（1/2/4/8），适配器不支持时退回到更小的可用值。
+    ///
+    /// 返回实际使用的采样数；发生变化时重建渲染管线以及多重采样的颜色和深度附件。
+    pub fn set_sample_count(&mut self, requested: u32) -> u32 {
+        let supported = Self::query_sample_counts(&self.adapter, &self.device);
+        let sample_count = Self::nearest_sample_count(&supported, requested);
+        if sample_count != requested {
+            log::warn!("适配器不支持 {requested}x MSAA，可用: {supported:?}，改用 {sample_count}x");
+        }
+        if sample_count == self.sample_count {
+            return sample_count;
+        }
+
+        info!("场景使用 {sample_count}x MSAA");
+        self.sample_count = sample_count;
+        self.render_pipeline = Self::create_pipeline(
+            &self.device,
+            PostProcess::HDR_FORMAT,
+            sample_count,
+            &self.render_pipeline_layout,
+            &self.shader_source,
+        );
+        self.depth_texture = texture::Texture::create_depth_texture(
+            &self.device,
+            &self.surface_config,
+            sample_count,
+            "depth_texture",
+        );
+        self.msaa_view = Self::create_msaa_view(&self.device, &self.surface_config, sample_count);
+        self.skybox.set_sample_count(&self.device, sample_count);
+        sample_count
     }
 
     fn create_pipeline(
         device: &wgpu::Device,
-        swap_chain_format: wgpu::TextureFormat,
+        color_format: wgpu::TextureFormat,
+        sample_count: u32,
         pipeline_layout: &wgpu::PipelineLayout,
+        shader_source: &str,
     ) -> wgpu::RenderPipeline {
         let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
             label: None,
-            source: wgpu::ShaderSource::Wgsl(std::borrow::Cow::Borrowed(include_str!(
-                "shader.wgsl"
-            ))),
+            source: wgpu::ShaderSource::Wgsl(std::borrow::Cow::Borrowed(shader_source)),
         });
 
-        return device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
             label: None,
             layout: Some(pipeline_layout),
             vertex: wgpu::VertexState {
                 module: &shader,
                 entry_point: Some("vs_main"),
-                buffers: &[ModelVertex::desc(), InstanceRaw::desc()],
+                buffers: &Self::vertex_layouts(),
                 compilation_options: Default::default(),
             },
             primitive: wgpu::PrimitiveState {
@@ -326,7 +764,7 @@ impl<'window> WgpuCtx<'window> {
                 module: &shader,
                 entry_point: Some("fs_main"),
                 compilation_options: Default::default(),
-                targets: &[Some(swap_chain_format.into())],
+                targets: &[Some(color_format.into())],
             }),
             depth_stencil: Some(wgpu::DepthStencilState {
                 format: texture::Texture::DEPTH_FORMAT,
@@ -335,44 +773,137 @@ impl<'window> WgpuCtx<'window> {
                 stencil: wgpu::StencilState::default(),
                 bias: wgpu::DepthBiasState::default(),
             }),
-            multisample: wgpu::MultisampleState::default(),
+            multisample: wgpu::MultisampleState {
+                count: sample_count,
+                ..Default::default()
+            },
             multiview: None,
             cache: None,
-        });
+        })
     }
 
+    /// 开启着色器热重载：之后每次 `update` 都会检查磁盘上的 shader.wgsl 是否被修改
+    pub fn enable_shader_hot_reload(&mut self) {
+        info!("着色器热重载已开启，监视 {}", shader_reload::SHADER_PATH);
+        self.shader_watcher = Some(ShaderWatcher::new(shader_reload::SHADER_PATH));
+    }
+
+    /// 着色器文件有改动时重建渲染管线，编译失败则记录诊断信息并继续使用上一个可用的管线
+    fn reload_shader_if_changed(&mut self) {
+        let Some(source) = self.shader_watcher.as_mut().and_then(ShaderWatcher::poll) else {
+            return;
+        };
+        match self.try_create_pipeline(&source) {
+            Ok(pipeline) => {
+                info!("已重新加载着色器 {}", shader_reload::SHADER_PATH);
+                self.render_pipeline = pipeline;
+                self.shader_source = source;
+            }
+            Err(err) => log::error!("着色器重新加载失败，继续使用上一个管线:\n{err:#}"),
+        }
+    }
+
+    fn try_create_pipeline(&self, source: &str) -> anyhow::Result<wgpu::RenderPipeline> {
+        let path = std::path::Path::new(shader_reload::SHADER_PATH);
+        shader_reload::validate_wgsl(source, path)?;
+        shader_reload::validate_vertex_layouts(source, path, "vs_main", &Self::vertex_layouts())?;
+
+        // naga 通过后，绑定布局不匹配等错误仍可能在创建管线时出现，用错误作用域捕获而不是 panic
+        self.device.push_error_scope(wgpu::ErrorFilter::Validation);
+        let pipeline = Self::create_pipeline(
+            &self.device,
+            PostProcess::HDR_FORMAT,
+            self.sample_count,
+            &self.render_pipeline_layout,
+            source,
+        );
+        match pollster::block_on(self.device.pop_error_scope()) {
+            Some(err) => Err(anyhow::anyhow!("{}: {err}", path.display())),
+            None => Ok(pipeline),
+        }
+    }
+
+    /// 绘制一帧。窗口最小化或设备丢失时跳过；交换链过期或丢失时重新配置，跳过这一帧
     pub fn draw(&mut self) {
-        let surface_texture = self
+        if self.minimized || self.is_device_lost() {
+            return;
+        }
+        let surface_texture = match self
             .surface
-            .get_current_texture()
-            .expect("Failed to acquire next swap chain texture");
+            .as_ref()
+            .map(wgpu::Surface::get_current_texture)
+        {
+            None => None,
+            Some(Ok(surface_texture)) => Some(surface_texture),
+            Some(Err(err @ (wgpu::SurfaceError::Outdated | wgpu::SurfaceError::Lost))) => {
+                log::warn!("交换链不可用，重新配置: {err}");
+                self.configure_surface();
+                return;
+            }
+            Some(Err(wgpu::SurfaceError::Timeout)) => {
+                log::warn!("获取交换链纹理超时，跳过这一帧");
+                return;
+            }
+            Some(Err(err)) => {
+                log::error!("获取交换链纹理失败，跳过这一帧: {err}");
+                return;
+            }
+        };
 
-        let texutre_view = surface_texture
-            .texture
-            .create_view(&wgpu::TextureViewDescriptor::default());
+        let target_texture = match (&surface_texture, &self.offscreen_texture) {
+            (Some(surface_texture), _) => &surface_texture.texture,
+            (None, Some(offscreen_texture)) => offscreen_texture,
+            (None, None) => unreachable!("WgpuCtx 既没有 Surface 也没有离屏纹理"),
+        };
+        let texutre_view = target_texture.create_view(&wgpu::TextureViewDescriptor::default());
 
         let mut encoder = self
             .device
             .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        let mut culling_stats = CullingStats::default();
+        for culling in self
+            .models
+            .iter_mut()
+            .filter_map(|model| model.culling.as_mut())
+        {
+            culling_stats += culling.stats();
+            culling.cull(&mut encoder);
+        }
+        let frustum = self.camera.frustum();
+        // 先从光源视角渲染阴影贴图。
+        // 视锥外的实例仍可能把阴影投进画面，所以阴影通道使用未剔除的实例
+        if self.lights.shadow_view_proj().is_some() {
+            self.lights.shadow_map.render(
+                &mut encoder,
+                self.models
+                    .iter()
+                    .filter(|model| model.casts_shadow)
+                    .map(|model| (&model.model, &model.instances)),
+            );
+        }
         {
+            // 开启 MSAA 时渲染到多重采样纹理，通道结束时解析到后处理的输入纹理
+            let scene_view = self.post_process.scene_view();
+            let (view, resolve_target) = match &self.msaa_view {
+                Some(msaa_view) => (msaa_view, Some(scene_view)),
+                None => (scene_view, None),
+            };
             let mut r_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
                 label: None,
-                color_attachments: &[Some(
-                    (wgpu::RenderPassColorAttachment {
-                        view: &texutre_view,
-                        resolve_target: None,
-                        depth_slice: None,
-                        ops: wgpu::Operations {
-                            load: wgpu::LoadOp::Clear(wgpu::Color {
-                                r: 0.1,
-                                g: 0.2,
-                                b: 0.3,
-                                a: 1.0,
-                            }),
-                            store: wgpu::StoreOp::Store,
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view,
+                    resolve_target,
+                    depth_slice: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(self.clear_color),
+                        // 多重采样的数据解析后就不再需要
+                        store: if resolve_target.is_some() {
+                            wgpu::StoreOp::Discard
+                        } else {
+                            wgpu::StoreOp::Store
                         },
-                    }),
-                )],
+                    },
+                })],
                 depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
                     view: &self.depth_texture.view,
                     depth_ops: Some(wgpu::Operations {
@@ -384,42 +915,249 @@ impl<'window> WgpuCtx<'window> {
                 timestamp_writes: None,
                 occlusion_query_set: None,
             });
-            r_pass.set_bind_group(0, &self.bind_group, &[]);
+            // 第 0 组（材质）和顶点、索引缓冲区由每个网格的绘制设置
             r_pass.set_bind_group(1, &self.camera_bind_group, &[]);
+            r_pass.set_bind_group(3, &self.environment.bind_group, &[]);
             r_pass.set_pipeline(&self.render_pipeline);
-            r_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
-            r_pass.set_vertex_buffer(1, self.instance_buffer.slice(..));
-            r_pass.set_index_buffer(
-                self.vertex_index_buffer.slice(..),
-                wgpu::IndexFormat::Uint16,
-            );
 
-            r_pass.draw_model_instanced(
-                &self.obj_model,
-                0..self.instances.len() as u32,
-                &self.camera_bind_group,
-            );
+            for SceneModel {
+                model,
+                instances,
+                culling,
+                ..
+            } in &self.models
+            {
+                match culling {
+                    Some(culling) => {
+                        r_pass.set_vertex_buffer(1, culling.visible_buffer().slice(..));
+                        r_pass.draw_model_indirect(
+                            model,
+                            culling.indirect_buffer(),
+                            &self.camera_bind_group,
+                            &self.lights.bind_group,
+                        );
+                    }
+                    None => {
+                        r_pass.set_vertex_buffer(1, instances.buffer().slice(..));
+                        r_pass.draw_model_culled(
+                            model,
+                            instances.as_slice(),
+                            &frustum,
+                            &mut culling_stats,
+                            &self.camera_bind_group,
+                            &self.lights.bind_group,
+                        );
+                    }
+                }
+            }
+
+            // 天空盒最后绘制，被模型遮挡的像素在深度测试中直接跳过
+            self.skybox.render(&mut r_pass);
         }
+        if culling_stats != self.culling_stats {
+            log::debug!("视锥剔除: {culling_stats:?}");
+            self.culling_stats = culling_stats;
+        }
+        self.post_process.render(
+            &self.device,
+            &mut encoder,
+            &self.tone_mapping,
+            &texutre_view,
+        );
 
         self.queue.submit(Some(encoder.finish()));
-        surface_texture.present();
+        for culling in self
+            .models
+            .iter_mut()
+            .filter_map(|model| model.culling.as_mut())
+        {
+            culling.request_readback();
+        }
+        if let Some(surface_texture) = surface_texture {
+            let suboptimal = surface_texture.suboptimal;
+            surface_texture.present();
+            if suboptimal {
+                self.configure_surface();
+            }
+        }
     }
 
+    fn configure_surface(&self) {
+        if let Some(surface) = self.surface.as_ref() {
+            surface.configure(&self.device, &self.surface_config);
+        }
+    }
+
+    /// 将离屏纹理中最近一次 `draw` 的结果读回 CPU，见 `readback::capture_texture`
+    pub fn capture_frame(&self) -> anyhow::Result<image::RgbaImage> {
+        let texture = self
+            .offscreen_texture
+            .as_ref()
+            .context("只有离屏模式（new_headless）才能读回帧")?;
+        readback::capture_texture(&self.device, &self.queue, texture)
+    }
+
+    /// 读回当前帧并保存为 PNG 文件
+    pub fn save_frame_png(&self, path: impl AsRef<std::path::Path>) -> anyhow::Result<()> {
+        let path = path.as_ref();
+        self.capture_frame()?.save(path)?;
+        info!("帧已保存到 {}", path.display());
+        Ok(())
+    }
+
+    /// 大小为零（窗口最小化）时只暂停渲染，恢复后再按新的大小重建
     pub fn resize(&mut self, size: PhysicalSize<u32>) {
-        self.surface_config.width = size.width.max(1);
-        self.surface_config.height = size.height.max(1);
-        self.surface.configure(&self.device, &self.surface_config);
+        self.minimized = size.width == 0 || size.height == 0;
+        if self.minimized {
+            return;
+        }
+        self.surface_config.width = size.width;
+        self.surface_config.height = size.height;
+        if self.surface.is_some() {
+            self.configure_surface();
+        } else {
+            self.offscreen_texture = Some(Self::create_offscreen_texture(
+                &self.device,
+                &self.surface_config,
+            ));
+        }
 
         self.depth_texture = texture::Texture::create_depth_texture(
             &self.device,
             &self.surface_config,
+            self.sample_count,
             "depth_texture",
         );
+        self.msaa_view =
+            Self::create_msaa_view(&self.device, &self.surface_config, self.sample_count);
+        self.post_process.resize(
+            &self.device,
+            self.surface_config.width,
+            self.surface_config.height,
+        );
+    }
+
+    /// 推进一步场景模拟，`dt` 可能是固定步长，见 `FrameTimer::simulation_steps`
+    pub fn simulate(&mut self, dt: Duration) {
+        for spin in &self.spins {
+            if let Some(local) = self.scene.local_mut(spin.node) {
+                let angle = (spin.degrees * dt.as_secs_f32()).to_radians();
+                local.rotation *= glam::Quat::from_axis_angle(spin.axis, angle);
+            }
+        }
+    }
+
+    /// 场景中 `ModelId` 对应的实例
+    fn model_instances(&mut self, model: ModelId) -> Option<&mut InstanceManager> {
+        self.models
+            .get_mut(model.0)
+            .map(|model| &mut model.instances)
     }
 
-    pub fn update(&mut self /*, _dt: instant::Duration */) {
-        self.camera_controller.update_camera(&mut self.camera);
+    /// 把世界变换改变了的节点同步到实例、光源和相机，并释放已删除节点占用的实例和光源
+    fn sync_scene(&mut self) {
+        for id in self.scene.take_removed() {
+            if let Some(binding) = self.scene_bindings.remove(&id) {
+                self.unbind(binding);
+            }
+        }
+
+        for id in self.scene.update_world_transforms() {
+            let Some(node) = self.scene.get(id) else {
+                continue;
+            };
+            let (world, content) = (node.world(), node.content);
+            let binding = self.scene_bindings.get(&id).copied();
+            match content {
+                NodeContent::Empty => {}
+                NodeContent::Model(model) => {
+                    let (scale, rotation, pos) = world.to_scale_rotation_translation();
+                    let Some(instances) = self.model_instances(model) else {
+                        log::warn!("场景节点 {id:?} 引用了不存在的模型 {model:?}");
+                        continue;
+                    };
+                    if let Some(SceneBinding::Instance(_, instance)) = binding {
+                        if let Some(instance) = instances.get_mut(instance) {
+                            instance.pos = pos;
+                            instance.rotation = rotation;
+                            instance.scale = scale;
+                        }
+                    } else {
+                        let mut instance = Instance::new(pos, rotation);
+                        instance.scale = scale;
+                        let instance = instances.add(instance);
+                        self.scene_bindings
+                            .insert(id, SceneBinding::Instance(model, instance));
+                    }
+                }
+                NodeContent::Light(light) => {
+                    let light = light.transformed(&world);
+                    if let Some(SceneBinding::Light(light_id)) = binding {
+                        if let Some(current) = self.lights.get_mut(light_id) {
+                            *current = light;
+                        }
+                    } else if let Some(light_id) = self.lights.add(light) {
+                        self.scene_bindings
+                            .insert(id, SceneBinding::Light(light_id));
+                    } else {
+                        log::warn!(
+                            "光源数量超过 {}，忽略场景节点 {id:?}",
+                            crate::light::MAX_LIGHTS
+                        );
+                    }
+                }
+                // 保持视线长度不变，环绕模式仍围绕视线前方同样远的点旋转
+                NodeContent::Camera => {
+                    let eye = world.transform_point3(glam::Vec3::ZERO);
+                    let forward = world
+                        .transform_vector3(glam::Vec3::NEG_Z)
+                        .normalize_or(glam::Vec3::NEG_Z);
+                    let distance = self.camera.target.distance(self.camera.eye);
+                    self.camera.eye = eye;
+                    self.camera.target = eye + forward * distance;
+                }
+            }
+        }
+    }
+
+    /// 释放场景节点占用的实例或光源
+    fn unbind(&mut self, binding: SceneBinding) {
+        match binding {
+            SceneBinding::Instance(model, instance) => {
+                if let Some(instances) = self.model_instances(model) {
+                    instances.remove(instance);
+                }
+            }
+            SceneBinding::Light(light) => {
+                self.lights.remove(light);
+            }
+        }
+    }
+
+    /// 处理每帧的交互并把相机和光源上传到 GPU，`dt` 为距上一帧的时间。
+    ///
+    /// 设备丢失时先重建设备和全部 GPU 资源，重建失败则跳过这一帧
+    pub fn update(&mut self, dt: Duration) {
+        if self.is_device_lost()
+            && let Err(err) = self.recover_device()
+        {
+            log::error!("重建图形设备失败: {err:#}");
+            return;
+        }
+        self.reload_shader_if_changed();
+        self.sync_scene();
+        self.camera_controller.update_camera(&mut self.camera, dt);
         self.camera_uniform.update_view_proj(&self.camera);
+        self.skybox.update(&self.queue, &self.camera);
+        self.lights.update(&self.queue);
+        let frustum = self.camera.frustum();
+        for model in &mut self.models {
+            model.instances.update(&self.device, &self.queue);
+            if let Some(culling) = &mut model.culling {
+                culling.update(&self.device, &self.queue, &frustum, &model.instances);
+            }
+        }
+        self.tone_mapping.update(&self.queue);
         self.queue.write_buffer(
             &self.camera_buffer,
             0,
@@ -427,3 +1165,343 @@ impl<'window> WgpuCtx<'window> {
         );
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::light::Light;
+    use std::path::Path;
+
+    // 与相机固定的 4:3 宽高比一致
+    const WIDTH: u32 = 320;
+    const HEIGHT: u32 = 240;
+
+    /// 用内置场景创建离屏上下文。
+    ///
+    /// 找不到图形适配器时让测试失败而不是跳过，否则没有 GPU 的 CI 上这些测试什么也没检查；
+    /// 这类机器可以安装 Mesa（llvmpipe/lavapipe）提供软件适配器。
+    fn headless_ctx() -> WgpuCtx<'static> {
+        WgpuCtx::new_headless(
+            WIDTH,
+            HEIGHT,
+            &SceneFile::builtin(),
+            ContextOptions::default(),
+        )
+        .expect("无法创建离屏上下文，渲染测试需要一个图形适配器（软件适配器也可以）")
+    }
+
+    fn golden_path(name: &str) -> std::path::PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/golden")
+            .join(name)
+    }
+
+    #[test]
+    fn cube_grid_matches_golden() {
+        let mut wgpu_ctx = headless_ctx();
+        wgpu_ctx.simulate(WgpuCtx::HEADLESS_FRAME_TIME);
+        wgpu_ctx.update(WgpuCtx::HEADLESS_FRAME_TIME);
+        wgpu_ctx.draw();
+        let frame = wgpu_ctx.capture_frame().unwrap();
+
+        golden::assert_golden(
+            &golden_path("cube_grid.png"),
+            &frame,
+            &golden::Tolerance::default(),
+        );
+    }
+
+    #[test]
+    fn post_process_chain_matches_golden() {
+        let mut wgpu_ctx = headless_ctx();
+        // 默认关闭的调色也打开，覆盖全部四个效果
+        assert_eq!(
+            wgpu_ctx.post_process.toggle(1),
+            Some(("color_grading", true))
+        );
+        wgpu_ctx.simulate(WgpuCtx::HEADLESS_FRAME_TIME);
+        wgpu_ctx.update(WgpuCtx::HEADLESS_FRAME_TIME);
+        wgpu_ctx.draw();
+        let frame = wgpu_ctx.capture_frame().unwrap();
+
+        golden::assert_golden(
+            &golden_path("post_process_all.png"),
+            &frame,
+            &golden::Tolerance::default(),
+        );
+    }
+
+    #[test]
+    fn device_loss_rebuilds_resources_and_keeps_state() {
+        let mut wgpu_ctx = headless_ctx();
+        wgpu_ctx.post_process.toggle(1);
+        wgpu_ctx.device.destroy();
+        let _ = wgpu_ctx.device.poll(wgpu::PollType::wait_indefinitely());
+        assert!(wgpu_ctx.is_device_lost());
+        // 设备丢失后的绘制直接跳过
+        wgpu_ctx.draw();
+
+        // 重建后的画面与从未丢失过设备时一致，后处理开关也保留了下来
+        wgpu_ctx.simulate(WgpuCtx::HEADLESS_FRAME_TIME);
+        wgpu_ctx.update(WgpuCtx::HEADLESS_FRAME_TIME);
+        assert!(!wgpu_ctx.is_device_lost());
+        wgpu_ctx.draw();
+        let frame = wgpu_ctx.capture_frame().unwrap();
+
+        golden::assert_golden(
+            &golden_path("post_process_all.png"),
+            &frame,
+            &golden::Tolerance::default(),
+        );
+    }
+
+    #[test]
+    fn device_loss_keeps_lights_and_instances_outside_scene_graph() {
+        let mut wgpu_ctx = headless_ctx();
+        let light = wgpu_ctx
+            .lights
+            .add(Light::Point {
+                position: glam::vec3(0.0, 3.0, 0.0),
+                color: glam::Vec3::ONE,
+                intensity: 2.0,
+                range: 10.0,
+            })
+            .unwrap();
+        let pos = glam::vec3(0.0, 5.0, 0.0);
+        let instance = wgpu_ctx.models[0]
+            .instances
+            .add(Instance::new(pos, glam::Quat::IDENTITY));
+        let light_count = wgpu_ctx.lights.to_uniform().count;
+        let instance_count = wgpu_ctx.models[0].instances.as_slice().len();
+
+        wgpu_ctx.device.destroy();
+        let _ = wgpu_ctx.device.poll(wgpu::PollType::wait_indefinitely());
+        assert!(wgpu_ctx.is_device_lost());
+        wgpu_ctx.update(WgpuCtx::HEADLESS_FRAME_TIME);
+        assert!(!wgpu_ctx.is_device_lost());
+
+        // 旧句柄依然指向同一个光源和实例
+        assert_eq!(wgpu_ctx.lights.to_uniform().count, light_count);
+        assert!(wgpu_ctx.lights.get_mut(light).is_some());
+        let instances = &wgpu_ctx.models[0].instances;
+        assert_eq!(instances.as_slice().len(), instance_count);
+        assert_eq!(
+            instances.get(instance).map(|instance| instance.pos),
+            Some(pos)
+        );
+        wgpu_ctx.draw();
+    }
+
+    #[test]
+    fn minimized_window_pauses_rendering() {
+        let mut wgpu_ctx = headless_ctx();
+        wgpu_ctx.resize(PhysicalSize::new(0, 0));
+        assert!(wgpu_ctx.is_minimized());
+        assert_eq!(
+            (
+                wgpu_ctx.surface_config.width,
+                wgpu_ctx.surface_config.height
+            ),
+            (WIDTH, HEIGHT)
+        );
+        wgpu_ctx.update(WgpuCtx::HEADLESS_FRAME_TIME);
+        wgpu_ctx.draw();
+
+        wgpu_ctx.resize(PhysicalSize::new(WIDTH / 2, HEIGHT / 2));
+        assert!(!wgpu_ctx.is_minimized());
+        wgpu_ctx.draw();
+        let frame = wgpu_ctx.capture_frame().unwrap();
+        assert_eq!(frame.dimensions(), (WIDTH / 2, HEIGHT / 2));
+    }
+
+    #[test]
+    fn invalid_shader_keeps_last_good_pipeline() {
+        let wgpu_ctx = headless_ctx();
+
+        let source = include_str!("shader.wgsl");
+        assert!(wgpu_ctx.try_create_pipeline(source).is_ok());
+        // 语法错误由 naga 报告
+        let err = wgpu_ctx
+            .try_create_pipeline(&source.replace("fn fs_main", "fn fs_main("))
+            .unwrap_err();
+        assert!(err.to_string().contains("shader.wgsl:"), "{err}");
+        // 着色器本身合法，但入口函数不存在，只能在创建管线时发现
+        assert!(
+            wgpu_ctx
+                .try_create_pipeline(&source.replace("fn fs_main", "fn fs_other"))
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn msaa_falls_back_to_supported_sample_count() {
+        let mut wgpu_ctx = headless_ctx();
+        let supported = WgpuCtx::query_sample_counts(&wgpu_ctx.adapter, &wgpu_ctx.device);
+        assert!(supported.contains(&1), "{supported:?}");
+
+        // 16x 超出范围，退回到不超过它的最大可用值；之后改尺寸和绘制都不应触发验证错误
+        let sample_count = wgpu_ctx.set_sample_count(16);
+        assert!(sample_count <= 8 && supported.contains(&sample_count));
+        wgpu_ctx.resize(PhysicalSize::new(WIDTH / 2, HEIGHT / 2));
+        wgpu_ctx.draw();
+        // 后端不支持某种多重采样附件时，解析结果往往是全黑，左上角应当是天空的清屏色
+        let frame = wgpu_ctx.capture_frame().unwrap();
+        assert!(frame.get_pixel(0, 0)[2] > 64, "{:?}", frame.get_pixel(0, 0));
+        assert_eq!(wgpu_ctx.set_sample_count(1), 1);
+        assert!(wgpu_ctx.msaa_view.is_none());
+        wgpu_ctx.draw();
+    }
+
+    #[test]
+    fn nearest_sample_count_rounds_down() {
+        assert_eq!(WgpuCtx::nearest_sample_count(&[1, 4], 8), 4);
+        assert_eq!(WgpuCtx::nearest_sample_count(&[1, 4], 2), 1);
+        assert_eq!(WgpuCtx::nearest_sample_count(&[1, 2, 4, 8], 8), 8);
+        assert_eq!(WgpuCtx::nearest_sample_count(&[], 4), 1);
+    }
+
+    #[test]
+    fn instances_grow_and_keep_handles_after_removal() {
+        let mut wgpu_ctx = headless_ctx();
+        // 默认场景的第一个模型是立方体
+        let instances = &mut wgpu_ctx.models[0].instances;
+        let capacity = instances.capacity();
+        let count = instances.range().end;
+        let first = instances.add(Instance::new(glam::Vec3::X, glam::Quat::IDENTITY));
+        let second = instances.add(Instance::new(glam::Vec3::Y, glam::Quat::IDENTITY));
+        wgpu_ctx.update(WgpuCtx::HEADLESS_FRAME_TIME);
+        let instances = &mut wgpu_ctx.models[0].instances;
+        assert!(instances.capacity() >= (count as usize + 2).max(capacity * 2));
+
+        // 删除后最后一个实例被移到空位，它的句柄仍然有效
+        assert_eq!(instances.remove(first).unwrap().pos, glam::Vec3::X);
+        assert!(instances.get(first).is_none());
+        assert!(instances.remove(first).is_none());
+        instances.get_mut(second).unwrap().tint = glam::Vec4::new(1.0, 0.0, 0.0, 1.0);
+        assert_eq!(instances.get(second).unwrap().pos, glam::Vec3::Y);
+        assert_eq!(instances.range(), 0..count + 1);
+        wgpu_ctx.update(WgpuCtx::HEADLESS_FRAME_TIME);
+        wgpu_ctx.draw();
+    }
+
+    #[test]
+    fn scene_nodes_drive_instances() {
+        let mut wgpu_ctx = headless_ctx();
+        // 移动父节点，所有立方体实例随之移动
+        let cube_grid = wgpu_ctx.scene.find("cube_grid").unwrap();
+        wgpu_ctx.scene.local_mut(cube_grid).unwrap().translation.y = 1.0;
+        wgpu_ctx.update(WgpuCtx::HEADLESS_FRAME_TIME);
+        assert_eq!(wgpu_ctx.models[0].instances.range(), 0..100);
+        assert!(
+            wgpu_ctx.models[0]
+                .instances
+                .as_slice()
+                .iter()
+                .all(|instance| (instance.pos.y - 1.0).abs() < 1e-5)
+        );
+
+        // 删除节点后释放它的实例
+        let ground = wgpu_ctx.scene.find("ground").unwrap();
+        wgpu_ctx.scene.remove(ground);
+        wgpu_ctx.update(WgpuCtx::HEADLESS_FRAME_TIME);
+        assert!(wgpu_ctx.models[1].instances.range().is_empty());
+        wgpu_ctx.draw();
+    }
+
+    #[test]
+    fn gpu_culling_matches_cpu_frustum_test() {
+        let mut wgpu_ctx = headless_ctx();
+        // 相机身后和远处侧面的实例应被剔除
+        for pos in [
+            glam::Vec3::new(0.0, 14.0, 40.0),
+            glam::Vec3::new(500.0, 0.0, 0.0),
+        ] {
+            wgpu_ctx.models[0]
+                .instances
+                .add(Instance::new(pos, glam::Quat::IDENTITY));
+        }
+        wgpu_ctx.update(WgpuCtx::HEADLESS_FRAME_TIME);
+        wgpu_ctx.draw();
+        // 剔除结果异步读回，等 GPU 完成后下一帧的统计才包含它
+        wgpu_ctx
+            .device
+            .poll(wgpu::PollType::wait_indefinitely())
+            .unwrap();
+        wgpu_ctx.draw();
+        let cubes = &wgpu_ctx.models[0];
+        let culling = cubes
+            .culling
+            .as_ref()
+            .expect("GPU 剔除测试需要支持计算着色器的适配器");
+
+        let frustum = wgpu_ctx.camera.frustum();
+        let bounds = cubes.model.bounding_sphere();
+        let expected = cubes
+            .instances
+            .as_slice()
+            .iter()
+            .filter(|instance| {
+                frustum.intersects_sphere(&bounds.transformed(&instance.model_matrix()))
+            })
+            .count() as u32;
+        let visible = culling
+            .read_visible_count(&wgpu_ctx.device, &wgpu_ctx.queue)
+            .unwrap();
+        assert_eq!(visible, expected);
+        assert!(visible > 0 && visible <= cubes.instances.range().end - 2);
+
+        let stats = wgpu_ctx.culling_stats;
+        let cube_meshes = cubes.model.meshes.len() as u32;
+        let culled = cubes.instances.range().end - visible;
+        assert!(stats.meshes_drawn >= cube_meshes);
+        assert!(stats.instances_drawn >= visible * cube_meshes);
+        assert!(stats.instances_culled >= culled * cube_meshes);
+        assert!(culled >= 2);
+    }
+
+    #[test]
+    fn scene_with_bare_or_broken_obj_does_not_panic() {
+        let dir = std::env::temp_dir();
+        let bare = dir.join("wgpu_01_scene_bare.obj");
+        let broken = dir.join("wgpu_01_scene_broken.obj");
+        std::fs::write(&bare, "v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 3\n").unwrap();
+        std::fs::write(&broken, "v 0 0 0\nv 1 0 0\nf 1 2 7\n").unwrap();
+
+        let mut scene = SceneFile::builtin();
+        scene
+            .override_model(None, bare.to_str().unwrap().to_string())
+            .unwrap();
+        scene.validate().unwrap();
+        let ctx = WgpuCtx::new_headless(WIDTH, HEIGHT, &scene, ContextOptions::default());
+        assert!(ctx.is_ok(), "{:?}", ctx.err());
+
+        scene
+            .override_model(None, broken.to_str().unwrap().to_string())
+            .unwrap();
+        let err = WgpuCtx::new_headless(WIDTH, HEIGHT, &scene, ContextOptions::default())
+            .err()
+            .expect("损坏的 OBJ 应返回错误");
+        assert!(format!("{err:?}").contains("无法加载模型"), "{err:?}");
+    }
+
+    #[test]
+    fn compressed_textures_upload_with_all_mips() {
+        let wgpu_ctx = headless_ctx();
+
+        for file_name in ["checker-bc1.ktx2", "checker-bc1.dds"] {
+            let bytes = std::fs::read(crate::resources::asset_path(file_name)).unwrap();
+            let texture = texture::Texture::from_bytes(
+                &wgpu_ctx.device,
+                &wgpu_ctx.queue,
+                None,
+                &bytes,
+                file_name,
+                &texture::TextureOptions::default(),
+            )
+            .unwrap();
+            assert_eq!(texture.view.texture().mip_level_count(), 4, "{file_name}");
+            // 不支持 BC 的适配器上会转码为 RGBA8，但仍保持 sRGB
+            assert!(texture.view.texture().format().is_srgb(), "{file_name}");
+        }
+    }
+}
@@ -1,37 +1,153 @@
-use std::{default, sync::Arc};
+use std::error::Error;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
 
 use crate::data::ControlPoints;
 use wgpu::{RequestAdapterOptions, util::DeviceExt};
 use winit::{
     application::ApplicationHandler,
+    dpi::PhysicalSize,
     event::WindowEvent,
-    event_loop::{ActiveEventLoop, EventLoop},
-    window::{Window, WindowId},
+    event_loop::ActiveEventLoop,
+    window::{Window, WindowAttributes, WindowId},
 };
+
+/// 由命令行决定的后端、适配器和交换链选项
+#[derive(Debug, Clone, Copy)]
+pub struct GpuOptions {
+    pub backends: wgpu::Backends,
+    pub power_preference: wgpu::PowerPreference,
+    pub present_mode: wgpu::PresentMode,
+}
+
+impl Default for GpuOptions {
+    fn default() -> Self {
+        Self {
+            backends: wgpu::Backends::all(),
+            power_preference: wgpu::PowerPreference::default(),
+            present_mode: wgpu::PresentMode::Fifo,
+        }
+    }
+}
+
+#[derive(Default)]
 pub struct BezierApp {
+    /// 在 `resumed` 中创建窗口时使用
+    pub window_attributes: WindowAttributes,
+    pub gpu_options: GpuOptions,
     pub window: Option<Arc<Window>>,
+    /// 设备丢失后用来重新请求适配器
+    pub instance: Option<wgpu::Instance>,
     pub surface: Option<wgpu::Surface<'static>>,
     pub device: Option<wgpu::Device>,
+    /// 由当前设备的丢失回调置位，下一次重绘时重建设备
+    pub device_lost: Arc<AtomicBool>,
     pub queue: Option<wgpu::Queue>,
     pub config: Option<wgpu::SurfaceConfiguration>,
     pub render_pipeline: Option<wgpu::RenderPipeline>,
     pub control_points_buffer: Option<wgpu::Buffer>,
     pub bind_group: Option<wgpu::BindGroup>,
+    /// 创建窗口或图形设备失败时的错误，事件循环退出后由 `main` 输出
+    pub error: Option<Box<dyn Error>>,
 }
 
 impl ApplicationHandler for BezierApp {
     fn resumed(&mut self, event_loop: &ActiveEventLoop) {
-        let window_attributes = Window::default_attributes().with_title("Bezier Curve");
-        let window = Arc::new(event_loop.create_window(window_attributes).unwrap());
-        let instance = wgpu::Instance::default();
+        if let Err(err) = self.init_window(event_loop) {
+            log::error!("初始化失败: {err}");
+            self.error = Some(err);
+            event_loop.exit();
+        }
+    }
+
+    fn window_event(
+        &mut self,
+        event_loop: &ActiveEventLoop,
+        _window_id: WindowId,
+        event: WindowEvent,
+    ) {
+        match event {
+            WindowEvent::CloseRequested => {
+                event_loop.exit();
+            }
+            // 最小化时大小为零，交换链保持原来的配置，恢复后再重新配置
+            WindowEvent::Resized(size) if !is_zero(size) => {
+                if let Some(config) = self.config.as_mut() {
+                    config.width = size.width;
+                    config.height = size.height;
+                    self.configure_surface();
+                    self.request_redraw();
+                }
+            }
+            WindowEvent::RedrawRequested => {
+                if self
+                    .window
+                    .as_ref()
+                    .is_none_or(|window| is_zero(window.inner_size()))
+                {
+                    return;
+                }
+                if self.device_lost.load(Ordering::Acquire) {
+                    if let Err(err) = self.init_device() {
+                        log::error!("重建图形设备失败: {err}");
+                        return;
+                    }
+                    log::info!("图形设备已重建");
+                }
+                match self.render() {
+                    Ok(()) => {}
+                    // 交换链过期或丢失时按当前配置重建，再画一次
+                    Err(err @ (wgpu::SurfaceError::Outdated | wgpu::SurfaceError::Lost)) => {
+                        log::warn!("交换链不可用，重新配置: {err}");
+                        self.configure_surface();
+                        self.request_redraw();
+                    }
+                    Err(wgpu::SurfaceError::Timeout) => {
+                        log::warn!("获取交换链纹理超时，跳过这一帧");
+                        self.request_redraw();
+                    }
+                    Err(err) => log::error!("获取交换链纹理失败，跳过这一帧: {err}"),
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+fn is_zero(size: PhysicalSize<u32>) -> bool {
+    size.width == 0 || size.height == 0
+}
+
+impl BezierApp {
+    /// 创建窗口和 Surface，再初始化图形设备
+    fn init_window(&mut self, event_loop: &ActiveEventLoop) -> Result<(), Box<dyn Error>> {
+        let window = Arc::new(event_loop.create_window(self.window_attributes.clone())?);
+        let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor {
+            backends: self.gpu_options.backends,
+            ..Default::default()
+        });
+
+        let surface = instance.create_surface(&window)?;
+
+        // 将创建的资源存入App结构体
+        self.window = Some(window.clone());
+        // 使用 `wgpu::Surface<'static>` 来满足生命周期要求
+        self.surface = Some(unsafe {
+            std::mem::transmute::<wgpu::Surface<'_>, wgpu::Surface<'static>>(surface)
+        });
+        self.instance = Some(instance);
+        self.init_device()
+    }
 
-        let surface = instance.create_surface(&window).unwrap();
+    /// 请求适配器和设备、配置交换链并创建渲染资源；设备丢失后再次调用即可整体重建
+    fn init_device(&mut self) -> Result<(), Box<dyn Error>> {
+        let instance = self.instance.as_ref().unwrap();
+        let surface = self.surface.as_ref().unwrap();
         let adapter = pollster::block_on(instance.request_adapter(&RequestAdapterOptions {
-            power_preference: wgpu::PowerPreference::default(),
-            compatible_surface: Some(&surface),
+            power_preference: self.gpu_options.power_preference,
+            compatible_surface: Some(surface),
             force_fallback_adapter: false,
-        }))
-        .expect("Failed to find an appropriate adapter.");
+        }))?;
 
         let (device, queue) =
             pollster::block_on(adapter.request_device(&wgpu::wgt::DeviceDescriptor {
@@ -40,30 +156,99 @@ impl ApplicationHandler for BezierApp {
                 required_limits: wgpu::Limits::defaults(),
                 memory_hints: wgpu::MemoryHints::Performance,
                 ..Default::default()
-            }))
-            .expect("Failed to create device.");
+            }))?;
+        let device_lost = Arc::new(AtomicBool::new(false));
+        device.set_device_lost_callback({
+            let device_lost = device_lost.clone();
+            move |reason, message| {
+                log::error!("图形设备丢失 ({reason:?}): {message}");
+                device_lost.store(true, Ordering::Release);
+            }
+        });
 
-        let mut size = window.inner_size();
+        let size = self.window.as_ref().unwrap().inner_size();
         let surface_caps = surface.get_capabilities(&adapter);
         let surface_format = surface_caps.formats[0];
+        let present_mode = if surface_caps
+            .present_modes
+            .contains(&self.gpu_options.present_mode)
+        {
+            self.gpu_options.present_mode
+        } else {
+            log::warn!(
+                "交换链不支持 {:?}，可用: {:?}，改用 Fifo",
+                self.gpu_options.present_mode,
+                surface_caps.present_modes
+            );
+            wgpu::PresentMode::Fifo
+        };
         let surface_config = wgpu::SurfaceConfiguration {
             usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
             format: surface_format,
             width: size.width.max(1),
             height: size.height.max(1),
-            present_mode: wgpu::PresentMode::Fifo,
+            present_mode,
             alpha_mode: wgpu::CompositeAlphaMode::Auto,
             view_formats: vec![],
             desired_maximum_frame_latency: 3,
         };
         surface.configure(&device, &surface_config);
 
+        self.create_render_resources(&device, surface_format);
+        self.device = Some(device);
+        self.queue = Some(queue);
+        self.config = Some(surface_config);
+        self.device_lost = device_lost;
+        Ok(())
+    }
+
+    fn configure_surface(&self) {
+        if let (Some(surface), Some(device), Some(config)) = (
+            self.surface.as_ref(),
+            self.device.as_ref(),
+            self.config.as_ref(),
+        ) {
+            surface.configure(device, config);
+        }
+    }
+
+    fn request_redraw(&self) {
+        if let Some(window) = self.window.as_ref() {
+            window.request_redraw();
+        }
+    }
+
+    fn render(&mut self) -> Result<(), wgpu::SurfaceError> {
+        let surface = self.surface.as_ref().unwrap();
+        let device = self.device.as_ref().unwrap();
+        let queue = self.queue.as_ref().unwrap();
+
+        let frame = surface.get_current_texture()?;
+        let view = frame
+            .texture
+            .create_view(&wgpu::wgt::TextureViewDescriptor::default());
+
+        let mut encoder =
+            device.create_command_encoder(&wgpu::wgt::CommandEncoderDescriptor::default());
+        self.encode_render_pass(&mut encoder, &view);
+
+        queue.submit(std::iter::once(encoder.finish()));
+        frame.present();
+        Ok(())
+    }
+
+    /// 创建曲线渲染所需的 GPU 资源，窗口渲染和离屏渲染共用
+    fn create_render_resources(
+        &mut self,
+        device: &wgpu::Device,
+        surface_format: wgpu::TextureFormat,
+    ) {
         let control_points = ControlPoints {
             points: [
-                [-0.5, -0.5], // P0: 起点
-                [-0.2, 0.8],  // P1: 控制点
-                [0.5, -0.7],  // P2: 控制点
-                [0.7, 0.6],   // P3: 终点
+                [-0.5, -0.5, 0.0, 0.0], // P0: 起点
+                [-0.2, 0.8, 0.0, 0.0],  // P1: 控制点
+                [0.5, -0.7, 0.0, 0.0],  // P2: 控制点
+                [0.7, 0.6, 0.0, 0.0],   // P3: 终点
             ],
         };
 
@@ -76,7 +261,8 @@ impl ApplicationHandler for BezierApp {
         const BEZIER_SHADER_SRC: &str = r#"
 // 定义与控制点结构体匹配的Uniform Buffer
 struct ControlPoints {
-    points: array<vec2f, 4>,
+    // uniform 数组步长须为 16 字节，只使用 xy 分量
+    points: array<vec4f, 4>,
 };
 @group(0) @binding(0)
 var<uniform> ctrl_pts: ControlPoints;
@@ -94,10 +280,10 @@ fn bezier(t: f32) -> vec2f {
     let omt2 = one_minus_t * one_minus_t;
     let omt3 = omt2 * one_minus_t;
 
-    return omt3 * ctrl_pts.points[0]
-         + 3.0 * one_minus_t * t * ctrl_pts.points[1]
-         + 3.0 * omt2 * t2 * ctrl_pts.points[2]
-         + t3 * ctrl_pts.points[3];
+    return omt3 * ctrl_pts.points[0].xy
+         + 3.0 * one_minus_t * t * ctrl_pts.points[1].xy
+         + 3.0 * omt2 * t2 * ctrl_pts.points[2].xy
+         + t3 * ctrl_pts.points[3].xy;
 }
 
 @vertex
@@ -186,79 +372,82 @@ fn fs_main(input: VertexOutput) -> @location(0) vec4f {
             cache: None,
         });
 
-        // 将创建的资源存入App结构体
-        self.window = Some(window.clone());
-        // 使用 `wgpu::Surface<'static>` 来满足生命周期要求
-        self.surface = Some(unsafe { std::mem::transmute(surface) });
-        self.device = Some(device);
-        self.queue = Some(queue);
-        self.config = Some(surface_config);
         self.render_pipeline = Some(render_pipeline);
         self.control_points_buffer = Some(control_points_buffer);
         self.bind_group = Some(bind_group);
     }
 
-    fn window_event(
-        &mut self,
-        event_loop: &ActiveEventLoop,
-        window_id: WindowId,
-        event: WindowEvent,
-    ) {
-        match event {
-            WindowEvent::CloseRequested => {
-                event_loop.exit();
-            }
-            WindowEvent::RedrawRequested => {
-                self.render().expect("Render failed");
-            }
-            _ => {}
-        }
+    /// 把曲线绘制到给定的纹理视图上
+    fn encode_render_pass(&self, encoder: &mut wgpu::CommandEncoder, view: &wgpu::TextureView) {
+        let render_pipeline = self.render_pipeline.as_ref().unwrap();
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Render Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view,
+                depth_slice: None,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color {
+                        r: 0.05,
+                        g: 0.05,
+                        b: 0.05,
+                        a: 1.0,
+                    }),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        render_pass.set_bind_group(0, &self.bind_group, &[]);
+        render_pass.set_pipeline(render_pipeline);
+        render_pass.draw(0..100, 0..1);
     }
 }
 
-impl BezierApp {
-    fn render(&mut self) -> Result<(), wgpu::SurfaceError> {
-        let surface = self.surface.as_ref().unwrap();
-        let device = self.device.as_ref().unwrap();
-        let queue = self.queue.as_ref().unwrap();
-        let render_pipeline = self.render_pipeline.as_ref().unwrap();
-        let config = self.config.as_ref().unwrap();
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
 
-        let frame = surface.get_current_texture()?;
-        let view = frame
-            .texture
-            .create_view(&wgpu::wgt::TextureViewDescriptor::default());
+    const WIDTH: u32 = 320;
+    const HEIGHT: u32 = 240;
 
-        let mut encoder =
-            device.create_command_encoder(&wgpu::wgt::CommandEncoderDescriptor::default());
-        {
-            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                label: Some("Render Pass"),
-                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view: &view,
-                    depth_slice: None,
-                    resolve_target: None,
-                    ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(wgpu::Color {
-                            r: 0.05,
-                            g: 0.05,
-                            b: 0.05,
-                            a: 1.0,
-                        }),
-                        store: wgpu::StoreOp::Store,
-                    },
-                })],
-                depth_stencil_attachment: None,
-                timestamp_writes: None,
-                occlusion_query_set: None,
-            });
-            render_pass.set_bind_group(0, &self.bind_group, &[]);
-            render_pass.set_pipeline(render_pipeline);
-            render_pass.draw(0..100, 0..1);
-        }
+    #[test]
+    fn bezier_curve_matches_golden() {
+        let (device, queue) = golden::headless_device()
+            .expect("找不到可用的图形适配器，基准图测试需要一个图形适配器（软件适配器也可以）");
 
-        queue.submit(std::iter::once(encoder.finish()));
-        frame.present();
-        Ok(())
+        let format = wgpu::TextureFormat::Rgba8UnormSrgb;
+        let mut app = BezierApp::default();
+        app.create_render_resources(&device, format);
+
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("bezier_offscreen_texture"),
+            size: wgpu::Extent3d {
+                width: WIDTH,
+                height: HEIGHT,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+        app.encode_render_pass(&mut encoder, &view);
+        queue.submit(Some(encoder.finish()));
+
+        let frame = golden::capture_texture(&device, &queue, &texture).unwrap();
+        golden::assert_golden(
+            &Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden/bezier_curve.png"),
+            &frame,
+            &golden::Tolerance::default(),
+        );
     }
 }
@@ -0,0 +1,112 @@
+use std::path::PathBuf;
+
+use app_cli::{parse_backends, parse_size};
+use clap::Parser;
+
+pub use app_cli::{PowerPreference, PresentMode, list_adapters};
+
+use crate::bezier_app::GpuOptions;
+
+/// 在顶点着色器中计算并绘制三次贝塞尔曲线
+#[derive(Debug, Parser)]
+#[command(version)]
+pub struct Cli {
+    /// 图形后端，逗号分隔：vulkan、metal、dx12、gl 或 all
+    #[arg(long, value_name = "BACKENDS", default_value = "all", value_parser = parse_backends)]
+    pub backend: wgpu::Backends,
+
+    /// 适配器的功耗偏好
+    #[arg(long, value_enum, default_value_t = PowerPreference::Default)]
+    pub power: PowerPreference,
+
+    /// 交换链的呈现模式，适配器不支持时退回到 fifo
+    #[arg(long, value_enum, default_value_t = PresentMode::Fifo)]
+    pub present_mode: PresentMode,
+
+    /// 窗口大小，例如 1280x720
+    #[arg(long, value_name = "WxH", value_parser = parse_size)]
+    pub size: Option<(u32, u32)>,
+
+    /// 以无边框全屏方式打开窗口
+    #[arg(long)]
+    pub fullscreen: bool,
+
+    /// 窗口标题
+    #[arg(long, default_value = "Bezier Curve")]
+    pub title: String,
+
+    /// 日志级别：off、error、warn、info、debug 或 trace；RUST_LOG 可进一步按模块调整
+    #[arg(long, value_name = "LEVEL", default_value = "debug")]
+    pub log_level: log::LevelFilter,
+
+    /// 追加写入的日志文件，`-` 表示输出到标准错误
+    #[arg(long, value_name = "FILE", default_value = "my_app.log")]
+    pub log_file: PathBuf,
+
+    /// 列出 `--backend` 下所有可用的图形适配器后退出
+    #[arg(long)]
+    pub list_adapters: bool,
+}
+
+impl Cli {
+    pub fn gpu_options(&self) -> GpuOptions {
+        GpuOptions {
+            backends: self.backend,
+            power_preference: self.power.into(),
+            present_mode: self.present_mode.into(),
+        }
+    }
+
+    /// 按 `--log-level` 和 `--log-file` 初始化日志
+    pub fn init_logger(&self) -> anyhow::Result<()> {
+        app_cli::init_logger(self.log_level, &self.log_file)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::CommandFactory;
+
+    #[test]
+    fn cli_definition_is_valid() {
+        Cli::command().debug_assert();
+    }
+
+    #[test]
+    fn options_are_parsed() {
+        let cli = Cli::try_parse_from([
+            "example2",
+            "--backend",
+            "metal,dx12",
+            "--present-mode",
+            "mailbox",
+            "--size",
+            "640x480",
+        ])
+        .unwrap();
+        assert_eq!(cli.backend, wgpu::Backends::METAL | wgpu::Backends::DX12);
+        assert_eq!(cli.present_mode, PresentMode::Mailbox);
+        assert_eq!(cli.size, Some((640, 480)));
+        let options = cli.gpu_options();
+        assert_eq!(options.present_mode, wgpu::PresentMode::Mailbox);
+
+        let cli = Cli::try_parse_from(["example2"]).unwrap();
+        assert_eq!(cli.backend, wgpu::Backends::all());
+        assert_eq!(cli.present_mode, PresentMode::Fifo);
+        assert_eq!(cli.size, None);
+    }
+
+    #[test]
+    fn invalid_values_are_rejected() {
+        for args in [
+            ["--backend", "glide"],
+            ["--size", "640"],
+            ["--size", "640x0"],
+            ["--present-mode", "vsync"],
+        ] {
+            let result = Cli::try_parse_from(std::iter::once("example2").chain(args));
+            assert!(result.is_err(), "{args:?}");
+        }
+    }
+}
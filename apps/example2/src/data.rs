@@ -1,6 +1,7 @@
 #[repr(C)]
 #[derive(Copy,Clone,Debug,bytemuck::Pod,bytemuck::Zeroable)]
 pub(crate) struct ControlPoints{
-  pub   points:[[f32;2];4],
+  // uniform 中数组元素的步长必须是 16 字节，所以每个点用 vec4 存放，只用 xy
+  pub   points:[[f32;4];4],
 }
 
@@ -1,49 +1,47 @@
-use std::fs::OpenOptions;
-use std::io::Write;
-use winit::event_loop::{self, EventLoop};
+use clap::Parser;
+use winit::dpi::PhysicalSize;
+use winit::event_loop::EventLoop;
+use winit::window::{Fullscreen, Window};
 
 use crate::bezier_app::BezierApp;
+use crate::cli::Cli;
 
 mod bezier_app;
+mod cli;
 mod data;
 
 fn main() {
-    // 尝试创建或打开日志文件（以追加模式）
-    let log_file = OpenOptions::new()
-        .create(true)
-        .append(true)
-        .open("my_app.log")
-        .expect("无法打开日志文件");
+    let cli = Cli::parse();
+    if cli.list_adapters {
+        cli::list_adapters(cli.backend);
+        return;
+    }
+    if let Err(err) = cli.init_logger() {
+        eprintln!("{err}");
+        std::process::exit(1);
+    }
 
-    env_logger::builder()
-        .format(|buf, record| {
-            writeln!(
-                buf,
-                "[{} {}] {}",
-                record.level(),
-                record.target(), // 这会显示模块路径（如 wgpu_core）
-                record.args()
-            )
-        })
-        .filter_level(log::LevelFilter::Debug)
-        .filter_module("wgpu_core", log::LevelFilter::Debug)
-        .filter_module("wgpu_hal", log::LevelFilter::Debug)
-        .filter_module("naga", log::LevelFilter::Error)
-        .parse_default_env()
-        .target(env_logger::Target::Pipe(Box::new(log_file)))
-        .init();
+    let mut window_attributes = Window::default_attributes().with_title(&cli.title);
+    if let Some((width, height)) = cli.size {
+        window_attributes = window_attributes.with_inner_size(PhysicalSize::new(width, height));
+    }
+    if cli.fullscreen {
+        window_attributes = window_attributes.with_fullscreen(Some(Fullscreen::Borderless(None)));
+    }
 
-    let event_loop = EventLoop::new().unwrap();
     let mut app = BezierApp {
-        window: None,
-        surface: None,
-        device: None,
-        queue: None,
-        config: None,
-        render_pipeline: None,
-        control_points_buffer: None,
-        bind_group: None,
+        window_attributes,
+        gpu_options: cli.gpu_options(),
+        ..Default::default()
     };
 
-    event_loop.run_app(&mut app).unwrap();
+    let result = EventLoop::new().and_then(|event_loop| event_loop.run_app(&mut app));
+    if let Err(err) = result {
+        eprintln!("事件循环出错: {err}");
+        std::process::exit(1);
+    }
+    if let Some(err) = app.error.take() {
+        eprintln!("初始化失败: {err}");
+        std::process::exit(1);
+    }
 }
@@ -0,0 +1,307 @@
+//! 基准图（golden image）回归测试工具。
+//!
+//! 场景先离屏渲染到固定分辨率，再与仓库中提交的参考图比较：
+//! 逐像素容差统计超差像素比例，SSIM 衡量感知上的结构差异。
+//! 比较失败时把实际输出和差异图写到 `target/golden-diff/` 下便于排查。
+//!
+//! 设置环境变量 `GOLDEN_BLESS=1` 运行测试会用当前输出覆盖参考图。
+
+use anyhow::{Context, bail};
+use std::path::{Path, PathBuf};
+
+pub use readback::capture_texture;
+
+/// 比较时允许的误差
+#[derive(Debug, Clone, Copy)]
+pub struct Tolerance {
+    /// 单个通道允许的最大差值，超过即视为该像素不匹配
+    pub per_channel: u8,
+    /// 允许不匹配像素占总像素的最大比例
+    pub max_mismatched_ratio: f64,
+    /// 亮度 SSIM 的最小值，1.0 表示完全一致
+    pub min_ssim: f64,
+}
+
+impl Default for Tolerance {
+    fn default() -> Self {
+        Self {
+            per_channel: 8,
+            max_mismatched_ratio: 0.002,
+            min_ssim: 0.98,
+        }
+    }
+}
+
+/// 一次比较的统计结果
+#[derive(Debug, Clone, Copy)]
+pub struct Comparison {
+    pub mismatched_pixels: usize,
+    pub total_pixels: usize,
+    pub max_channel_diff: u8,
+    pub ssim: f64,
+}
+
+impl Comparison {
+    pub fn mismatched_ratio(&self) -> f64 {
+        self.mismatched_pixels as f64 / self.total_pixels.max(1) as f64
+    }
+
+    pub fn passes(&self, tolerance: &Tolerance) -> bool {
+        self.mismatched_ratio() <= tolerance.max_mismatched_ratio
+            && self.ssim >= tolerance.min_ssim
+    }
+}
+
+fn max_channel_diff(a: &image::Rgba<u8>, b: &image::Rgba<u8>) -> u8 {
+    a.0.iter()
+        .zip(b.0.iter())
+        .map(|(x, y)| x.abs_diff(*y))
+        .max()
+        .unwrap_or(0)
+}
+
+/// 比较两张同尺寸的图像
+pub fn compare(
+    actual: &image::RgbaImage,
+    expected: &image::RgbaImage,
+    tolerance: &Tolerance,
+) -> anyhow::Result<Comparison> {
+    if actual.dimensions() != expected.dimensions() {
+        bail!(
+            "图像尺寸不一致: 实际 {:?}, 参考 {:?}",
+            actual.dimensions(),
+            expected.dimensions()
+        );
+    }
+
+    let mut mismatched_pixels = 0;
+    let mut max_diff = 0;
+    for (a, e) in actual.pixels().zip(expected.pixels()) {
+        let diff = max_channel_diff(a, e);
+        max_diff = max_diff.max(diff);
+        if diff > tolerance.per_channel {
+            mismatched_pixels += 1;
+        }
+    }
+
+    Ok(Comparison {
+        mismatched_pixels,
+        total_pixels: (actual.width() * actual.height()) as usize,
+        max_channel_diff: max_diff,
+        ssim: ssim(actual, expected),
+    })
+}
+
+fn luma(pixel: &image::Rgba<u8>) -> f64 {
+    let [r, g, b, _] = pixel.0;
+    0.299 * r as f64 + 0.587 * g as f64 + 0.114 * b as f64
+}
+
+/// 在亮度通道上计算平均 SSIM（8x8 窗口，步长 4）
+pub fn ssim(a: &image::RgbaImage, b: &image::RgbaImage) -> f64 {
+    const WINDOW: u32 = 8;
+    const STRIDE: u32 = 4;
+    // 8 位动态范围下的稳定常数
+    const C1: f64 = (0.01 * 255.0) * (0.01 * 255.0);
+    const C2: f64 = (0.03 * 255.0) * (0.03 * 255.0);
+
+    let (width, height) = a.dimensions();
+    if width < WINDOW || height < WINDOW {
+        return if a == b { 1.0 } else { 0.0 };
+    }
+
+    let mut total = 0.0;
+    let mut windows = 0usize;
+    for y0 in (0..=height - WINDOW).step_by(STRIDE as usize) {
+        for x0 in (0..=width - WINDOW).step_by(STRIDE as usize) {
+            let (mut sum_a, mut sum_b, mut sum_aa, mut sum_bb, mut sum_ab) =
+                (0.0, 0.0, 0.0, 0.0, 0.0);
+            for y in y0..y0 + WINDOW {
+                for x in x0..x0 + WINDOW {
+                    let la = luma(a.get_pixel(x, y));
+                    let lb = luma(b.get_pixel(x, y));
+                    sum_a += la;
+                    sum_b += lb;
+                    sum_aa += la * la;
+                    sum_bb += lb * lb;
+                    sum_ab += la * lb;
+                }
+            }
+            let n = (WINDOW * WINDOW) as f64;
+            let mean_a = sum_a / n;
+            let mean_b = sum_b / n;
+            let var_a = sum_aa / n - mean_a * mean_a;
+            let var_b = sum_bb / n - mean_b * mean_b;
+            let cov = sum_ab / n - mean_a * mean_b;
+
+            total += ((2.0 * mean_a * mean_b + C1) * (2.0 * cov + C2))
+                / ((mean_a * mean_a + mean_b * mean_b + C1) * (var_a + var_b + C2));
+            windows += 1;
+        }
+    }
+    total / windows as f64
+}
+
+/// 生成差异图：超出容差的像素标红，其余像素以变暗的灰度显示参考图
+pub fn diff_image(
+    actual: &image::RgbaImage,
+    expected: &image::RgbaImage,
+    per_channel: u8,
+) -> image::RgbaImage {
+    image::RgbaImage::from_fn(actual.width(), actual.height(), |x, y| {
+        let a = actual.get_pixel(x, y);
+        let e = expected.get_pixel(x, y);
+        if max_channel_diff(a, e) > per_channel {
+            image::Rgba([255, 0, 0, 255])
+        } else {
+            let gray = (luma(e) * 0.3) as u8;
+            image::Rgba([gray, gray, gray, 255])
+        }
+    })
+}
+
+fn failure_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("../../target/golden-diff")
+}
+
+fn is_blessing() -> bool {
+    std::env::var_os("GOLDEN_BLESS").is_some_and(|value| value != "0")
+}
+
+/// 将渲染结果与参考图比较，不匹配时写出实际图像和差异图并 panic
+pub fn assert_golden(reference: &Path, actual: &image::RgbaImage, tolerance: &Tolerance) {
+    if let Err(err) = check_golden(reference, actual, tolerance) {
+        panic!("{err:?}");
+    }
+}
+
+fn check_golden(
+    reference: &Path,
+    actual: &image::RgbaImage,
+    tolerance: &Tolerance,
+) -> anyhow::Result<()> {
+    if is_blessing() {
+        if let Some(parent) = reference.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        actual.save(reference)?;
+        eprintln!("已更新参考图 {}", reference.display());
+        return Ok(());
+    }
+
+    let expected = image::open(reference)
+        .with_context(|| {
+            format!(
+                "无法读取参考图 {}，可用 GOLDEN_BLESS=1 重新生成",
+                reference.display()
+            )
+        })?
+        .to_rgba8();
+    let comparison = compare(actual, &expected, tolerance)?;
+    if comparison.passes(tolerance) {
+        return Ok(());
+    }
+
+    let name = reference
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or("golden");
+    let dir = failure_dir();
+    std::fs::create_dir_all(&dir)?;
+    let actual_path = dir.join(format!("{name}.actual.png"));
+    let diff_path = dir.join(format!("{name}.diff.png"));
+    actual.save(&actual_path)?;
+    diff_image(actual, &expected, tolerance.per_channel).save(&diff_path)?;
+
+    bail!(
+        "{} 与参考图不一致: 不匹配像素 {}/{} ({:.4}%), 最大通道差 {}, SSIM {:.5}\n实际输出: {}\n差异图: {}",
+        name,
+        comparison.mismatched_pixels,
+        comparison.total_pixels,
+        comparison.mismatched_ratio() * 100.0,
+        comparison.max_channel_diff,
+        comparison.ssim,
+        actual_path.display(),
+        diff_path.display(),
+    )
+}
+
+/// 请求一个用于离屏渲染的设备，优先使用软件（fallback）适配器。
+///
+/// 没有任何可用适配器时返回 `None`。测试应当因此失败而不是跳过，
+/// 否则在没有 GPU 的 CI 上什么也没检查。
+pub fn headless_device() -> Option<(wgpu::Device, wgpu::Queue)> {
+    let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor {
+        backends: wgpu::Backends::all(),
+        ..Default::default()
+    });
+    let adapter = [true, false].into_iter().find_map(|force_fallback_adapter| {
+        pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
+            power_preference: wgpu::PowerPreference::default(),
+            force_fallback_adapter,
+            compatible_surface: None,
+        }))
+        .ok()
+    })?;
+    pollster::block_on(adapter.request_device(&wgpu::DeviceDescriptor {
+        required_limits: wgpu::Limits::downlevel_webgl2_defaults()
+            .using_resolution(adapter.limits()),
+        ..Default::default()
+    }))
+    .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn checkerboard(width: u32, height: u32) -> image::RgbaImage {
+        image::RgbaImage::from_fn(width, height, |x, y| {
+            if (x / 4 + y / 4) % 2 == 0 {
+                image::Rgba([255, 255, 255, 255])
+            } else {
+                image::Rgba([0, 0, 0, 255])
+            }
+        })
+    }
+
+    #[test]
+    fn identical_images_pass() {
+        let image = checkerboard(32, 32);
+        let comparison = compare(&image, &image, &Tolerance::default()).unwrap();
+        assert_eq!(comparison.mismatched_pixels, 0);
+        assert!((comparison.ssim - 1.0).abs() < 1e-9);
+        assert!(comparison.passes(&Tolerance::default()));
+    }
+
+    #[test]
+    fn small_noise_stays_within_tolerance() {
+        let expected = checkerboard(32, 32);
+        let mut actual = expected.clone();
+        for pixel in actual.pixels_mut().step_by(7) {
+            pixel.0[0] = pixel.0[0].saturating_sub(3);
+        }
+        let comparison = compare(&actual, &expected, &Tolerance::default()).unwrap();
+        assert_eq!(comparison.mismatched_pixels, 0);
+        assert!(comparison.passes(&Tolerance::default()));
+    }
+
+    #[test]
+    fn structural_change_fails() {
+        let expected = checkerboard(32, 32);
+        let actual = image::RgbaImage::from_pixel(32, 32, image::Rgba([128, 128, 128, 255]));
+        let comparison = compare(&actual, &expected, &Tolerance::default()).unwrap();
+        assert!(comparison.ssim < 0.5);
+        assert!(!comparison.passes(&Tolerance::default()));
+
+        let diff = diff_image(&actual, &expected, 8);
+        assert_eq!(diff.get_pixel(0, 0).0, [255, 0, 0, 255]);
+    }
+
+    #[test]
+    fn size_mismatch_is_an_error() {
+        let a = checkerboard(16, 16);
+        let b = checkerboard(32, 16);
+        assert!(compare(&a, &b, &Tolerance::default()).is_err());
+    }
+}
@@ -0,0 +1,61 @@
+//! 把 GPU 纹理读回 CPU 的工具，离屏渲染保存 PNG 和基准图测试共用。
+
+use anyhow::Context;
+
+/// 把 `Rgba8Unorm`/`Rgba8UnormSrgb` 纹理读回为图像。
+///
+/// 纹理先复制到映射用的暂存缓冲区，行字节数需按
+/// `COPY_BYTES_PER_ROW_ALIGNMENT` 对齐，读回后再去掉填充。
+pub fn capture_texture(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    texture: &wgpu::Texture,
+) -> anyhow::Result<image::RgbaImage> {
+    let width = texture.width();
+    let height = texture.height();
+    let unpadded_bytes_per_row = width * 4;
+    let padded_bytes_per_row = unpadded_bytes_per_row
+        .div_ceil(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT)
+        * wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+
+    let staging_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("readback_staging_buffer"),
+        size: (padded_bytes_per_row * height) as wgpu::BufferAddress,
+        usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+        mapped_at_creation: false,
+    });
+
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+    encoder.copy_texture_to_buffer(
+        texture.as_image_copy(),
+        wgpu::TexelCopyBufferInfo {
+            buffer: &staging_buffer,
+            layout: wgpu::TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(padded_bytes_per_row),
+                rows_per_image: Some(height),
+            },
+        },
+        texture.size(),
+    );
+    queue.submit(Some(encoder.finish()));
+
+    let buffer_slice = staging_buffer.slice(..);
+    let (sender, receiver) = std::sync::mpsc::channel();
+    buffer_slice.map_async(wgpu::MapMode::Read, move |result| {
+        let _ = sender.send(result);
+    });
+    device.poll(wgpu::PollType::wait_indefinitely())?;
+    receiver.recv()??;
+
+    let mut pixels = Vec::with_capacity((unpadded_bytes_per_row * height) as usize);
+    {
+        let data = buffer_slice.get_mapped_range();
+        for row in data.chunks(padded_bytes_per_row as usize) {
+            pixels.extend_from_slice(&row[..unpadded_bytes_per_row as usize]);
+        }
+    }
+    staging_buffer.unmap();
+
+    image::RgbaImage::from_raw(width, height, pixels).context("读回的像素数据大小不正确")
+}